@@ -0,0 +1,154 @@
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use deltas::{encode::SourceIndex, patch::Patch};
+
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+fn read_request(stream: &TcpStream) -> io::Result<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "bad request line",
+            ))
+        }
+    };
+    let mut content_length = 0usize;
+    loop {
+        line.clear();
+        reader.read_line(&mut line)?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad length"))?;
+            }
+        }
+    }
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Request { method, path, body })
+}
+
+fn write_response(mut stream: &TcpStream, status: &str, body: &[u8]) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        body.len()
+    )?;
+    stream.write_all(body)
+}
+
+fn handle(stream: TcpStream, file: &Mutex<Vec<u8>>) -> io::Result<()> {
+    let request = read_request(&stream)?;
+    let mut file = file.lock().unwrap();
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/signature") => {
+            write_response(&stream, "200 OK", &SourceIndex::new(&file).to_bytes())
+        }
+        ("POST", "/patch") => match deltas::apply(&*file, &request.body) {
+            Ok(target) => {
+                *file = target;
+                write_response(&stream, "200 OK", &SourceIndex::new(&file).to_bytes())
+            }
+            Err(error) => write_response(&stream, "409 Conflict", error.to_string().as_bytes()),
+        },
+        _ => write_response(&stream, "404 Not Found", b""),
+    }
+}
+
+fn serve(listener: TcpListener, file: Arc<Mutex<Vec<u8>>>) {
+    for stream in listener.incoming().flatten() {
+        if let Err(error) = handle(stream, &file) {
+            eprintln!("request failed: {}", error);
+        }
+    }
+}
+
+fn request(address: &str, method: &str, path: &str, body: &[u8]) -> io::Result<(u16, Vec<u8>)> {
+    let mut stream = TcpStream::connect(address)?;
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        method,
+        path,
+        address,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    let mut response: Vec<u8> = Vec::new();
+    stream.read_to_end(&mut response)?;
+    let split = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no header end"))?;
+    let status = String::from_utf8_lossy(&response[..split])
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no status"))?;
+    Ok((status, response[split + 4..].to_vec()))
+}
+
+/// Serves `GET /signature` with the [`SourceIndex`] of the server's file and applies uploads
+/// from `POST /patch`, answering with the index of the updated file. The client checks the
+/// index against its own copy before encoding against it with [`Patch::new_with_index`].
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let source = std::fs::read("files/source.txt")?;
+    let target = std::fs::read("files/target.txt")?;
+
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let address = listener.local_addr()?.to_string();
+    let file = Arc::new(Mutex::new(source.clone()));
+    thread::spawn({
+        let file = Arc::clone(&file);
+        move || serve(listener, file)
+    });
+
+    let (_, signature) = request(&address, "GET", "/signature", b"")?;
+    let index = SourceIndex::try_from_bytes(&signature)?;
+    if index != SourceIndex::new(&source) {
+        return Err("the server holds a different source".into());
+    }
+    let patch = Patch::new_with_index(&index, &source, &target)
+        .ok_or("source length doesn't match the signature")?
+        .to_bytes();
+    let (status, signature) = request(&address, "POST", "/patch", &patch)?;
+    if status != 200 {
+        return Err(format!("upload failed with status {}", status).into());
+    }
+    assert_eq!(
+        SourceIndex::try_from_bytes(&signature)?,
+        SourceIndex::new(&target)
+    );
+    assert_eq!(*file.lock().unwrap(), target);
+    println!(
+        "sent a {} byte patch for a {} byte file",
+        patch.len(),
+        target.len()
+    );
+
+    let (status, _) = request(&address, "POST", "/patch", &patch)?;
+    assert_eq!(status, 409);
+    Ok(())
+}
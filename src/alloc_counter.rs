@@ -0,0 +1,125 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn count_allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (result, ALLOCATIONS.with(Cell::get) - before)
+}
+
+#[cfg(test)]
+mod alloc_counter_tests {
+    use std::fs;
+
+    use super::*;
+    use crate::patch::Patch;
+
+    fn corpus() -> (Vec<u8>, Vec<u8>) {
+        (
+            fs::read("files/source.txt").unwrap(),
+            fs::read("files/target.txt").unwrap(),
+        )
+    }
+
+    #[test]
+    fn encode_allocations() {
+        let (source, target) = corpus();
+        let (patch, allocations) = count_allocations(|| Patch::new(&source, &target));
+        assert!(
+            allocations <= source.len() + 2 * patch.instructions().len() + 64,
+            "encode made {} allocations",
+            allocations
+        );
+    }
+
+    #[test]
+    fn apply_allocations() {
+        let (source, target) = corpus();
+        let patch = Patch::new(&source, &target);
+        let (_, allocations) = count_allocations(|| patch.apply(&source));
+        assert_eq!(allocations, 1);
+
+        let mut output = Vec::with_capacity(target.len());
+        let (_, allocations) = count_allocations(|| patch.apply_vectored(&source, &mut output));
+        assert!(
+            allocations <= 16,
+            "apply_vectored made {} allocations",
+            allocations
+        );
+    }
+
+    #[test]
+    fn apply_no_alloc_allocations() {
+        let (source, target) = corpus();
+        let patch = Patch::new_with_target_copies(&source, &target).to_bytes();
+        let mut out = vec![0u8; target.len()];
+        let (length, allocations) =
+            count_allocations(|| crate::apply_no_alloc(&source, &patch, &mut out));
+        assert_eq!(length, Ok(target.len()));
+        assert_eq!(allocations, 0);
+    }
+
+    #[test]
+    fn bytes_allocations() {
+        let (source, target) = corpus();
+        let patch = Patch::new(&source, &target);
+        let (bytes, allocations) = count_allocations(|| patch.to_bytes());
+        assert!(allocations <= patch.instructions().len() + 1);
+
+        let (_, allocations) = count_allocations(|| Patch::try_from_bytes(&bytes));
+        assert!(
+            allocations <= 2 * patch.instructions().len(),
+            "try_from_bytes made {} allocations",
+            allocations
+        );
+    }
+
+    #[test]
+    fn apply_batch_allocations() {
+        let sources: Vec<Vec<u8>> = (0..100u8).map(|index| vec![index; 64]).collect();
+        let patches: Vec<Patch> = sources
+            .iter()
+            .map(|source| {
+                let mut target = source.clone();
+                target[10] ^= 0xff;
+                Patch::new(source, &target)
+            })
+            .collect();
+        let mut records = sources.clone();
+        let (result, allocations) =
+            count_allocations(|| crate::batch::apply_batch(&mut records, &patches));
+        assert_eq!(result, Ok(()));
+        assert!(
+            allocations <= 1,
+            "apply_batch made {} allocations",
+            allocations
+        );
+    }
+}
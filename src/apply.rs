@@ -0,0 +1,650 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, Seek, SeekFrom, Write},
+    ops::Range,
+    slice::Iter,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    instructions::{delta_instruction::DeltaInstruction, InstructionContent, InstructionInfo},
+    patch::{Patch, TargetHistory},
+};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct YieldPoint {
+    pub instruction: usize,
+    pub instruction_count: usize,
+    pub target_offset: usize,
+}
+
+#[derive(Default)]
+pub struct ApplyOptions<'a> {
+    pub max_bytes_per_sec: Option<u64>,
+    pub yield_every: Option<usize>,
+    pub on_yield: Option<Box<dyn FnMut(YieldPoint) + 'a>>,
+    pub on_changed: Option<Box<dyn FnMut(Range<usize>) + 'a>>,
+    /// The version currently installed. When set, only a [`VersionedPatch`] whose binding
+    /// allows upgrading from it is applied; plain patches are refused.
+    ///
+    /// [`VersionedPatch`]: crate::version::VersionedPatch
+    pub enforce_versions: Option<u64>,
+}
+
+impl ApplyOptions<'_> {
+    pub(crate) fn yield_point(&mut self, point: YieldPoint) {
+        let Some(yield_every) = self.yield_every.filter(|every| *every > 0) else {
+            return;
+        };
+        if !point.instruction.is_multiple_of(yield_every) {
+            return;
+        }
+        match self.on_yield.as_mut() {
+            Some(on_yield) => on_yield(point),
+            None => thread::yield_now(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ChangeTracker {
+    pending: Option<Range<usize>>,
+}
+
+impl ChangeTracker {
+    pub(crate) fn changed(&mut self, options: &mut ApplyOptions, range: Range<usize>) {
+        if range.is_empty() || options.on_changed.is_none() {
+            return;
+        }
+        match self.pending.as_mut() {
+            Some(pending) if pending.end == range.start => pending.end = range.end,
+            _ => {
+                if let Some(pending) = self.pending.replace(range) {
+                    (options.on_changed.as_mut().unwrap())(pending);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn finish(&mut self, options: &mut ApplyOptions) {
+        if let (Some(pending), Some(on_changed)) =
+            (self.pending.take(), options.on_changed.as_mut())
+        {
+            on_changed(pending);
+        }
+    }
+}
+
+impl std::fmt::Debug for ApplyOptions<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApplyOptions")
+            .field("max_bytes_per_sec", &self.max_bytes_per_sec)
+            .field("yield_every", &self.yield_every)
+            .field("on_yield", &self.on_yield.is_some())
+            .field("on_changed", &self.on_changed.is_some())
+            .field("enforce_versions", &self.enforce_versions)
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Throttle {
+    max_bytes_per_sec: Option<u64>,
+    start: Instant,
+    bytes: u64,
+}
+
+impl Throttle {
+    pub(crate) fn new(max_bytes_per_sec: Option<u64>) -> Self {
+        Self {
+            max_bytes_per_sec,
+            start: Instant::now(),
+            bytes: 0,
+        }
+    }
+
+    pub(crate) fn consume(&mut self, bytes: usize) {
+        let delay = self.delay(bytes, self.start.elapsed());
+        if !delay.is_zero() {
+            thread::sleep(delay);
+        }
+    }
+
+    /// Records `bytes` more and returns how long to wait so that, `elapsed` after the start,
+    /// the rate stays at most `max_bytes_per_sec`.
+    fn delay(&mut self, bytes: usize, elapsed: Duration) -> Duration {
+        let Some(max_bytes_per_sec) = self.max_bytes_per_sec.filter(|rate| *rate > 0) else {
+            return Duration::ZERO;
+        };
+        self.bytes += bytes as u64;
+        let expected = Duration::from_secs_f64(self.bytes as f64 / max_bytes_per_sec as f64);
+        expected.saturating_sub(elapsed)
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct ApplierState {
+    pub instruction: usize,
+    pub source_offset: usize,
+    pub target_offset: usize,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Step {
+    pub instruction: usize,
+    pub applied: DeltaInstruction,
+    pub source: Range<usize>,
+    pub target: Range<usize>,
+    pub output: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct Applier<'a> {
+    patch: &'a Patch,
+    source: &'a [u8],
+    source_iter: Iter<'a, u8>,
+    target: Vec<u8>,
+    instruction: usize,
+}
+
+impl<'a> Applier<'a> {
+    pub fn new(patch: &'a Patch, source: &'a [u8]) -> Option<Self> {
+        if source.len() != patch.source_length() {
+            return None;
+        }
+        Some(Self {
+            patch,
+            source,
+            source_iter: source.iter(),
+            target: Vec::with_capacity(patch.target_length()),
+            instruction: 0,
+        })
+    }
+
+    pub fn state(&self) -> ApplierState {
+        ApplierState {
+            instruction: self.instruction,
+            source_offset: self.source.len() - self.source_iter.len(),
+            target_offset: self.target.len(),
+        }
+    }
+
+    pub fn target(&self) -> &[u8] {
+        &self.target
+    }
+
+    pub fn step(&mut self) -> Option<Step> {
+        let applied = self.patch.instructions().get(self.instruction)?;
+        let before = self.state();
+        applied.apply(&mut self.source_iter, &mut self.target);
+        self.instruction += 1;
+        let after = self.state();
+        Some(Step {
+            instruction: before.instruction,
+            applied: applied.clone(),
+            source: before.source_offset..after.source_offset,
+            target: before.target_offset..after.target_offset,
+            output: self.target[before.target_offset..].to_vec(),
+        })
+    }
+
+    /// Steps until an instruction's output differs from `expected`, returning that step.
+    pub fn step_until_divergence(&mut self, expected: &[u8]) -> Option<Step> {
+        while let Some(step) = self.step() {
+            if expected.get(step.target.clone()) != Some(&step.output[..]) {
+                return Some(step);
+            }
+        }
+        None
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        while self.step().is_some() {}
+        self.target
+    }
+}
+
+/// Largest source read [`Patch::apply_from`] requests at once.
+const MAX_SOURCE_READ: usize = 64 * 1024;
+
+/// Largest write [`Patch::apply_into`] hands to a [`TargetSink`] at once.
+const MAX_TARGET_WRITE: usize = 64 * 1024;
+
+/// Random access to a source that needn't be a contiguous slice, such as a chunk store, a
+/// range requestable URL or a sparse local cache.
+pub trait SourceProvider {
+    fn source_length(&self) -> usize;
+
+    /// Fills `buffer` with the source bytes starting at `offset`.
+    fn read_at(&mut self, offset: usize, buffer: &mut [u8]) -> io::Result<()>;
+}
+
+impl SourceProvider for &[u8] {
+    fn source_length(&self) -> usize {
+        self.len()
+    }
+
+    fn read_at(&mut self, offset: usize, buffer: &mut [u8]) -> io::Result<()> {
+        let bytes = self
+            .get(offset..offset + buffer.len())
+            .ok_or(io::ErrorKind::UnexpectedEof)?;
+        buffer.copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// Destination for target bytes, such as a file, a preallocated mapping or an upload.
+pub trait TargetSink {
+    /// Writes `bytes` after everything written so far.
+    fn append(&mut self, bytes: &[u8]) -> io::Result<()>;
+
+    /// Writes `bytes` at `offset`, for strategies that produce the target out of order.
+    fn write_at(&mut self, offset: usize, bytes: &[u8]) -> io::Result<()>;
+
+    /// Called once after the whole target has been written.
+    fn finalize(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl TargetSink for Vec<u8> {
+    fn append(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn write_at(&mut self, offset: usize, bytes: &[u8]) -> io::Result<()> {
+        if self.len() < offset + bytes.len() {
+            self.resize(offset + bytes.len(), 0);
+        }
+        self[offset..offset + bytes.len()].copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+impl TargetSink for File {
+    fn append(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.write_all(bytes)
+    }
+
+    fn write_at(&mut self, offset: usize, bytes: &[u8]) -> io::Result<()> {
+        let position = self.stream_position()?;
+        self.seek(SeekFrom::Start(offset as u64))?;
+        self.write_all(bytes)?;
+        self.seek(SeekFrom::Start(position)).map(|_| ())
+    }
+
+    fn finalize(&mut self) -> io::Result<()> {
+        self.sync_all()
+    }
+}
+
+#[derive(Debug)]
+pub enum ProviderError {
+    SourceMismatch,
+    Io(io::Error),
+}
+
+impl std::fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProviderError::SourceMismatch => {
+                write!(f, "Source length doesn't match the patch source length")
+            }
+            ProviderError::Io(err) => write!(f, "Reading the source failed: {}", err),
+        }
+    }
+}
+
+impl Error for ProviderError {}
+
+impl From<io::Error> for ProviderError {
+    fn from(err: io::Error) -> Self {
+        ProviderError::Io(err)
+    }
+}
+
+impl Patch {
+    /// Source ranges read by copies, merging adjacent copies into reads of up to
+    /// [`MAX_SOURCE_READ`] bytes.
+    fn source_reads(&self) -> Vec<Range<usize>> {
+        let mut reads: Vec<Range<usize>> = Vec::new();
+        let mut offset = 0usize;
+        for instruction in self.instructions() {
+            let length = instruction.len() as usize;
+            match instruction {
+                DeltaInstruction::Remove(_) => offset += length,
+                DeltaInstruction::Copy(_) if length > 0 => {
+                    match reads.last_mut() {
+                        Some(read)
+                            if read.end == offset && read.len() + length <= MAX_SOURCE_READ =>
+                        {
+                            read.end += length
+                        }
+                        _ => reads.push(offset..offset + length),
+                    }
+                    offset += length;
+                }
+                _ => (),
+            }
+        }
+        reads
+    }
+
+    /// Like [`Patch::apply`], reading the source from `source` only where copies need it.
+    /// Adjacent copies are fetched together and removed bytes are never read.
+    pub fn apply_from<P: SourceProvider + ?Sized>(
+        &self,
+        source: &mut P,
+    ) -> Result<Vec<u8>, ProviderError> {
+        let mut target: Vec<u8> = Vec::with_capacity(self.target_length());
+        self.apply_into(source, &mut target)?;
+        Ok(target)
+    }
+
+    /// Like [`Patch::apply_from`], appending the target to `sink` in writes of up to
+    /// [`MAX_TARGET_WRITE`] bytes and finalizing it. Only the target copy window is kept in
+    /// memory. Returns the target length.
+    pub fn apply_into<P, S>(&self, source: &mut P, sink: &mut S) -> Result<usize, ProviderError>
+    where
+        P: SourceProvider + ?Sized,
+        S: TargetSink + ?Sized,
+    {
+        if source.source_length() != self.source_length() {
+            return Err(ProviderError::SourceMismatch);
+        }
+        let mut reads = self.source_reads().into_iter();
+        let mut window: Vec<u8> = Vec::new();
+        let mut window_start = 0usize;
+        let mut source_offset = 0usize;
+        let mut history = TargetHistory::new(self);
+        let mut pending: Vec<u8> = Vec::with_capacity(MAX_TARGET_WRITE.min(self.target_length()));
+        let mut written = 0usize;
+        for instruction in self.instructions() {
+            let length = instruction.len() as usize;
+            let output = match instruction {
+                DeltaInstruction::Remove(_) => {
+                    source_offset += length;
+                    continue;
+                }
+                DeltaInstruction::Copy(_) if length == 0 => continue,
+                DeltaInstruction::Copy(_) => {
+                    if source_offset + length > window_start + window.len() {
+                        let read = reads.next().ok_or_else(|| {
+                            io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                "copy reads past the planned source reads",
+                            )
+                        })?;
+                        window.resize(read.len(), 0);
+                        source.read_at(read.start, &mut window)?;
+                        window_start = read.start;
+                    }
+                    let start = source_offset - window_start;
+                    source_offset += length;
+                    history.apply(instruction, &mut window[start..start + length].iter())
+                }
+                _ => history.apply(instruction, &mut [].iter()),
+            };
+            if pending.len() + output.len() > MAX_TARGET_WRITE {
+                sink.append(&pending)?;
+                written += pending.len();
+                pending.clear();
+            }
+            pending.extend_from_slice(output);
+        }
+        sink.append(&pending)?;
+        sink.finalize()?;
+        Ok(written + pending.len())
+    }
+}
+
+#[cfg(test)]
+mod apply_tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn applier() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+        let mut applier = Applier::new(&patch, &source).unwrap();
+        assert_eq!(applier.state(), ApplierState::default());
+        let first = applier.step().unwrap();
+        assert_eq!(first.instruction, 0);
+        assert_eq!(&first.applied, &patch.instructions()[0]);
+        assert_eq!(target[first.target.clone()], first.output[..]);
+        assert_eq!(applier.state().instruction, 1);
+        assert_eq!(applier.state().source_offset, first.source.end);
+        assert_eq!(applier.finish(), target);
+        assert!(Applier::new(&patch, &source[1..]).is_none());
+
+        let mut applier = Applier::new(&patch, &source).unwrap();
+        assert_eq!(applier.step_until_divergence(&target), None);
+        assert_eq!(applier.state().target_offset, target.len());
+        assert_eq!(applier.step(), None);
+
+        let mut expected = target.clone();
+        expected[target.len() / 2] ^= 1;
+        let mut applier = Applier::new(&patch, &source).unwrap();
+        let step = applier.step_until_divergence(&expected).unwrap();
+        assert!(step.target.contains(&(target.len() / 2)));
+        assert_eq!(applier.state().target_offset, step.target.end);
+    }
+
+    #[test]
+    fn yield_point() {
+        let mut points: Vec<usize> = Vec::new();
+        let mut options = ApplyOptions {
+            yield_every: Some(3),
+            on_yield: Some(Box::new(|point: YieldPoint| points.push(point.instruction))),
+            ..Default::default()
+        };
+        for instruction in 1..=10 {
+            options.yield_point(YieldPoint {
+                instruction,
+                instruction_count: 10,
+                target_offset: 0,
+            });
+        }
+        drop(options);
+        assert_eq!(points, vec![3, 6, 9]);
+    }
+
+    #[test]
+    fn change_tracker() {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        let mut options = ApplyOptions {
+            on_changed: Some(Box::new(|range| ranges.push(range))),
+            ..Default::default()
+        };
+        let mut tracker = ChangeTracker::default();
+        tracker.changed(&mut options, 0..2);
+        tracker.changed(&mut options, 2..4);
+        tracker.changed(&mut options, 4..4);
+        tracker.changed(&mut options, 6..7);
+        tracker.finish(&mut options);
+        drop(options);
+        assert_eq!(ranges, vec![0..4, 6..7]);
+    }
+
+    #[test]
+    fn throttle() {
+        let mut throttle = Throttle::new(Some(10_000));
+        assert_eq!(
+            throttle.delay(100, Duration::ZERO),
+            Duration::from_millis(10)
+        );
+        assert_eq!(
+            throttle.delay(100, Duration::from_millis(5)),
+            Duration::from_millis(15)
+        );
+        assert_eq!(
+            throttle.delay(100, Duration::from_millis(30)),
+            Duration::ZERO
+        );
+        assert_eq!(
+            throttle.delay(1000, Duration::from_millis(30)),
+            Duration::from_millis(100)
+        );
+
+        for rate in [None, Some(0)] {
+            let mut throttle = Throttle::new(rate);
+            assert_eq!(throttle.delay(usize::MAX, Duration::ZERO), Duration::ZERO);
+            throttle.consume(usize::MAX);
+        }
+    }
+
+    /// A sparse cache holding some chunks of the source, counting reads.
+    struct ChunkCache {
+        length: usize,
+        chunks: Vec<(usize, Vec<u8>)>,
+        reads: Vec<Range<usize>>,
+    }
+
+    impl SourceProvider for ChunkCache {
+        fn source_length(&self) -> usize {
+            self.length
+        }
+
+        fn read_at(&mut self, offset: usize, buffer: &mut [u8]) -> io::Result<()> {
+            self.reads.push(offset..offset + buffer.len());
+            for (position, byte) in (offset..).zip(buffer.iter_mut()) {
+                *byte = self
+                    .chunks
+                    .iter()
+                    .find(|(start, bytes)| (*start..start + bytes.len()).contains(&position))
+                    .map(|(start, bytes)| bytes[position - start])
+                    .ok_or(io::ErrorKind::NotFound)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn apply_from() {
+        let source = fs::read("files/source.txt").unwrap();
+        let mut target = fs::read("files/target.txt").unwrap();
+        target.extend(b"0123456789".repeat(40));
+        let patch = Patch::new_with_target_copies(&source, &target);
+        assert_eq!(patch.apply_from(&mut &source[..]).unwrap(), target);
+
+        let reads = patch.source_reads();
+        let mut cache = ChunkCache {
+            length: source.len(),
+            chunks: reads
+                .iter()
+                .map(|read| (read.start, source[read.clone()].to_vec()))
+                .collect(),
+            reads: Vec::new(),
+        };
+        assert_eq!(patch.apply_from(&mut cache).unwrap(), target);
+        assert_eq!(cache.reads, reads);
+        assert!(reads.len() < patch.instructions().len());
+
+        let mut builder = crate::patch::PatchBuilder::default();
+        builder.copy_unchanged(10);
+        builder.remove(5);
+        builder.copy_unchanged(MAX_SOURCE_READ + 300);
+        let reads = builder.build().source_reads();
+        assert_eq!(reads.len(), 3);
+        assert_eq!(reads[0], 0..10);
+        assert_eq!(reads[1].start, 15);
+        assert!(reads
+            .windows(2)
+            .skip(1)
+            .all(|pair| pair[0].end == pair[1].start));
+        assert!(reads.iter().all(|read| read.len() <= MAX_SOURCE_READ));
+        assert_eq!(reads[2].end, 15 + MAX_SOURCE_READ + 300);
+    }
+
+    #[test]
+    fn apply_from_err() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+        assert!(matches!(
+            patch.apply_from(&mut &source[1..]),
+            Err(ProviderError::SourceMismatch)
+        ));
+        let mut empty = ChunkCache {
+            length: source.len(),
+            chunks: Vec::new(),
+            reads: Vec::new(),
+        };
+        assert!(matches!(
+            patch.apply_from(&mut empty),
+            Err(ProviderError::Io(err)) if err.kind() == io::ErrorKind::NotFound
+        ));
+
+        let patch = Patch::try_from_bytes(b"-\x05|\x00").unwrap();
+        assert_eq!(patch.apply(b"abcde"), Some(Vec::new()));
+        assert_eq!(patch.apply_from(&mut &b"abcde"[..]).unwrap(), b"");
+    }
+
+    /// Records every call, to check how the target is handed over.
+    #[derive(Default)]
+    struct RecordingSink {
+        appends: Vec<usize>,
+        bytes: Vec<u8>,
+        finalized: bool,
+    }
+
+    impl TargetSink for RecordingSink {
+        fn append(&mut self, bytes: &[u8]) -> io::Result<()> {
+            assert!(!self.finalized);
+            self.appends.push(bytes.len());
+            self.bytes.extend_from_slice(bytes);
+            Ok(())
+        }
+
+        fn write_at(&mut self, offset: usize, bytes: &[u8]) -> io::Result<()> {
+            self.bytes.write_at(offset, bytes)
+        }
+
+        fn finalize(&mut self) -> io::Result<()> {
+            self.finalized = true;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn apply_into() {
+        let source = fs::read("files/source.txt").unwrap();
+        let mut target = fs::read("files/target.txt").unwrap();
+        target.extend(b"0123456789".repeat(20_000));
+        let patch = Patch::new_with_target_copies(&source, &target);
+        let mut sink = RecordingSink::default();
+        assert_eq!(
+            patch.apply_into(&mut &source[..], &mut sink).unwrap(),
+            target.len()
+        );
+        assert_eq!(sink.bytes, target);
+        assert!(sink.finalized);
+        assert!(sink.appends.len() > 1);
+        assert!(sink
+            .appends
+            .iter()
+            .all(|length| *length <= MAX_TARGET_WRITE));
+
+        let path = std::env::temp_dir().join(format!("deltas-apply-into-{}", std::process::id()));
+        let mut file = File::create(&path).unwrap();
+        patch.apply_into(&mut &source[..], &mut file).unwrap();
+        file.write_at(2, b"at").unwrap();
+        file.append(b"end").unwrap();
+        drop(file);
+        let mut expected = target.clone();
+        expected.write_at(2, b"at").unwrap();
+        TargetSink::append(&mut expected, b"end").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), expected);
+        fs::remove_file(&path).unwrap();
+
+        let mut grown: Vec<u8> = b"ab".to_vec();
+        grown.write_at(4, b"cd").unwrap();
+        assert_eq!(grown, b"ab\0\0cd");
+    }
+}
@@ -0,0 +1,321 @@
+use std::error::Error;
+
+use crate::{checkpoint::crc32, instructions::InstructionError, patch::Patch};
+
+const BEGIN: &str = "-----BEGIN DELTAS PATCH-----";
+const END: &str = "-----END DELTAS PATCH-----";
+const LINE_LENGTH: usize = 64;
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const Z85: &[u8; 85] =
+    b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ.-:+=^!/*?&<>()[]{}@%$#";
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub enum ArmorEncoding {
+    #[default]
+    Base64,
+    Z85,
+}
+
+impl ArmorEncoding {
+    fn name(&self) -> &'static str {
+        match self {
+            ArmorEncoding::Base64 => "base64",
+            ArmorEncoding::Z85 => "z85",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ArmorError {
+    MissingBegin,
+    MissingEnd,
+    InvalidHeader(String),
+    InvalidCharacter(char),
+    LengthMismatch,
+    ChecksumMismatch,
+    InvalidPatch(InstructionError),
+}
+
+impl std::fmt::Display for ArmorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArmorError::MissingBegin => write!(f, "No {:?} line found", BEGIN),
+            ArmorError::MissingEnd => write!(f, "No {:?} line found", END),
+            ArmorError::InvalidHeader(header) => write!(f, "Invalid armor header {:?}", header),
+            ArmorError::InvalidCharacter(character) => {
+                write!(f, "Invalid armor character {:?}", character)
+            }
+            ArmorError::LengthMismatch => write!(f, "Armored length didn't match the header"),
+            ArmorError::ChecksumMismatch => write!(f, "Armored checksum didn't match"),
+            ArmorError::InvalidPatch(error) => write!(f, "Armored patch is invalid: {}", error),
+        }
+    }
+}
+
+impl Error for ArmorError {}
+
+impl From<InstructionError> for ArmorError {
+    fn from(error: InstructionError) -> Self {
+        ArmorError::InvalidPatch(error)
+    }
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (index, byte)| {
+            group | (*byte as u32) << (16 - 8 * index)
+        });
+        for index in 0..4 {
+            match index <= chunk.len() {
+                true => text.push(BASE64[(group >> (18 - 6 * index)) as usize & 63] as char),
+                false => text.push('='),
+            }
+        }
+    }
+    text
+}
+
+fn decode_base64(text: &[u8]) -> Result<Vec<u8>, ArmorError> {
+    let text = text
+        .strip_suffix(b"==")
+        .or(text.strip_suffix(b"="))
+        .unwrap_or(text);
+    let mut bytes: Vec<u8> = Vec::with_capacity(text.len() / 4 * 3);
+    for chunk in text.chunks(4) {
+        let mut group = 0u32;
+        for (index, character) in chunk.iter().enumerate() {
+            let value = BASE64
+                .iter()
+                .position(|item| item == character)
+                .ok_or(ArmorError::InvalidCharacter(*character as char))?;
+            group |= (value as u32) << (18 - 6 * index);
+        }
+        if chunk.len() == 1 {
+            return Err(ArmorError::LengthMismatch);
+        }
+        bytes.extend(&group.to_be_bytes()[1..chunk.len()]);
+    }
+    Ok(bytes)
+}
+
+fn encode_z85(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(4) * 5);
+    for chunk in bytes.chunks(4) {
+        let mut group = [0u8; 4];
+        group[..chunk.len()].copy_from_slice(chunk);
+        let mut value = u32::from_be_bytes(group);
+        let mut encoded = [0u8; 5];
+        for item in encoded.iter_mut().rev() {
+            *item = Z85[(value % 85) as usize];
+            value /= 85;
+        }
+        text.extend(encoded.iter().map(|item| *item as char));
+    }
+    text
+}
+
+fn decode_z85(text: &[u8]) -> Result<Vec<u8>, ArmorError> {
+    if !text.len().is_multiple_of(5) {
+        return Err(ArmorError::LengthMismatch);
+    }
+    let mut bytes: Vec<u8> = Vec::with_capacity(text.len() / 5 * 4);
+    for chunk in text.chunks(5) {
+        let mut value = 0u64;
+        for character in chunk {
+            let digit = Z85
+                .iter()
+                .position(|item| item == character)
+                .ok_or(ArmorError::InvalidCharacter(*character as char))?;
+            value = value * 85 + digit as u64;
+        }
+        let value = u32::try_from(value).map_err(|_| ArmorError::LengthMismatch)?;
+        bytes.extend(value.to_be_bytes());
+    }
+    Ok(bytes)
+}
+
+pub fn armor(bytes: &[u8], encoding: ArmorEncoding) -> String {
+    let body = match encoding {
+        ArmorEncoding::Base64 => encode_base64(bytes),
+        ArmorEncoding::Z85 => encode_z85(bytes),
+    };
+    let mut text = format!(
+        "{}\nEncoding: {}\nLength: {}\nChecksum: {:08x}\n\n",
+        BEGIN,
+        encoding.name(),
+        bytes.len(),
+        crc32(0, bytes)
+    );
+    for line in body.as_bytes().chunks(LINE_LENGTH) {
+        text.push_str(std::str::from_utf8(line).unwrap());
+        text.push('\n');
+    }
+    text.push_str(END);
+    text.push('\n');
+    text
+}
+
+/// Decodes [`armor`] output, ignoring text around the armor, blank lines and any whitespace
+/// transports insert into the body.
+pub fn dearmor(text: &str) -> Result<Vec<u8>, ArmorError> {
+    let start = text.find(BEGIN).ok_or(ArmorError::MissingBegin)? + BEGIN.len();
+    let end = start + text[start..].find(END).ok_or(ArmorError::MissingEnd)?;
+    let armored = &text[start..end];
+    let mut lines = armored
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| line.is_empty());
+    let has_headers = armored.lines().any(|line| line.trim().is_empty())
+        && armored
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .is_some_and(|line| line.contains(": "));
+
+    let mut encoding = ArmorEncoding::Base64;
+    let mut length: Option<usize> = None;
+    let mut checksum: Option<u32> = None;
+    if has_headers {
+        for line in lines.by_ref() {
+            if line.is_empty() {
+                break;
+            }
+            let invalid = || ArmorError::InvalidHeader(line.to_string());
+            let (name, value) = line.split_once(':').ok_or_else(invalid)?;
+            let value = value.trim();
+            match name.trim().to_ascii_lowercase().as_str() {
+                "encoding" if value.eq_ignore_ascii_case("base64") => {
+                    encoding = ArmorEncoding::Base64
+                }
+                "encoding" if value.eq_ignore_ascii_case("z85") => encoding = ArmorEncoding::Z85,
+                "length" => length = Some(value.parse().map_err(|_| invalid())?),
+                "checksum" => {
+                    checksum = Some(u32::from_str_radix(value, 16).map_err(|_| invalid())?)
+                }
+                _ => return Err(invalid()),
+            }
+        }
+    }
+
+    let body: Vec<u8> = lines
+        .flat_map(str::bytes)
+        .filter(|byte| !byte.is_ascii_whitespace())
+        .collect();
+    let mut bytes = match encoding {
+        ArmorEncoding::Base64 => decode_base64(&body)?,
+        ArmorEncoding::Z85 => decode_z85(&body)?,
+    };
+    if let Some(length) = length {
+        if length > bytes.len() || (encoding == ArmorEncoding::Base64 && length != bytes.len()) {
+            return Err(ArmorError::LengthMismatch);
+        }
+        bytes.truncate(length);
+    }
+    if checksum.is_some_and(|checksum| checksum != crc32(0, &bytes)) {
+        return Err(ArmorError::ChecksumMismatch);
+    }
+    Ok(bytes)
+}
+
+impl Patch {
+    pub fn to_armored(&self, encoding: ArmorEncoding) -> String {
+        armor(&self.to_bytes(), encoding)
+    }
+
+    pub fn try_from_armored(text: &str) -> Result<Self, ArmorError> {
+        Ok(Patch::try_from_bytes(&dearmor(text)?)?)
+    }
+}
+
+#[cfg(test)]
+mod armor_tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn codecs() {
+        assert_eq!(encode_base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(encode_base64(b"fooba"), "Zm9vYmE=");
+        assert_eq!(encode_base64(b"foob"), "Zm9vYg==");
+        assert_eq!(decode_base64(b"Zm9vYg==").unwrap(), b"foob");
+        assert_eq!(decode_base64(b"Zm9vYmE").unwrap(), b"fooba");
+        assert_eq!(
+            decode_base64(b"Zm9v!"),
+            Err(ArmorError::InvalidCharacter('!'))
+        );
+        let hello = [0x86, 0x4F, 0xD2, 0x6F, 0xB5, 0x59, 0xF7, 0x5B];
+        assert_eq!(encode_z85(&hello), "HelloWorld");
+        assert_eq!(decode_z85(b"HelloWorld").unwrap(), hello);
+        assert_eq!(decode_z85(b"Hello~"), Err(ArmorError::LengthMismatch));
+    }
+
+    #[test]
+    fn armored() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+        for encoding in [ArmorEncoding::Base64, ArmorEncoding::Z85] {
+            let text = patch.to_armored(encoding);
+            assert!(text
+                .lines()
+                .all(|line| line.len() <= LINE_LENGTH.max(BEGIN.len())));
+            assert_eq!(Patch::try_from_armored(&text), Ok(patch.clone()));
+
+            let mangled = format!(
+                "Hi,\r\nthe patch:\r\n\r\n{}\r\nThanks",
+                text.lines()
+                    .map(|line| match line.starts_with('-') || line.contains(": ") {
+                        true => format!("  {}", line),
+                        false =>
+                            line.split_at(line.len() / 2).0.to_string()
+                                + " \t"
+                                + line.split_at(line.len() / 2).1,
+                    })
+                    .collect::<Vec<String>>()
+                    .join("\r\n")
+            );
+            assert_eq!(Patch::try_from_armored(&mangled), Ok(patch.clone()));
+        }
+        for length in 0..6 {
+            let bytes: Vec<u8> = (0..length).collect();
+            for encoding in [ArmorEncoding::Base64, ArmorEncoding::Z85] {
+                assert_eq!(dearmor(&armor(&bytes, encoding)), Ok(bytes.clone()));
+            }
+        }
+    }
+
+    #[test]
+    fn dearmor_err() {
+        let text = armor(b"delta patch", ArmorEncoding::Base64);
+        assert_eq!(dearmor("nothing"), Err(ArmorError::MissingBegin));
+        assert_eq!(
+            dearmor(&text[..text.len() - 10]),
+            Err(ArmorError::MissingEnd)
+        );
+        assert_eq!(
+            dearmor(&text.replace(
+                &format!("{:08x}", crc32(0, b"delta patch")),
+                &format!("{:08x}", crc32(0, b"delta patch") ^ 1)
+            )),
+            Err(ArmorError::ChecksumMismatch)
+        );
+        assert_eq!(
+            dearmor(&text.replace("Length: 11", "Length: 10")),
+            Err(ArmorError::LengthMismatch)
+        );
+        assert_eq!(
+            dearmor(&text.replace("Encoding", "Compression")),
+            Err(ArmorError::InvalidHeader(String::from(
+                "Compression: base64"
+            )))
+        );
+        let bare = format!("{}\n{}\n{}", BEGIN, encode_base64(b"delta"), END);
+        assert_eq!(dearmor(&bare), Ok(b"delta".to_vec()));
+        assert_eq!(
+            Patch::try_from_armored(&bare),
+            Err(ArmorError::InvalidPatch(InstructionError::InvalidSign))
+        );
+    }
+}
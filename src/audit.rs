@@ -0,0 +1,141 @@
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use crate::{digest::Digest, patch::Patch};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Verification {
+    Unchecked,
+    Passed,
+    Failed,
+}
+
+impl Verification {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Verification::Unchecked => "unchecked",
+            Verification::Passed => "passed",
+            Verification::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ApplyAudit<O> {
+    pub patch_id: O,
+    pub source_digest: O,
+    pub target_digest: Option<O>,
+    pub source_length: usize,
+    pub target_length: Option<usize>,
+    pub duration: Duration,
+    pub verification: Verification,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+impl<O: AsRef<[u8]>> ApplyAudit<O> {
+    pub fn to_json(&self) -> String {
+        let optional = |value: Option<String>| value.unwrap_or_else(|| String::from("null"));
+        format!(
+            "{{\"patch_id\":\"{}\",\"source_digest\":\"{}\",\"target_digest\":{},\"source_length\":{},\"target_length\":{},\"duration_ns\":{},\"verification\":\"{}\"}}",
+            hex(self.patch_id.as_ref()),
+            hex(self.source_digest.as_ref()),
+            optional(self.target_digest.as_ref().map(|digest| format!("\"{}\"", hex(digest.as_ref())))),
+            self.source_length,
+            optional(self.target_length.map(|length| length.to_string())),
+            self.duration.as_nanos(),
+            self.verification.as_str(),
+        )
+    }
+
+    pub fn write_json<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(self.to_json().as_bytes())?;
+        writer.write_all(b"\n")
+    }
+}
+
+impl Patch {
+    pub fn apply_audited<D: Digest>(
+        &self,
+        source: &[u8],
+        expected_target_digest: Option<&[u8]>,
+    ) -> (Option<Vec<u8>>, ApplyAudit<D::Output>) {
+        let start = Instant::now();
+        let target = self.apply(source);
+        let duration = start.elapsed();
+        let target_digest = target.as_deref().map(D::digest);
+        let verification = match (expected_target_digest, &target_digest) {
+            (None, _) => Verification::Unchecked,
+            (Some(expected), Some(digest)) if digest.as_ref() == expected => Verification::Passed,
+            (Some(_), _) => Verification::Failed,
+        };
+        let audit = ApplyAudit {
+            patch_id: D::digest(&self.to_bytes()),
+            source_digest: D::digest(source),
+            target_digest,
+            source_length: source.len(),
+            target_length: target.as_ref().map(Vec::len),
+            duration,
+            verification,
+        };
+        (target, audit)
+    }
+}
+
+#[cfg(test)]
+mod audit_tests {
+    use std::fs;
+
+    use super::*;
+    use crate::digest::digest_tests::Fnv1a;
+
+    #[test]
+    fn apply_audited() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+
+        let (applied, audit) = patch.apply_audited::<Fnv1a>(&source, None);
+        assert_eq!(applied.as_ref(), Some(&target));
+        assert_eq!(audit.patch_id, Fnv1a::digest(&patch.to_bytes()));
+        assert_eq!(audit.source_digest, Fnv1a::digest(&source));
+        assert_eq!(audit.target_digest, Some(Fnv1a::digest(&target)));
+        assert_eq!(audit.target_length, Some(target.len()));
+        assert_eq!(audit.verification, Verification::Unchecked);
+
+        let expected = Fnv1a::digest(&target);
+        let (_, audit) = patch.apply_audited::<Fnv1a>(&source, Some(&expected));
+        assert_eq!(audit.verification, Verification::Passed);
+        let (_, audit) = patch.apply_audited::<Fnv1a>(&source, Some(&[0; 8]));
+        assert_eq!(audit.verification, Verification::Failed);
+
+        let (applied, audit) = patch.apply_audited::<Fnv1a>(&source[1..], Some(&expected));
+        assert_eq!(applied, None);
+        assert_eq!(audit.target_digest, None);
+        assert_eq!(audit.verification, Verification::Failed);
+    }
+
+    #[test]
+    fn to_json() {
+        let audit = ApplyAudit {
+            patch_id: [0xab, 0x01],
+            source_digest: [0x00, 0xff],
+            target_digest: None,
+            source_length: 3,
+            target_length: None,
+            duration: Duration::from_micros(5),
+            verification: Verification::Failed,
+        };
+        assert_eq!(
+            audit.to_json(),
+            "{\"patch_id\":\"ab01\",\"source_digest\":\"00ff\",\"target_digest\":null,\"source_length\":3,\"target_length\":null,\"duration_ns\":5000,\"verification\":\"failed\"}"
+        );
+        let mut written: Vec<u8> = Vec::new();
+        audit.write_json(&mut written).unwrap();
+        assert_eq!(written, format!("{}\n", audit.to_json()).into_bytes());
+    }
+}
@@ -0,0 +1,174 @@
+use std::{error::Error, mem, thread};
+
+use crate::{instructions::InstructionContent, patch::Patch};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum BatchError {
+    LengthMismatch {
+        records: usize,
+        patches: usize,
+    },
+    /// Indices of the records whose length didn't match their patch source length. These
+    /// records are left unchanged, every other record is patched.
+    SourceMismatch(Vec<usize>),
+}
+
+impl std::fmt::Display for BatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BatchError::LengthMismatch { records, patches } => {
+                write!(f, "Got {} records but {} patches", records, patches)
+            }
+            BatchError::SourceMismatch(indices) => write!(
+                f,
+                "{} records don't match their patch source length",
+                indices.len()
+            ),
+        }
+    }
+}
+
+impl Error for BatchError {}
+
+/// Patches `records[index]` with `patches[index]` in place.
+///
+/// Each target is built in a scratch buffer that is then swapped with the record, so the
+/// record's old allocation becomes the scratch buffer for the next one. When records keep
+/// roughly the same size, as they do during compaction, the whole batch needs about one
+/// allocation.
+pub fn apply_batch(records: &mut [Vec<u8>], patches: &[Patch]) -> Result<(), BatchError> {
+    check_lengths(records, patches)?;
+    let mut failed: Vec<usize> = Vec::new();
+    apply_records(records, patches, 0, &mut failed);
+    finish(failed)
+}
+
+/// [`apply_batch`] split into contiguous runs over up to `threads` scoped threads, each with
+/// its own scratch buffer.
+pub fn apply_batch_parallel(
+    records: &mut [Vec<u8>],
+    patches: &[Patch],
+    threads: usize,
+) -> Result<(), BatchError> {
+    check_lengths(records, patches)?;
+    let run = records.len().div_ceil(threads.max(1)).max(1);
+    let failed = thread::scope(|scope| {
+        let workers: Vec<_> = records
+            .chunks_mut(run)
+            .zip(patches.chunks(run))
+            .enumerate()
+            .map(|(index, (records, patches))| {
+                scope.spawn(move || {
+                    let mut failed: Vec<usize> = Vec::new();
+                    apply_records(records, patches, index * run, &mut failed);
+                    failed
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect()
+    });
+    finish(failed)
+}
+
+fn check_lengths(records: &[Vec<u8>], patches: &[Patch]) -> Result<(), BatchError> {
+    match records.len() == patches.len() {
+        true => Ok(()),
+        false => Err(BatchError::LengthMismatch {
+            records: records.len(),
+            patches: patches.len(),
+        }),
+    }
+}
+
+fn apply_records(
+    records: &mut [Vec<u8>],
+    patches: &[Patch],
+    offset: usize,
+    failed: &mut Vec<usize>,
+) {
+    let mut scratch: Vec<u8> = Vec::new();
+    for (index, (record, patch)) in records.iter_mut().zip(patches).enumerate() {
+        if record.len() != patch.source_length() {
+            failed.push(offset + index);
+            continue;
+        }
+        scratch.clear();
+        scratch.reserve(patch.target_length());
+        let mut source = record.iter();
+        for instruction in patch.instructions() {
+            instruction.apply(&mut source, &mut scratch);
+        }
+        mem::swap(record, &mut scratch);
+    }
+}
+
+fn finish(failed: Vec<usize>) -> Result<(), BatchError> {
+    match failed.is_empty() {
+        true => Ok(()),
+        false => Err(BatchError::SourceMismatch(failed)),
+    }
+}
+
+#[cfg(test)]
+mod batch_tests {
+    use super::*;
+
+    fn records() -> (Vec<Vec<u8>>, Vec<Vec<u8>>, Vec<Patch>) {
+        let sources: Vec<Vec<u8>> = (0..50usize)
+            .map(|index| format!("record {} value {}", index, index * 3).into_bytes())
+            .collect();
+        let targets: Vec<Vec<u8>> = (0..50usize)
+            .map(|index| format!("record {} value {}!", index, index * 7).into_bytes())
+            .collect();
+        let patches = sources
+            .iter()
+            .zip(&targets)
+            .map(|(source, target)| Patch::new_with_target_copies(source, target))
+            .collect();
+        (sources, targets, patches)
+    }
+
+    #[test]
+    fn apply_batch() {
+        let (sources, targets, patches) = records();
+        let mut batch = sources.clone();
+        assert_eq!(super::apply_batch(&mut batch, &patches), Ok(()));
+        assert_eq!(batch, targets);
+        for threads in [0, 1, 3, 8, 100] {
+            let mut batch = sources.clone();
+            assert_eq!(apply_batch_parallel(&mut batch, &patches, threads), Ok(()));
+            assert_eq!(batch, targets);
+        }
+        assert_eq!(super::apply_batch(&mut [], &[]), Ok(()));
+    }
+
+    #[test]
+    fn apply_batch_err() {
+        let (sources, targets, patches) = records();
+        assert_eq!(
+            super::apply_batch(&mut sources.clone(), &patches[1..]),
+            Err(BatchError::LengthMismatch {
+                records: 50,
+                patches: 49
+            })
+        );
+
+        let mut batch = sources.clone();
+        batch[4].push(b'x');
+        batch[31].clear();
+        let expected = Err(BatchError::SourceMismatch(vec![4, 31]));
+        let mut parallel = batch.clone();
+        assert_eq!(super::apply_batch(&mut batch, &patches), expected);
+        assert_eq!(apply_batch_parallel(&mut parallel, &patches, 4), expected);
+        for (index, record) in batch.iter().enumerate() {
+            match index {
+                4 | 31 => assert_ne!(record, &targets[index]),
+                _ => assert_eq!(record, &targets[index]),
+            }
+        }
+        assert_eq!(parallel, batch);
+    }
+}
@@ -0,0 +1,240 @@
+use crate::instructions::{InstructionError, Result};
+
+const BLOCK_SIZE: usize = 64;
+const BIT_PATCH_SIGN: u8 = b'^';
+const HEADER_LENGTH: usize = 17;
+
+#[derive(Debug, PartialEq, Clone)]
+struct Block {
+    index: usize,
+    mask: u64,
+    residue: Vec<u8>,
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct BitPatch {
+    source_length: usize,
+    target_length: usize,
+    blocks: Vec<Block>,
+    tail: Vec<u8>,
+}
+
+impl BitPatch {
+    pub fn new(source: &[u8], target: &[u8]) -> Self {
+        let shared = source.len().min(target.len());
+        let mut blocks: Vec<Block> = Vec::new();
+        for (index, (source, target)) in source[..shared]
+            .chunks(BLOCK_SIZE)
+            .zip(target[..shared].chunks(BLOCK_SIZE))
+            .enumerate()
+        {
+            let mut block = Block {
+                index,
+                mask: 0,
+                residue: Vec::new(),
+            };
+            for (position, (source, target)) in source.iter().zip(target).enumerate() {
+                if source != target {
+                    block.mask |= 1 << position;
+                    block.residue.push(source ^ target);
+                }
+            }
+            if block.mask != 0 {
+                blocks.push(block);
+            }
+        }
+        Self {
+            source_length: source.len(),
+            target_length: target.len(),
+            blocks,
+            tail: target[shared..].to_vec(),
+        }
+    }
+
+    pub fn source_length(&self) -> usize {
+        self.source_length
+    }
+
+    pub fn target_length(&self) -> usize {
+        self.target_length
+    }
+
+    pub fn flipped_bits(&self) -> usize {
+        self.blocks
+            .iter()
+            .flat_map(|block| block.residue.iter())
+            .map(|residue| residue.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn apply(&self, source: &[u8]) -> Option<Vec<u8>> {
+        if source.len() != self.source_length {
+            return None;
+        }
+        let mut target = source[..self.source_length.min(self.target_length)].to_vec();
+        for block in self.blocks.iter() {
+            let start = block.index * BLOCK_SIZE;
+            let mut residue = block.residue.iter();
+            for position in 0..BLOCK_SIZE {
+                if block.mask & (1 << position) != 0 {
+                    target[start + position] ^= residue.next().unwrap();
+                }
+            }
+        }
+        target.extend_from_slice(&self.tail);
+        Some(target)
+    }
+
+    fn block_count(&self) -> usize {
+        self.source_length
+            .min(self.target_length)
+            .div_ceil(BLOCK_SIZE)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(HEADER_LENGTH + self.block_count() / 8 + 1);
+        bytes.push(BIT_PATCH_SIGN);
+        bytes.extend((self.source_length as u64).to_be_bytes());
+        bytes.extend((self.target_length as u64).to_be_bytes());
+        let mut changed = vec![0u8; self.block_count().div_ceil(8)];
+        for block in self.blocks.iter() {
+            changed[block.index / 8] |= 1 << (block.index % 8);
+        }
+        bytes.extend(changed);
+        for block in self.blocks.iter() {
+            bytes.extend(block.mask.to_be_bytes());
+            bytes.extend(block.residue.iter());
+        }
+        bytes.extend(self.tail.iter());
+        bytes
+    }
+
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self> {
+        match bytes.first() {
+            Some(&BIT_PATCH_SIGN) => (),
+            Some(_) => return Err(InstructionError::InvalidSign),
+            None => return Err(InstructionError::MissignSign),
+        }
+        let header = bytes
+            .get(1..HEADER_LENGTH)
+            .ok_or(InstructionError::MissingLength)?;
+        let length = |bytes: &[u8]| {
+            usize::try_from(u64::from_be_bytes(bytes.try_into().unwrap()))
+                .map_err(|_| InstructionError::InvalidLength)
+        };
+        let mut patch = Self {
+            source_length: length(&header[..8])?,
+            target_length: length(&header[8..])?,
+            ..Default::default()
+        };
+        let block_count = patch.block_count();
+        let mut offset = HEADER_LENGTH;
+        let changed = bytes
+            .get(offset..offset + block_count.div_ceil(8))
+            .ok_or(InstructionError::MissingContent)?;
+        offset += changed.len();
+        let shared = patch.source_length.min(patch.target_length);
+        for index in 0..block_count {
+            if changed[index / 8] & (1 << (index % 8)) == 0 {
+                continue;
+            }
+            let mask = bytes
+                .get(offset..offset + 8)
+                .ok_or(InstructionError::MissingContent)?;
+            let mask = u64::from_be_bytes(mask.try_into().unwrap());
+            let block_length = (shared - index * BLOCK_SIZE).min(BLOCK_SIZE);
+            if mask == 0 || (block_length < BLOCK_SIZE && mask >> block_length != 0) {
+                return Err(InstructionError::InvalidContent);
+            }
+            offset += 8;
+            let residue = bytes
+                .get(offset..offset + mask.count_ones() as usize)
+                .ok_or(InstructionError::MissingContent)?;
+            offset += residue.len();
+            patch.blocks.push(Block {
+                index,
+                mask,
+                residue: residue.to_vec(),
+            });
+        }
+        let tail_length = patch.target_length - shared;
+        let end = offset
+            .checked_add(tail_length)
+            .ok_or(InstructionError::MissingContent)?;
+        patch.tail = bytes
+            .get(offset..end)
+            .ok_or(InstructionError::MissingContent)?
+            .to_vec();
+        if end != bytes.len() {
+            return Err(InstructionError::InvalidContent);
+        }
+        Ok(patch)
+    }
+}
+
+#[cfg(test)]
+mod bitdiff_tests {
+    use super::*;
+
+    fn dump() -> (Vec<u8>, Vec<u8>) {
+        let source: Vec<u8> = (0..10_000u32).map(|item| (item * 31 % 251) as u8).collect();
+        let mut target = source.clone();
+        for position in [3, 64, 65, 4_000, 9_999] {
+            target[position] ^= 1 << (position % 8);
+        }
+        (source, target)
+    }
+
+    #[test]
+    fn new_apply() {
+        let (source, target) = dump();
+        let patch = BitPatch::new(&source, &target);
+        assert_eq!(patch.flipped_bits(), 5);
+        assert_eq!(patch.apply(&source), Some(target.clone()));
+        assert_eq!(patch.apply(&source[1..]), None);
+        assert!(patch.to_bytes().len() < 128);
+
+        let patch = BitPatch::new(&source, &target[..5_000]);
+        assert_eq!(patch.apply(&source), Some(target[..5_000].to_vec()));
+        let mut grown = target.clone();
+        grown.extend(b"tail");
+        let patch = BitPatch::new(&source, &grown);
+        assert_eq!(patch.apply(&source), Some(grown));
+    }
+
+    #[test]
+    fn bytes() {
+        let (source, mut target) = dump();
+        target.extend(b"tail");
+        let patch = BitPatch::new(&source, &target);
+        let bytes = patch.to_bytes();
+        assert_eq!(BitPatch::try_from_bytes(&bytes), Ok(patch));
+        assert_eq!(
+            BitPatch::try_from_bytes(&BitPatch::default().to_bytes()),
+            Ok(BitPatch::default())
+        );
+
+        assert_eq!(
+            BitPatch::try_from_bytes(&[]),
+            Err(InstructionError::MissignSign)
+        );
+        assert_eq!(
+            BitPatch::try_from_bytes(b"+"),
+            Err(InstructionError::InvalidSign)
+        );
+        assert_eq!(
+            BitPatch::try_from_bytes(&bytes[..10]),
+            Err(InstructionError::MissingLength)
+        );
+        assert_eq!(
+            BitPatch::try_from_bytes(&bytes[..bytes.len() - 1]),
+            Err(InstructionError::MissingContent)
+        );
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            BitPatch::try_from_bytes(&trailing),
+            Err(InstructionError::InvalidContent)
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    os::unix::fs::{FileExt, OpenOptionsExt},
+    path::Path,
+    sync::{mpsc, Mutex},
+    thread,
+};
+
+use crate::patch::Patch;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BlockDeviceOptions {
+    pub alignment: usize,
+    pub queue_depth: usize,
+    pub direct: bool,
+}
+
+impl Default for BlockDeviceOptions {
+    fn default() -> Self {
+        Self {
+            alignment: 4096,
+            queue_depth: 4,
+            direct: true,
+        }
+    }
+}
+
+struct AlignedBuffer {
+    bytes: Vec<u8>,
+    offset: usize,
+    length: usize,
+}
+
+impl AlignedBuffer {
+    fn new(length: usize, alignment: usize) -> Self {
+        let bytes = vec![0u8; length + alignment];
+        let offset = bytes.as_ptr().align_offset(alignment);
+        Self {
+            bytes,
+            offset,
+            length,
+        }
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &self.bytes[self.offset..self.offset + self.length]
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.bytes[self.offset..self.offset + self.length]
+    }
+}
+
+fn write_block(device: &File, block: u64, buffer: &AlignedBuffer) -> io::Result<()> {
+    device.write_all_at(buffer.as_slice(), block * buffer.length as u64)
+}
+
+pub fn apply_to_block_device<P: AsRef<Path>>(
+    source: &[u8],
+    patch: &Patch,
+    path: P,
+    options: &BlockDeviceOptions,
+) -> io::Result<()> {
+    if !options.alignment.is_power_of_two() || options.queue_depth == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Alignment must be a power of two and queue depth greater than zero",
+        ));
+    }
+    let mut open_options = OpenOptions::new();
+    open_options.read(true).write(true);
+    if options.direct {
+        open_options.custom_flags(libc::O_DIRECT);
+    }
+    let device = open_options.open(path)?;
+
+    let alignment = options.alignment;
+    let (sender, receiver) = mpsc::sync_channel::<(u64, AlignedBuffer)>(options.queue_depth);
+    let receiver = Mutex::new(receiver);
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..options.queue_depth)
+            .map(|_| {
+                scope.spawn(|| -> io::Result<()> {
+                    loop {
+                        let job = receiver.lock().unwrap().recv();
+                        match job {
+                            Ok((block, buffer)) => write_block(&device, block, &buffer)?,
+                            Err(_) => return Ok(()),
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        let mut result: io::Result<()> = Ok(());
+        let applied = patch.apply_aligned(source, alignment, |block, bytes| {
+            if result.is_err() {
+                return;
+            }
+            let mut buffer = AlignedBuffer::new(alignment, alignment);
+            if bytes.len() < alignment {
+                if let Err(err) = device.read_at(buffer.as_mut_slice(), (block * alignment) as u64)
+                {
+                    result = Err(err);
+                    return;
+                }
+            }
+            buffer.as_mut_slice()[..bytes.len()].copy_from_slice(bytes);
+            if sender.send((block as u64, buffer)).is_err() {
+                result = Err(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "Block device writer stopped",
+                ));
+            }
+        });
+        drop(sender);
+
+        for worker in workers {
+            let worker_result = worker.join().unwrap();
+            if result.is_ok() {
+                result = worker_result;
+            }
+        }
+        if applied.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Source length doesn't match the patch source length",
+            ));
+        }
+        result
+    })?;
+    device.sync_all()
+}
+
+#[cfg(test)]
+mod block_device_tests {
+    use std::{env, fs, process};
+
+    use super::*;
+
+    #[test]
+    fn aligned_buffer() {
+        for alignment in [1, 512, 4096] {
+            let buffer = AlignedBuffer::new(alignment, alignment);
+            assert_eq!(buffer.as_slice().as_ptr().align_offset(alignment), 0);
+            assert_eq!(buffer.as_slice().len(), alignment);
+        }
+    }
+
+    #[test]
+    fn apply_to_block_device() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+        let path = env::temp_dir().join(format!("deltas-block-device-{}", process::id()));
+        fs::write(&path, vec![u8::MAX; 4 * 1024]).unwrap();
+
+        let options = BlockDeviceOptions {
+            alignment: 512,
+            queue_depth: 3,
+            direct: false,
+        };
+        super::apply_to_block_device(&source, &patch, &path, &options).unwrap();
+        let device = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(&device[..target.len()], target.as_slice());
+        assert!(device[target.len()..].iter().all(|byte| *byte == u8::MAX));
+        assert_eq!(device.len(), 4 * 1024);
+    }
+
+    #[test]
+    fn apply_to_block_device_err() {
+        let options = BlockDeviceOptions {
+            alignment: 500,
+            ..Default::default()
+        };
+        assert_eq!(
+            super::apply_to_block_device(b"", &Patch::default(), "/dev/null", &options)
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+}
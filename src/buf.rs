@@ -0,0 +1,81 @@
+use std::borrow::Cow;
+
+use bytes::Bytes;
+
+use crate::{instructions::InstructionError, patch::Patch, ApplyError};
+
+impl Patch {
+    /// Like [`Patch::apply_cow`], but a target that is a prefix of `source` shares its
+    /// buffer instead of borrowing from it.
+    pub fn apply_shared(&self, source: &Bytes) -> Option<Bytes> {
+        match self.apply_cow(source)? {
+            Cow::Borrowed(prefix) => Some(source.slice_ref(prefix)),
+            Cow::Owned(target) => Some(Bytes::from(target)),
+        }
+    }
+}
+
+impl From<&Patch> for Bytes {
+    fn from(patch: &Patch) -> Self {
+        Bytes::from(patch.to_bytes())
+    }
+}
+
+impl From<Patch> for Bytes {
+    fn from(patch: Patch) -> Self {
+        Bytes::from(patch.to_bytes())
+    }
+}
+
+impl TryFrom<Bytes> for Patch {
+    type Error = InstructionError;
+
+    fn try_from(value: Bytes) -> Result<Self, Self::Error> {
+        Patch::try_from_bytes(&value)
+    }
+}
+
+/// Encodes the changes from `source` to `target` as shared patch bytes.
+pub fn diff<S: AsRef<[u8]>, T: AsRef<[u8]>>(source: S, target: T) -> Bytes {
+    Bytes::from(crate::diff(source, target))
+}
+
+/// Like [`crate::apply`], returning a slice of `source` when the patch doesn't change the
+/// bytes it keeps.
+pub fn apply<P: AsRef<[u8]>>(source: &Bytes, patch: P) -> Result<Bytes, ApplyError> {
+    Patch::try_from_bytes(patch.as_ref())?
+        .apply_shared(source)
+        .ok_or(ApplyError::SourceMismatch)
+}
+
+#[cfg(test)]
+mod buf_tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn apply_shared() {
+        let source = Bytes::from(fs::read("files/source.txt").unwrap());
+        let target = Bytes::from(fs::read("files/target.txt").unwrap());
+        let patch = diff(&source, &target);
+        assert_eq!(apply(&source, &patch), Ok(target.clone()));
+        assert_eq!(crate::apply(&source, &patch), Ok(target.to_vec()));
+        assert_eq!(
+            apply(&source.slice(1..), &patch),
+            Err(ApplyError::SourceMismatch)
+        );
+
+        let truncated = apply(&source, diff(&source, &source[..100])).unwrap();
+        assert_eq!(truncated, source[..100]);
+        assert_eq!(truncated.as_ptr(), source.as_ptr());
+
+        let decoded = Patch::try_from(patch.clone()).unwrap();
+        assert_eq!(Bytes::from(&decoded), patch);
+        assert_eq!(Bytes::from(decoded), patch);
+        assert_eq!(
+            Patch::try_from(Bytes::from_static(b"?")),
+            Err(InstructionError::InvalidSign)
+        );
+    }
+}
@@ -0,0 +1,275 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    mem,
+};
+
+use crate::{digest::Digest, encode::SourceIndex, patch::Patch, telemetry};
+
+/// Bytes a cached value keeps alive.
+pub trait Weighted {
+    fn weight(&self) -> usize;
+}
+
+impl Weighted for Patch {
+    fn weight(&self) -> usize {
+        mem::size_of::<Patch>() + self.heap_size()
+    }
+}
+
+impl Weighted for SourceIndex {
+    fn weight(&self) -> usize {
+        mem::size_of::<SourceIndex>() + self.heap_size()
+    }
+}
+
+impl Weighted for Vec<u8> {
+    fn weight(&self) -> usize {
+        mem::size_of::<Vec<u8>>() + self.capacity()
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub insertions: u64,
+    pub evictions: u64,
+}
+
+impl CacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            lookups => self.hits as f64 / lookups as f64,
+        }
+    }
+}
+
+struct Entry<V> {
+    value: V,
+    weight: usize,
+    used: u64,
+}
+
+/// Least recently used cache bounded by the total [`Weighted::weight`] of its values rather
+/// than their count.
+pub struct WeightedCache<K, V> {
+    capacity: usize,
+    weight: usize,
+    clock: u64,
+    entries: HashMap<K, Entry<V>>,
+    order: BTreeMap<u64, K>,
+    stats: CacheStats,
+}
+
+/// Patches keyed by the [`content_key`]s of their source and target.
+pub type PatchCache<O> = WeightedCache<(O, O), Patch>;
+/// Source indexes keyed by the [`content_key`] of their source.
+pub type SourceIndexCache<O> = WeightedCache<O, SourceIndex>;
+
+/// Digest to key the caches with. Contents with the same key share a cached value, so use a
+/// cryptographic digest such as SHA-256 when callers can choose the contents.
+pub fn content_key<D: Digest>(bytes: &[u8]) -> D::Output {
+    D::digest(bytes)
+}
+
+impl<K: Hash + Eq + Clone, V: Weighted> WeightedCache<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            weight: 0,
+            clock: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            stats: CacheStats::default(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Total weight of the cached values.
+    pub fn weight(&self) -> usize {
+        self.weight
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Looks `key` up and marks it as most recently used.
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        let hit = self.touch(key);
+        telemetry::record_cache_lookup(hit);
+        match hit {
+            true => self.stats.hits += 1,
+            false => self.stats.misses += 1,
+        }
+        self.entries.get(key).map(|entry| &entry.value)
+    }
+
+    /// Caches `value` under `key`, evicting least recently used values until it fits. A
+    /// value heavier than the whole capacity isn't cached and is handed back.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let weight = value.weight();
+        if weight > self.capacity {
+            return Some(value);
+        }
+        self.remove(&key);
+        while self.weight + weight > self.capacity {
+            self.evict();
+        }
+        self.clock += 1;
+        self.order.insert(self.clock, key.clone());
+        self.entries.insert(
+            key,
+            Entry {
+                value,
+                weight,
+                used: self.clock,
+            },
+        );
+        self.weight += weight;
+        self.stats.insertions += 1;
+        None
+    }
+
+    /// Returns the cached value for `key`, building and caching it with `f` on a miss. A
+    /// built value too heavy to cache is handed back as the error.
+    pub fn get_or_insert_with<F: FnOnce() -> V>(&mut self, key: K, f: F) -> Result<&V, V> {
+        if self.get(&key).is_none() {
+            if let Some(value) = self.insert(key.clone(), f()) {
+                return Err(value);
+            }
+        }
+        Ok(&self.entries[&key].value)
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let entry = self.entries.remove(key)?;
+        self.order.remove(&entry.used);
+        self.weight -= entry.weight;
+        Some(entry.value)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.weight = 0;
+    }
+
+    fn touch(&mut self, key: &K) -> bool {
+        let Some(entry) = self.entries.get_mut(key) else {
+            return false;
+        };
+        self.clock += 1;
+        let key = self.order.remove(&entry.used).unwrap();
+        entry.used = self.clock;
+        self.order.insert(self.clock, key);
+        true
+    }
+
+    fn evict(&mut self) {
+        if let Some((_, key)) = self.order.pop_first() {
+            let entry = self.entries.remove(&key).unwrap();
+            self.weight -= entry.weight;
+            self.stats.evictions += 1;
+            telemetry::record_cache_eviction();
+        }
+    }
+}
+
+#[cfg(test)]
+mod cache_tests {
+    use std::fs;
+
+    use super::*;
+    use crate::digest::digest_tests::Fnv1a;
+
+    #[test]
+    fn weighted_cache() {
+        let mut cache: WeightedCache<u8, Vec<u8>> = WeightedCache::new(300);
+        let value = |length: usize| vec![0u8; length];
+        let overhead = mem::size_of::<Vec<u8>>();
+        assert_eq!(cache.insert(1, value(100 - overhead)), None);
+        assert_eq!(cache.insert(2, value(100 - overhead)), None);
+        assert_eq!(cache.insert(3, value(100 - overhead)), None);
+        assert_eq!(cache.weight(), 300);
+        assert!(cache.get(&1).is_some());
+
+        assert_eq!(cache.insert(4, value(150 - overhead)), None);
+        assert!(cache.contains_key(&1));
+        assert!(!cache.contains_key(&2));
+        assert!(!cache.contains_key(&3));
+        assert_eq!(cache.weight(), 250);
+
+        assert_eq!(cache.insert(5, value(400)), Some(value(400)));
+        assert!(cache.get(&5).is_none());
+        assert_eq!(
+            cache.get_or_insert_with(6, || value(20)).map(Vec::len),
+            Ok(20)
+        );
+        assert_eq!(
+            cache.get_or_insert_with(6, || unreachable!()).map(Vec::len),
+            Ok(20)
+        );
+        assert_eq!(
+            cache.get_or_insert_with(7, || value(400)).map(Vec::len),
+            Err(value(400))
+        );
+        assert_eq!(
+            cache.remove(&4).map(|value| value.len()),
+            Some(150 - overhead)
+        );
+        assert_eq!(cache.len(), 2);
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 2,
+                misses: 3,
+                insertions: 5,
+                evictions: 2,
+            }
+        );
+        assert_eq!(cache.stats().hit_rate(), 0.4);
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.weight(), 0);
+    }
+
+    #[test]
+    fn patch_cache() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let key = (content_key::<Fnv1a>(&source), content_key::<Fnv1a>(&target));
+        let mut patches = PatchCache::new(1 << 20);
+        let patch = patches
+            .get_or_insert_with(key, || Patch::new(&source, &target))
+            .unwrap();
+        assert_eq!(patch.apply(&source), Some(target.clone()));
+        let heap_size = patch.heap_size();
+        assert!(patches.weight() > heap_size);
+
+        let mut indexes = SourceIndexCache::new(1 << 20);
+        let index = indexes
+            .get_or_insert_with(content_key::<Fnv1a>(&source), || SourceIndex::new(&source))
+            .unwrap();
+        assert_eq!(index.source_length(), source.len());
+        assert!(index.weight() > mem::size_of::<SourceIndex>());
+        assert_eq!(indexes.stats().misses, 1);
+    }
+}
@@ -0,0 +1,195 @@
+use std::{error::Error, ops::BitAnd, ops::BitOr};
+
+const HANDSHAKE_MAGIC: [u8; 4] = *b"DLTC";
+const HANDSHAKE_VERSION: u8 = 1;
+const HANDSHAKE_LENGTH: usize = 9;
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct Capabilities(u32);
+
+impl Capabilities {
+    pub const BYTES_FORMAT: Self = Self(1 << 0);
+    pub const CBOR_FORMAT: Self = Self(1 << 1);
+    pub const TARGET_COPIES: Self = Self(1 << 2);
+    pub const ENCRYPTED_ADDS: Self = Self(1 << 3);
+    pub const SKIP_UNKNOWN: Self = Self(1 << 4);
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn supported() -> Self {
+        Self(
+            Self::BYTES_FORMAT.0
+                | Self::CBOR_FORMAT.0
+                | Self::TARGET_COPIES.0
+                | Self::ENCRYPTED_ADDS.0
+                | Self::SKIP_UNKNOWN.0,
+        )
+    }
+
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn to_handshake(&self) -> [u8; HANDSHAKE_LENGTH] {
+        let mut bytes = [0u8; HANDSHAKE_LENGTH];
+        bytes[..4].copy_from_slice(&HANDSHAKE_MAGIC);
+        bytes[4] = HANDSHAKE_VERSION;
+        bytes[5..].copy_from_slice(&self.0.to_be_bytes());
+        bytes
+    }
+
+    pub fn try_from_handshake(bytes: &[u8]) -> Result<Self, HandshakeError> {
+        if bytes.len() < HANDSHAKE_LENGTH {
+            return Err(HandshakeError::Truncated);
+        }
+        if bytes[..4] != HANDSHAKE_MAGIC {
+            return Err(HandshakeError::InvalidMagic);
+        }
+        if bytes[4] == 0 {
+            return Err(HandshakeError::UnsupportedVersion(bytes[4]));
+        }
+        Ok(Self(u32::from_be_bytes(bytes[5..9].try_into().unwrap())))
+    }
+}
+
+impl BitOr for Capabilities {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitAnd for Capabilities {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Self(self.0 & rhs.0)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PatchFormat {
+    Bytes,
+    Cbor,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Negotiated {
+    pub format: PatchFormat,
+    pub capabilities: Capabilities,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum HandshakeError {
+    Truncated,
+    InvalidMagic,
+    UnsupportedVersion(u8),
+}
+
+impl std::fmt::Display for HandshakeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HandshakeError::Truncated => write!(
+                f,
+                "Handshake must be at least {} bytes long",
+                HANDSHAKE_LENGTH
+            ),
+            HandshakeError::InvalidMagic => write!(f, "Handshake magic didn't match"),
+            HandshakeError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported handshake version {}", version)
+            }
+        }
+    }
+}
+
+impl Error for HandshakeError {}
+
+pub fn negotiate(ours: Capabilities, theirs: Capabilities) -> Option<Negotiated> {
+    let common = ours & theirs & Capabilities::supported();
+    let format = if common.contains(Capabilities::BYTES_FORMAT) {
+        PatchFormat::Bytes
+    } else if common.contains(Capabilities::CBOR_FORMAT) {
+        PatchFormat::Cbor
+    } else {
+        return None;
+    };
+    Some(Negotiated {
+        format,
+        capabilities: common,
+    })
+}
+
+#[cfg(test)]
+mod capabilities_tests {
+    use super::*;
+
+    #[test]
+    fn handshake() {
+        let capabilities = Capabilities::BYTES_FORMAT | Capabilities::TARGET_COPIES;
+        let handshake = capabilities.to_handshake();
+        assert_eq!(
+            Capabilities::try_from_handshake(&handshake),
+            Ok(capabilities)
+        );
+
+        let mut newer = Capabilities::from_bits(u32::MAX).to_handshake().to_vec();
+        newer[4] = HANDSHAKE_VERSION + 1;
+        newer.extend(b"trailing fields");
+        assert_eq!(
+            Capabilities::try_from_handshake(&newer),
+            Ok(Capabilities::from_bits(u32::MAX))
+        );
+
+        assert_eq!(
+            Capabilities::try_from_handshake(&handshake[..8]),
+            Err(HandshakeError::Truncated)
+        );
+        assert_eq!(
+            Capabilities::try_from_handshake(b"XXXX\x01\x00\x00\x00\x01"),
+            Err(HandshakeError::InvalidMagic)
+        );
+        assert_eq!(
+            Capabilities::try_from_handshake(b"DLTC\x00\x00\x00\x00\x01"),
+            Err(HandshakeError::UnsupportedVersion(0))
+        );
+    }
+
+    #[test]
+    fn negotiate() {
+        let ours = Capabilities::supported();
+        let theirs = Capabilities::CBOR_FORMAT
+            | Capabilities::TARGET_COPIES
+            | Capabilities::from_bits(1 << 31);
+        assert_eq!(
+            super::negotiate(ours, theirs),
+            Some(Negotiated {
+                format: PatchFormat::Cbor,
+                capabilities: Capabilities::CBOR_FORMAT | Capabilities::TARGET_COPIES,
+            })
+        );
+        assert_eq!(
+            super::negotiate(ours, ours).unwrap().format,
+            PatchFormat::Bytes
+        );
+        assert_eq!(
+            super::negotiate(Capabilities::BYTES_FORMAT, Capabilities::CBOR_FORMAT),
+            None
+        );
+        assert!(Capabilities::empty().is_empty());
+    }
+}
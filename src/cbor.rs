@@ -0,0 +1,154 @@
+use std::{iter::Peekable, slice::Iter};
+
+use crate::instructions::{InstructionError, Result};
+
+pub const MAJOR_UNSIGNED: u8 = 0;
+pub const MAJOR_BYTES: u8 = 2;
+pub const MAJOR_ARRAY: u8 = 4;
+
+const ONE_BYTE_ARGUMENT: u8 = 24;
+const TWO_BYTE_ARGUMENT: u8 = 25;
+const FOUR_BYTE_ARGUMENT: u8 = 26;
+const EIGHT_BYTE_ARGUMENT: u8 = 27;
+
+pub fn write_head(bytes: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+    if value < ONE_BYTE_ARGUMENT as u64 {
+        bytes.push(major | value as u8);
+    } else if value <= u8::MAX as u64 {
+        bytes.push(major | ONE_BYTE_ARGUMENT);
+        bytes.push(value as u8);
+    } else if value <= u16::MAX as u64 {
+        bytes.push(major | TWO_BYTE_ARGUMENT);
+        bytes.extend((value as u16).to_be_bytes());
+    } else if value <= u32::MAX as u64 {
+        bytes.push(major | FOUR_BYTE_ARGUMENT);
+        bytes.extend((value as u32).to_be_bytes());
+    } else {
+        bytes.push(major | EIGHT_BYTE_ARGUMENT);
+        bytes.extend(value.to_be_bytes());
+    }
+}
+
+pub fn read_head(bytes: &mut Peekable<Iter<'_, u8>>) -> Result<(u8, u64)> {
+    let initial = *bytes.next().ok_or(InstructionError::MissingContent)?;
+    let major = initial >> 5;
+    let argument_length = match initial & 0b0001_1111 {
+        value if value < ONE_BYTE_ARGUMENT => return Ok((major, value as u64)),
+        ONE_BYTE_ARGUMENT => 1,
+        TWO_BYTE_ARGUMENT => 2,
+        FOUR_BYTE_ARGUMENT => 4,
+        EIGHT_BYTE_ARGUMENT => 8,
+        _ => return Err(InstructionError::InvalidContent),
+    };
+    let mut value = 0u64;
+    for _ in 0..argument_length {
+        value = (value << 8) | *bytes.next().ok_or(InstructionError::MissingContent)? as u64;
+    }
+    Ok((major, value))
+}
+
+pub fn write_instruction_head(bytes: &mut Vec<u8>, sign: u8) {
+    write_head(bytes, MAJOR_ARRAY, 2);
+    write_head(bytes, MAJOR_UNSIGNED, sign as u64);
+}
+
+pub fn read_instruction_head(bytes: &mut Peekable<Iter<'_, u8>>) -> Result<u8> {
+    match read_head(bytes)? {
+        (MAJOR_ARRAY, 2) => (),
+        (MAJOR_ARRAY, 0) => return Err(InstructionError::MissignSign),
+        _ => return Err(InstructionError::InvalidContent),
+    };
+    match read_head(bytes) {
+        Ok((MAJOR_UNSIGNED, sign)) => u8::try_from(sign).map_err(|_| InstructionError::InvalidSign),
+        Ok(_) => Err(InstructionError::InvalidSign),
+        Err(InstructionError::MissingContent) => Err(InstructionError::MissignSign),
+        Err(err) => Err(err),
+    }
+}
+
+pub fn read_content(bytes: &mut Peekable<Iter<'_, u8>>) -> Result<Vec<u8>> {
+    let length = match read_head(bytes) {
+        Ok((MAJOR_BYTES, length)) => length,
+        Ok(_) => return Err(InstructionError::InvalidContent),
+        Err(InstructionError::MissingContent) => return Err(InstructionError::MissingLength),
+        Err(err) => return Err(err),
+    };
+    if length > u8::MAX as u64 {
+        return Err(InstructionError::ContentOverflow);
+    }
+    let content: Vec<u8> = bytes.take(length as usize).copied().collect();
+    if content.len() < length as usize {
+        return Err(InstructionError::MissingContent);
+    }
+    Ok(content)
+}
+
+#[cfg(test)]
+mod cbor_tests {
+    use super::*;
+
+    #[test]
+    fn write_head() {
+        let mut bytes = Vec::new();
+        super::write_head(&mut bytes, MAJOR_UNSIGNED, 23);
+        assert_eq!(bytes, vec![0x17]);
+
+        bytes.clear();
+        super::write_head(&mut bytes, MAJOR_UNSIGNED, 24);
+        assert_eq!(bytes, vec![0x18, 0x18]);
+
+        bytes.clear();
+        super::write_head(&mut bytes, MAJOR_BYTES, 256);
+        assert_eq!(bytes, vec![0x59, 0x01, 0x00]);
+
+        bytes.clear();
+        super::write_head(&mut bytes, MAJOR_ARRAY, 65536);
+        assert_eq!(bytes, vec![0x9a, 0x00, 0x01, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn read_head() {
+        for value in [0, 23, 24, u8::MAX as u64, 1000, 100_000, u64::MAX] {
+            let mut bytes = Vec::new();
+            super::write_head(&mut bytes, MAJOR_ARRAY, value);
+            assert_eq!(
+                super::read_head(&mut bytes.iter().peekable()),
+                Ok((MAJOR_ARRAY, value))
+            );
+        }
+
+        assert_eq!(
+            super::read_head(&mut [0x19, 0x01].iter().peekable()),
+            Err(InstructionError::MissingContent)
+        );
+        assert_eq!(
+            super::read_head(&mut [0x1f].iter().peekable()),
+            Err(InstructionError::InvalidContent)
+        );
+    }
+
+    #[test]
+    fn read_content() {
+        let mut bytes = Vec::new();
+        super::write_head(&mut bytes, MAJOR_BYTES, 3);
+        bytes.extend([1, 2, 3]);
+        assert_eq!(
+            super::read_content(&mut bytes.iter().peekable()),
+            Ok(vec![1, 2, 3])
+        );
+
+        bytes.pop();
+        assert_eq!(
+            super::read_content(&mut bytes.iter().peekable()),
+            Err(InstructionError::MissingContent)
+        );
+
+        bytes.clear();
+        super::write_head(&mut bytes, MAJOR_BYTES, u8::MAX as u64 + 1);
+        assert_eq!(
+            super::read_content(&mut bytes.iter().peekable()),
+            Err(InstructionError::ContentOverflow)
+        );
+    }
+}
@@ -0,0 +1,412 @@
+use std::{error::Error, ops::Range};
+
+use crate::{
+    instructions::{delta_instruction::DeltaInstruction, InstructionInfo},
+    patch::Patch,
+};
+
+/// A top-level field and where its serialized bytes sit.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Field {
+    pub name: &'static str,
+    pub range: Range<usize>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FieldChange {
+    pub name: &'static str,
+    pub old_length: usize,
+    pub new_length: usize,
+    /// Bytes of the field the patch removed, added or modified.
+    pub changed_bytes: usize,
+}
+
+impl std::fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.old_length == self.new_length {
+            true => write!(f, "{}: {} bytes", self.name, self.new_length)?,
+            false => write!(
+                f,
+                "{}: {} -> {} bytes",
+                self.name, self.old_length, self.new_length
+            )?,
+        }
+        write!(f, ", {} changed", self.changed_bytes)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum FieldError {
+    /// A field's range doesn't lie within the bytes it describes.
+    OutOfBounds(&'static str),
+}
+
+impl std::fmt::Display for FieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FieldError::OutOfBounds(name) => {
+                write!(f, "Field {} lies outside the serialized bytes", name)
+            }
+        }
+    }
+}
+
+impl Error for FieldError {}
+
+fn field_bytes<'a>(bytes: &'a [u8], field: &Field) -> Result<&'a [u8], FieldError> {
+    bytes
+        .get(field.range.clone())
+        .ok_or(FieldError::OutOfBounds(field.name))
+}
+
+/// Source ranges the patch drops or modifies and target ranges it writes or modifies.
+fn changed_ranges(patch: &Patch) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+    let (mut source, mut target) = (Vec::new(), Vec::new());
+    let (mut source_offset, mut target_offset) = (0usize, 0usize);
+    let push = |ranges: &mut Vec<Range<usize>>, range: Range<usize>| match ranges.last_mut() {
+        Some(last) if last.end == range.start => last.end = range.end,
+        _ => ranges.push(range),
+    };
+    for instruction in patch.instructions() {
+        let length = instruction.len() as usize;
+        match instruction {
+            DeltaInstruction::Remove(_) => {
+                push(&mut source, source_offset..source_offset + length);
+                source_offset += length;
+            }
+            DeltaInstruction::Copy(copy) => {
+                for (position, delta) in copy.content().iter().enumerate() {
+                    if *delta != 0 {
+                        let position = target_offset + position;
+                        push(&mut target, position..position + 1);
+                    }
+                }
+                source_offset += length;
+                target_offset += length;
+            }
+            _ => {
+                push(&mut target, target_offset..target_offset + length);
+                target_offset += length;
+            }
+        }
+    }
+    (source, target)
+}
+
+fn overlap(ranges: &[Range<usize>], range: &Range<usize>) -> usize {
+    ranges
+        .iter()
+        .map(|other| {
+            other
+                .end
+                .min(range.end)
+                .saturating_sub(other.start.max(range.start))
+        })
+        .sum()
+}
+
+/// Maps the bytes `patch` changes back to the fields they belong to. Fields are matched by
+/// name between the layout of `source` and of `target`, and only fields whose bytes differ
+/// are returned, in target order followed by removed fields. The encoder may realign bytes
+/// around an edit, so the counts are what the patch rewrote rather than a minimal diff.
+///
+/// Fails with [`FieldError::OutOfBounds`] if a field lies outside `source` or `target`.
+pub fn field_changes(
+    patch: &Patch,
+    source: &[u8],
+    old_fields: &[Field],
+    target: &[u8],
+    new_fields: &[Field],
+) -> Result<Vec<FieldChange>, FieldError> {
+    for old in old_fields {
+        field_bytes(source, old)?;
+    }
+    let (removed, written) = changed_ranges(patch);
+    let mut changes: Vec<FieldChange> = Vec::new();
+    for field in new_fields {
+        let new_bytes = field_bytes(target, field)?;
+        let old = old_fields.iter().find(|old| old.name == field.name);
+        if old.is_some_and(|old| field_bytes(source, old) == Ok(new_bytes)) {
+            continue;
+        }
+        let changed_bytes =
+            overlap(&written, &field.range) + old.map_or(0, |old| overlap(&removed, &old.range));
+        changes.push(FieldChange {
+            name: field.name,
+            old_length: old.map_or(0, |old| old.range.len()),
+            new_length: field.range.len(),
+            changed_bytes: changed_bytes.max(1),
+        });
+    }
+    changes.extend(
+        old_fields
+            .iter()
+            .filter(|old| new_fields.iter().all(|field| field.name != old.name))
+            .map(|old| FieldChange {
+                name: old.name,
+                old_length: old.range.len(),
+                new_length: 0,
+                changed_bytes: old.range.len(),
+            }),
+    );
+    Ok(changes)
+}
+
+/// Holds a value alongside its serialized bytes and turns every change into a patch between
+/// the previous and the new bytes.
+///
+/// The serializer is a plain function so any format works, e.g.
+/// `|value| bincode::serialize(value).unwrap()` for a serde type. A peer that starts from
+/// [`DeltaCell::bytes`] stays in sync by applying each returned patch in order.
+pub struct DeltaCell<T> {
+    value: T,
+    bytes: Vec<u8>,
+    serialize: fn(&T) -> Vec<u8>,
+    fields: Option<fn(&T) -> Vec<Field>>,
+    layout: Vec<Field>,
+    changes: Result<Vec<FieldChange>, FieldError>,
+}
+
+impl<T> DeltaCell<T> {
+    pub fn new(value: T, serialize: fn(&T) -> Vec<u8>) -> Self {
+        let bytes = serialize(&value);
+        Self {
+            value,
+            bytes,
+            serialize,
+            fields: None,
+            layout: Vec::new(),
+            changes: Ok(Vec::new()),
+        }
+    }
+
+    /// Describes where each top-level field lands in the serialized bytes, so changes can be
+    /// summarized per field with [`DeltaCell::summary`].
+    pub fn with_fields(mut self, fields: fn(&T) -> Vec<Field>) -> Self {
+        self.layout = fields(&self.value);
+        self.fields = Some(fields);
+        self
+    }
+
+    /// The fields the last change touched. Empty without [`DeltaCell::with_fields`], and an
+    /// error if the layout didn't fit the serialized bytes.
+    pub fn changes(&self) -> Result<&[FieldChange], &FieldError> {
+        self.changes.as_deref()
+    }
+
+    /// One line per field the last change touched, or the layout error.
+    pub fn summary(&self) -> String {
+        match &self.changes {
+            Ok(changes) => changes
+                .iter()
+                .map(|change| format!("{}\n", change))
+                .collect(),
+            Err(error) => format!("{}\n", error),
+        }
+    }
+
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    /// The serialized bytes of the current value.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    /// Stores `value` and returns the patch from the previous bytes to its bytes, or `None`
+    /// if it serializes the same.
+    pub fn set(&mut self, value: T) -> Option<Patch> {
+        self.value = value;
+        self.snapshot()
+    }
+
+    /// Changes the value in place, see [`DeltaCell::set`].
+    pub fn update<F: FnOnce(&mut T)>(&mut self, f: F) -> Option<Patch> {
+        f(&mut self.value);
+        self.snapshot()
+    }
+
+    fn snapshot(&mut self) -> Option<Patch> {
+        let bytes = (self.serialize)(&self.value);
+        if bytes == self.bytes {
+            return None;
+        }
+        let patch = Patch::new(&self.bytes, &bytes);
+        if let Some(fields) = self.fields {
+            let layout = fields(&self.value);
+            self.changes = field_changes(&patch, &self.bytes, &self.layout, &bytes, &layout);
+            self.layout = layout;
+        }
+        self.bytes = bytes;
+        Some(patch)
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for DeltaCell<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeltaCell")
+            .field("value", &self.value)
+            .field("bytes", &self.bytes.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod cell_tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Clone)]
+    struct Player {
+        name: String,
+        score: u32,
+        inventory: Vec<u16>,
+    }
+
+    fn serialize(player: &Player) -> Vec<u8> {
+        let mut bytes = player.name.as_bytes().to_vec();
+        bytes.push(0);
+        bytes.extend(player.score.to_le_bytes());
+        bytes.extend(player.inventory.iter().flat_map(|item| item.to_le_bytes()));
+        bytes
+    }
+
+    fn fields(player: &Player) -> Vec<Field> {
+        let name = player.name.len() + 1;
+        vec![
+            Field {
+                name: "name",
+                range: 0..name,
+            },
+            Field {
+                name: "score",
+                range: name..name + 4,
+            },
+            Field {
+                name: "inventory",
+                range: name + 4..name + 4 + player.inventory.len() * 2,
+            },
+        ]
+    }
+
+    #[test]
+    fn delta_cell() {
+        let player = Player {
+            name: "ferris".to_string(),
+            score: 10,
+            inventory: (0..200).collect(),
+        };
+        let mut cell = DeltaCell::new(player.clone(), serialize);
+        let mut replica = cell.bytes().to_vec();
+        assert_eq!(replica, serialize(&player));
+
+        let patch = cell.update(|player| player.score += 5).unwrap();
+        replica = patch.apply(&replica).unwrap();
+        assert_eq!(replica, cell.bytes());
+
+        let patch = cell.update(|player| player.inventory.push(7)).unwrap();
+        replica = patch.apply(&replica).unwrap();
+        let patch = cell
+            .set(Player {
+                name: "corro".to_string(),
+                ..cell.get().clone()
+            })
+            .unwrap();
+        replica = patch.apply(&replica).unwrap();
+        assert_eq!(replica, cell.bytes());
+        assert_eq!(cell.get().name, "corro");
+
+        assert_eq!(cell.update(|_| ()), None);
+        assert_eq!(cell.set(cell.get().clone()), None);
+        assert_eq!(cell.into_inner().inventory.len(), 201);
+    }
+
+    #[test]
+    fn field_changes() {
+        let player = Player {
+            name: "ferris".to_string(),
+            score: 10,
+            inventory: (0..100).collect(),
+        };
+        let mut cell = DeltaCell::new(player, serialize).with_fields(fields);
+        assert_eq!(cell.changes(), Ok(&[][..]));
+
+        cell.update(|player| player.score = 0xf0f0).unwrap();
+        assert_eq!(cell.summary(), "score: 4 bytes, 2 changed\n");
+
+        cell.update(|player| {
+            player.name = "ferris the crab".to_string();
+            player.inventory[50] = 999;
+        })
+        .unwrap();
+        assert_eq!(
+            cell.changes()
+                .unwrap()
+                .iter()
+                .map(|change| change.name)
+                .collect::<Vec<&str>>(),
+            vec!["name", "inventory"]
+        );
+        assert_eq!(cell.changes().unwrap()[0].old_length, 7);
+        assert_eq!(cell.changes().unwrap()[0].new_length, 16);
+        assert!(cell.summary().starts_with("name: 7 -> 16 bytes, "));
+
+        let patch = Patch::new(b"abcdef", b"abXdef");
+        let old = [
+            Field {
+                name: "a",
+                range: 0..3,
+            },
+            Field {
+                name: "b",
+                range: 3..6,
+            },
+        ];
+        assert_eq!(
+            super::field_changes(&patch, b"abcdef", &old, b"abXdef", &old[..1]),
+            Ok(vec![
+                FieldChange {
+                    name: "a",
+                    old_length: 3,
+                    new_length: 3,
+                    changed_bytes: 1,
+                },
+                FieldChange {
+                    name: "b",
+                    old_length: 3,
+                    new_length: 0,
+                    changed_bytes: 3,
+                },
+            ])
+        );
+
+        let outside = [Field {
+            name: "c",
+            range: 4..8,
+        }];
+        assert_eq!(
+            super::field_changes(&patch, b"abcdef", &outside, b"abXdef", &old),
+            Err(FieldError::OutOfBounds("c"))
+        );
+        assert_eq!(
+            super::field_changes(&patch, b"abcdef", &old, b"abXdef", &outside),
+            Err(FieldError::OutOfBounds("c"))
+        );
+        let mut cell = DeltaCell::new(b"abc".to_vec(), |bytes| bytes.clone()).with_fields(|_| {
+            vec![Field {
+                name: "all",
+                range: 0..4,
+            }]
+        });
+        cell.update(|bytes| bytes[0] = b'x').unwrap();
+        assert_eq!(cell.changes(), Err(&FieldError::OutOfBounds("all")));
+        assert_eq!(
+            cell.summary(),
+            "Field all lies outside the serialized bytes\n"
+        );
+    }
+}
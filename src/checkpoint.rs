@@ -0,0 +1,340 @@
+use std::{error::Error, io, io::Write, num::NonZeroUsize, ops::Range};
+
+use crate::{
+    instructions::{
+        delta_instruction::DeltaInstruction, InstructionBytes, InstructionContent,
+        InstructionError, InstructionInfo,
+    },
+    patch::{Patch, PatchBuilder},
+};
+
+pub const CHECKPOINT_SIGN: u8 = b'#';
+const CHECKPOINT_LENGTH: u8 = 4;
+
+pub(crate) fn crc32(crc: u32, bytes: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    InvalidInstruction {
+        segment: usize,
+        offset: usize,
+        error: InstructionError,
+    },
+    ChecksumMismatch {
+        segment: usize,
+        instructions: Range<usize>,
+        bytes: Range<usize>,
+    },
+    MissingCheckpoint {
+        segment: usize,
+        instructions: Range<usize>,
+    },
+    SourceMismatch {
+        segment: usize,
+    },
+    Io(io::Error),
+}
+
+impl std::fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckpointError::InvalidInstruction {
+                segment,
+                offset,
+                error,
+            } => write!(
+                f,
+                "Invalid instruction at byte {} in segment {}: {}",
+                offset, segment, error
+            ),
+            CheckpointError::ChecksumMismatch {
+                segment,
+                instructions,
+                bytes,
+            } => write!(
+                f,
+                "Checksum mismatch in segment {} (instructions {:?}, bytes {:?})",
+                segment, instructions, bytes
+            ),
+            CheckpointError::MissingCheckpoint {
+                segment,
+                instructions,
+            } => write!(
+                f,
+                "Segment {} (instructions {:?}) ended without a checkpoint",
+                segment, instructions
+            ),
+            CheckpointError::SourceMismatch { segment } => write!(
+                f,
+                "Source length doesn't match the patch in segment {}",
+                segment
+            ),
+            CheckpointError::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for CheckpointError {}
+
+impl From<io::Error> for CheckpointError {
+    fn from(error: io::Error) -> Self {
+        CheckpointError::Io(error)
+    }
+}
+
+struct Segment {
+    index: usize,
+    instructions: Vec<DeltaInstruction>,
+}
+
+struct Segments<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    crc: u32,
+    segment: usize,
+    instruction: usize,
+}
+
+impl<'a> Segments<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            offset: 0,
+            crc: 0,
+            segment: 0,
+            instruction: 0,
+        }
+    }
+
+    fn next_segment(&mut self) -> Option<Result<Segment, CheckpointError>> {
+        if self.offset == self.bytes.len() {
+            return None;
+        }
+        let start = self.offset;
+        let first_instruction = self.instruction;
+        let mut instructions: Vec<DeltaInstruction> = Vec::new();
+        let invalid = |offset, error| CheckpointError::InvalidInstruction {
+            segment: self.segment,
+            offset,
+            error,
+        };
+        while self.bytes[self.offset] != CHECKPOINT_SIGN {
+            let mut bytes_iter = self.bytes[self.offset..].iter().peekable();
+            let instruction = match DeltaInstruction::try_from_bytes(&mut bytes_iter) {
+                Ok(instruction) => instruction,
+                Err(error) => return Some(Err(invalid(self.offset, error))),
+            };
+            self.offset = self.bytes.len() - bytes_iter.len();
+            instructions.push(instruction);
+            self.instruction += 1;
+            if self.offset == self.bytes.len() {
+                return Some(Err(CheckpointError::MissingCheckpoint {
+                    segment: self.segment,
+                    instructions: first_instruction..self.instruction,
+                }));
+            }
+        }
+        let record = match self.bytes.get(self.offset..self.offset + 6) {
+            Some(record) if record[1] == CHECKPOINT_LENGTH => record,
+            Some(_) => return Some(Err(invalid(self.offset, InstructionError::InvalidLength))),
+            None => return Some(Err(invalid(self.offset, InstructionError::MissingContent))),
+        };
+        self.crc = crc32(self.crc, &self.bytes[start..self.offset]);
+        if u32::from_be_bytes(record[2..6].try_into().unwrap()) != self.crc {
+            return Some(Err(CheckpointError::ChecksumMismatch {
+                segment: self.segment,
+                instructions: first_instruction..self.instruction,
+                bytes: start..self.offset,
+            }));
+        }
+        self.offset += record.len();
+        self.segment += 1;
+        Some(Ok(Segment {
+            index: self.segment - 1,
+            instructions,
+        }))
+    }
+}
+
+impl Patch {
+    /// Serializes the patch with a checkpoint after every `interval` instructions.
+    pub fn to_bytes_with_checkpoints(&self, interval: NonZeroUsize) -> Vec<u8> {
+        let interval = interval.get();
+        let checkpoints = self.instructions().len().div_ceil(interval);
+        let mut bytes: Vec<u8> = Vec::with_capacity(
+            self.instructions()
+                .iter()
+                .map(|instruction| instruction.encoded_len())
+                .sum::<usize>()
+                + checkpoints * 6,
+        );
+        let mut crc = 0u32;
+        for chunk in self.instructions().chunks(interval) {
+            let start = bytes.len();
+            for instruction in chunk {
+                bytes.extend(instruction.to_bytes());
+            }
+            crc = crc32(crc, &bytes[start..]);
+            bytes.extend([CHECKPOINT_SIGN, CHECKPOINT_LENGTH]);
+            bytes.extend(crc.to_be_bytes());
+        }
+        bytes
+    }
+
+    pub fn try_from_checkpointed_bytes(bytes: &[u8]) -> Result<Self, CheckpointError> {
+        let mut segments = Segments::new(bytes);
+        let mut builder = PatchBuilder::default();
+        while let Some(segment) = segments.next_segment() {
+            for instruction in segment?.instructions {
+                builder.instruction(instruction);
+            }
+        }
+        let patch = builder.build();
+        Patch::validate_target_copies(patch.instructions()).map_err(|error| {
+            CheckpointError::InvalidInstruction {
+                segment: segments.segment,
+                offset: segments.offset,
+                error,
+            }
+        })?;
+        Ok(patch)
+    }
+
+    pub fn apply_checkpointed<W: Write>(
+        bytes: &[u8],
+        source: &[u8],
+        target: &mut W,
+    ) -> Result<usize, CheckpointError> {
+        let mut segments = Segments::new(bytes);
+        let mut source_iter = source.iter();
+        let mut output: Vec<u8> = Vec::new();
+        let mut segment_index = 0usize;
+        while let Some(segment) = segments.next_segment() {
+            let segment = segment?;
+            segment_index = segment.index;
+            let start = output.len();
+            for instruction in segment.instructions.iter() {
+                let consumed = match instruction {
+                    DeltaInstruction::Remove(_) | DeltaInstruction::Copy(_) => {
+                        instruction.len() as usize
+                    }
+                    _ => 0,
+                };
+                let invalid_distance = match instruction {
+                    DeltaInstruction::TargetCopy(target_copy) => {
+                        target_copy.distance() as usize > output.len()
+                    }
+                    _ => false,
+                };
+                if consumed > source_iter.len() || invalid_distance {
+                    return Err(CheckpointError::SourceMismatch {
+                        segment: segment.index,
+                    });
+                }
+                instruction.apply(&mut source_iter, &mut output);
+            }
+            target.write_all(&output[start..])?;
+        }
+        if source_iter.len() != 0 {
+            return Err(CheckpointError::SourceMismatch {
+                segment: segment_index,
+            });
+        }
+        target.flush()?;
+        Ok(output.len())
+    }
+}
+
+#[cfg(test)]
+mod checkpoint_tests {
+    use std::fs;
+
+    use super::*;
+
+    const INTERVAL: NonZeroUsize = NonZeroUsize::new(4).unwrap();
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(0, b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(crc32(0, b"1234"), b"56789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn round_trip() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+        let bytes = patch.to_bytes_with_checkpoints(INTERVAL);
+        assert_eq!(Patch::try_from_checkpointed_bytes(&bytes).unwrap(), patch);
+
+        let mut written: Vec<u8> = Vec::new();
+        let length = Patch::apply_checkpointed(&bytes, &source, &mut written).unwrap();
+        assert_eq!(length, target.len());
+        assert_eq!(written, target);
+
+        assert!(matches!(
+            Patch::apply_checkpointed(&bytes, &source[1..], &mut Vec::new()),
+            Err(CheckpointError::SourceMismatch { .. })
+        ));
+        assert_eq!(
+            Patch::default().to_bytes_with_checkpoints(INTERVAL),
+            Vec::<u8>::new()
+        );
+    }
+
+    #[test]
+    fn corruption() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+        let mut bytes = patch.to_bytes_with_checkpoints(INTERVAL);
+
+        let offsets: Vec<usize> = patch
+            .instructions()
+            .iter()
+            .scan(0, |offset, instruction| {
+                *offset += instruction.to_bytes().len();
+                Some(*offset - instruction.to_bytes().len())
+            })
+            .collect();
+        let first_segment = offsets[4];
+        let content = (4..8)
+            .find(|index| matches!(patch.instructions()[*index], DeltaInstruction::Add(_)))
+            .unwrap();
+        let corrupted = offsets[content] + 6 + 2;
+        bytes[corrupted] ^= 0x01;
+        let mut written: Vec<u8> = Vec::new();
+        match Patch::apply_checkpointed(&bytes, &source, &mut written) {
+            Err(CheckpointError::ChecksumMismatch {
+                segment,
+                instructions,
+                bytes,
+            }) => {
+                assert_eq!(segment, 1);
+                assert_eq!(instructions, 4..8);
+                assert!(bytes.contains(&corrupted));
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        let verified = Patch::try_from_bytes(&patch.to_bytes()[..first_segment])
+            .unwrap()
+            .target_length();
+        assert_eq!(written.len(), verified);
+
+        bytes[corrupted] ^= 0x01;
+        bytes.truncate(bytes.len() - 6);
+        assert!(matches!(
+            Patch::try_from_checkpointed_bytes(&bytes),
+            Err(CheckpointError::MissingCheckpoint { .. })
+        ));
+    }
+}
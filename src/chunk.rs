@@ -0,0 +1,312 @@
+use std::ops::Range;
+
+use crate::{
+    digest::Digest,
+    instructions::{
+        delta_instruction::DeltaInstruction, InstructionError, InstructionInfo, Result,
+    },
+    patch::Patch,
+};
+
+pub const CHUNK_PARAMS_SIGN: u8 = b'C';
+const CHUNK_PARAMS_LENGTH: usize = 25;
+
+/// Gear values for the rolling hash: splitmix64 outputs seeded with the golden ratio constant
+/// `0x9e3779b97f4a7c15`. Chunk boundaries depend only on these values, [`ChunkParams`] and the
+/// content, so the table is part of the format and must never change.
+const GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state = 0x9e37_79b9_7f4a_7c15u64;
+    let mut index = 0;
+    while index < 256 {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut value = state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[index] = value ^ (value >> 31);
+        index += 1;
+    }
+    table
+}
+
+/// Content defined chunking sizes. Boundaries only depend on nearby bytes, so an edit moves
+/// the chunks around it but leaves the ids of the others unchanged.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ChunkParams {
+    pub min_size: usize,
+    /// Rounded down to a power of two.
+    pub average_size: usize,
+    pub max_size: usize,
+}
+
+impl Default for ChunkParams {
+    fn default() -> Self {
+        Self {
+            min_size: 2 * 1024,
+            average_size: 8 * 1024,
+            max_size: 64 * 1024,
+        }
+    }
+}
+
+impl ChunkParams {
+    /// A header holding the sizes, so chunks cut by a peer can be recut the same way.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(CHUNK_PARAMS_LENGTH);
+        bytes.push(CHUNK_PARAMS_SIGN);
+        for size in [self.min_size, self.average_size, self.max_size] {
+            bytes.extend((size as u64).to_be_bytes());
+        }
+        bytes
+    }
+
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self> {
+        match bytes.first() {
+            Some(&CHUNK_PARAMS_SIGN) => (),
+            Some(_) => return Err(InstructionError::InvalidSign),
+            None => return Err(InstructionError::MissignSign),
+        }
+        let mut sizes = [0usize; 3];
+        for (index, size) in sizes.iter_mut().enumerate() {
+            let field = bytes
+                .get(1 + index * 8..9 + index * 8)
+                .ok_or(InstructionError::MissingLength)?;
+            *size = usize::try_from(u64::from_be_bytes(field.try_into().unwrap()))
+                .map_err(|_| InstructionError::InvalidLength)?;
+        }
+        if bytes.len() > CHUNK_PARAMS_LENGTH {
+            return Err(InstructionError::InvalidContent);
+        }
+        let [min_size, average_size, max_size] = sizes;
+        Ok(Self {
+            min_size,
+            average_size,
+            max_size,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Chunk<O> {
+    pub range: Range<usize>,
+    pub id: O,
+}
+
+/// Splits `bytes` into content defined chunks with a gear rolling hash.
+pub fn chunk_boundaries(bytes: &[u8], params: &ChunkParams) -> Vec<Range<usize>> {
+    let average = params.average_size.max(1);
+    let mask = (1u64 << average.ilog2()) - 1;
+    let max_size = params.max_size.max(1);
+    let mut ranges: Vec<Range<usize>> = Vec::with_capacity(bytes.len() / average + 1);
+    let mut start = 0;
+    while start < bytes.len() {
+        let end = bytes.len().min(start + max_size);
+        let mut hash = 0u64;
+        let mut cut = end;
+        for (offset, byte) in bytes[start..end].iter().enumerate() {
+            hash = (hash << 1).wrapping_add(GEAR[*byte as usize]);
+            if offset + 1 >= params.min_size && hash & mask == 0 {
+                cut = start + offset + 1;
+                break;
+            }
+        }
+        ranges.push(start..cut);
+        start = cut;
+    }
+    ranges
+}
+
+/// Chunks `bytes` and names each chunk by the digest of its contents.
+pub fn chunk_ids<D: Digest>(bytes: &[u8], params: &ChunkParams) -> Vec<Chunk<D::Output>> {
+    chunk_boundaries(bytes, params)
+        .into_iter()
+        .map(|range| Chunk {
+            id: D::digest(&bytes[range.clone()]),
+            range,
+        })
+        .collect()
+}
+
+impl Patch {
+    /// For every instruction, the indices of the source `chunks` it reads. Only copies read
+    /// the source, so other instructions map to no chunks.
+    pub fn instruction_chunks<O>(&self, chunks: &[Chunk<O>]) -> Vec<Vec<usize>> {
+        let mut source_offset = 0usize;
+        self.instructions()
+            .iter()
+            .map(|instruction| {
+                let length = instruction.len() as usize;
+                match instruction {
+                    DeltaInstruction::Remove(_) => {
+                        source_offset += length;
+                        Vec::new()
+                    }
+                    DeltaInstruction::Copy(_) => {
+                        let read = source_offset..source_offset + length;
+                        source_offset += length;
+                        let first = chunks.partition_point(|chunk| chunk.range.end <= read.start);
+                        (first..chunks.len())
+                            .take_while(|index| chunks[*index].range.start < read.end)
+                            .collect()
+                    }
+                    _ => Vec::new(),
+                }
+            })
+            .collect()
+    }
+
+    /// Indices of the source `chunks` the patch reads, in order. A peer holding only these
+    /// chunks can apply the patch with the rest of the source left zeroed.
+    pub fn required_chunks<O>(&self, chunks: &[Chunk<O>]) -> Vec<usize> {
+        let mut required: Vec<usize> = self
+            .instruction_chunks(chunks)
+            .into_iter()
+            .flatten()
+            .collect();
+        required.dedup();
+        required
+    }
+}
+
+#[cfg(test)]
+mod chunk_tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::digest::digest_tests::Fnv1a;
+
+    const PARAMS: ChunkParams = ChunkParams {
+        min_size: 64,
+        average_size: 256,
+        max_size: 1024,
+    };
+
+    fn data(length: usize) -> Vec<u8> {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        (0..length)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn chunk_boundaries() {
+        let bytes = data(20_000);
+        let ranges = super::chunk_boundaries(&bytes, &PARAMS);
+        assert_eq!(ranges.first().map(|range| range.start), Some(0));
+        assert_eq!(ranges.last().map(|range| range.end), Some(bytes.len()));
+        assert!(ranges.windows(2).all(|pair| pair[0].end == pair[1].start));
+        assert!(ranges[..ranges.len() - 1]
+            .iter()
+            .all(|range| (PARAMS.min_size..=PARAMS.max_size).contains(&range.len())));
+        assert!(ranges.len() > 20_000 / 1024);
+        assert!(super::chunk_boundaries(&[], &PARAMS).is_empty());
+        assert!(super::chunk_boundaries(&[0; 3000], &PARAMS)
+            .iter()
+            .all(|range| range.len() == 1024 || range.end == 3000));
+
+        let mut edited = bytes.clone();
+        edited.splice(5000..5000, *b"inserted");
+        let before: HashSet<_> = chunk_ids::<Fnv1a>(&bytes, &PARAMS)
+            .into_iter()
+            .map(|chunk| chunk.id)
+            .collect();
+        let after = chunk_ids::<Fnv1a>(&edited, &PARAMS);
+        let changed = after
+            .iter()
+            .filter(|chunk| !before.contains(&chunk.id))
+            .count();
+        assert!(changed <= 2, "{} chunks changed", changed);
+    }
+
+    // Boundaries must not change between releases, or chunk ids computed by older peers stop
+    // matching. A change here is a format change.
+    #[test]
+    fn golden() {
+        assert_eq!(GEAR[0], 0x6e78_9e6a_a1b9_65f4);
+        assert_eq!(GEAR[1], 0x06c4_5d18_8009_454f);
+        let table: Vec<u8> = GEAR.iter().flat_map(|value| value.to_be_bytes()).collect();
+        assert_eq!(
+            Fnv1a::digest(&table),
+            [0xa9, 0x99, 0xdf, 0x06, 0x7b, 0xdf, 0x49, 0x33]
+        );
+        assert_eq!(
+            super::chunk_boundaries(&data(4000), &PARAMS),
+            [
+                0..129,
+                129..196,
+                196..277,
+                277..598,
+                598..892,
+                892..1916,
+                1916..2365,
+                2365..2448,
+                2448..3291,
+                3291..4000,
+            ]
+        );
+    }
+
+    #[test]
+    fn params_bytes() {
+        let bytes = PARAMS.to_bytes();
+        assert_eq!(bytes.len(), CHUNK_PARAMS_LENGTH);
+        assert_eq!(ChunkParams::try_from_bytes(&bytes), Ok(PARAMS));
+        assert_eq!(
+            ChunkParams::try_from_bytes(&ChunkParams::default().to_bytes()),
+            Ok(ChunkParams::default())
+        );
+        assert_eq!(
+            ChunkParams::try_from_bytes(b""),
+            Err(InstructionError::MissignSign)
+        );
+        assert_eq!(
+            ChunkParams::try_from_bytes(b"+"),
+            Err(InstructionError::InvalidSign)
+        );
+        assert_eq!(
+            ChunkParams::try_from_bytes(&bytes[..20]),
+            Err(InstructionError::MissingLength)
+        );
+        let mut long = bytes.clone();
+        long.push(0);
+        assert_eq!(
+            ChunkParams::try_from_bytes(&long),
+            Err(InstructionError::InvalidContent)
+        );
+    }
+
+    #[test]
+    fn required_chunks() {
+        let source = data(20_000);
+        let mut target = source[..4000].to_vec();
+        target.extend(data(3000).iter().map(|byte| byte ^ 0x5a));
+        target.extend(&source[12_000..13_000]);
+        let patch = Patch::new(&source, &target);
+        let chunks = chunk_ids::<Fnv1a>(&source, &PARAMS);
+        let mapping = patch.instruction_chunks(&chunks);
+        assert_eq!(mapping.len(), patch.instructions().len());
+        for (instruction, indices) in patch.instructions().iter().zip(&mapping) {
+            if !matches!(instruction, DeltaInstruction::Copy(_)) {
+                assert!(indices.is_empty());
+            }
+        }
+
+        let required = patch.required_chunks(&chunks);
+        assert!(required.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(required.len() < chunks.len());
+        let mut sparse = vec![0u8; source.len()];
+        for index in required {
+            let range = chunks[index].range.clone();
+            assert_eq!(Fnv1a::digest(&source[range.clone()]), chunks[index].id);
+            sparse[range.clone()].copy_from_slice(&source[range]);
+        }
+        assert_eq!(patch.apply(&sparse), Some(target));
+    }
+}
@@ -0,0 +1,328 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    checkpoint::crc32,
+    encode::fnv1a,
+    instructions::{
+        delta_instruction::DeltaInstruction, InstructionBytes, InstructionError, Result,
+    },
+    patch::{Patch, PatchBuilder},
+};
+
+pub const DICTIONARY_SIGN: u8 = b'@';
+const DICTIONARY_HEADER_SIGN: u8 = b'D';
+const FRAGMENT_LENGTH: usize = 16;
+pub const DEFAULT_DICTIONARY_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Dictionary {
+    bytes: Vec<u8>,
+    fragments: HashMap<u64, Vec<u32>>,
+}
+
+impl Dictionary {
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        let mut fragments: HashMap<u64, Vec<u32>> = HashMap::new();
+        for (position, window) in bytes.windows(FRAGMENT_LENGTH).enumerate() {
+            fragments
+                .entry(fnv1a(window))
+                .or_default()
+                .push(position as u32);
+        }
+        Self { bytes, fragments }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn id(&self) -> u32 {
+        crc32(0, &self.bytes)
+    }
+
+    fn longest_match(&self, content: &[u8]) -> Option<(usize, usize)> {
+        let window = content.get(..FRAGMENT_LENGTH)?;
+        self.fragments
+            .get(&fnv1a(window))?
+            .iter()
+            .map(|position| {
+                let position = *position as usize;
+                let length = self.bytes[position..]
+                    .iter()
+                    .zip(content)
+                    .take_while(|(dictionary, content)| dictionary == content)
+                    .count();
+                (position, length)
+            })
+            .filter(|(_, length)| *length >= FRAGMENT_LENGTH)
+            .max_by_key(|(position, length)| (*length, usize::MAX - position))
+    }
+}
+
+fn add_runs(patch: &Patch) -> Vec<Vec<u8>> {
+    let mut runs: Vec<Vec<u8>> = Vec::new();
+    let mut previous_add = false;
+    for instruction in patch.instructions() {
+        match instruction {
+            DeltaInstruction::Add(add) => {
+                match runs.last_mut() {
+                    Some(run) if previous_add => run.extend_from_slice(add.content()),
+                    _ => runs.push(add.content().to_vec()),
+                }
+                previous_add = true;
+            }
+            _ => previous_add = false,
+        }
+    }
+    runs
+}
+
+pub fn train_dictionary(patch_corpus: &[Patch]) -> Dictionary {
+    train_dictionary_with(patch_corpus, DEFAULT_DICTIONARY_SIZE)
+}
+
+pub fn train_dictionary_with(patch_corpus: &[Patch], max_size: usize) -> Dictionary {
+    let corpus: Vec<Vec<Vec<u8>>> = patch_corpus.iter().map(add_runs).collect();
+    let mut window_counts: HashMap<&[u8], usize> = HashMap::new();
+    for runs in corpus.iter() {
+        let windows: HashSet<&[u8]> = runs
+            .iter()
+            .flat_map(|run| run.windows(FRAGMENT_LENGTH))
+            .collect();
+        for window in windows {
+            *window_counts.entry(window).or_default() += 1;
+        }
+    }
+
+    let mut segment_patches: HashMap<&[u8], HashSet<usize>> = HashMap::new();
+    for (patch, runs) in corpus.iter().enumerate() {
+        for run in runs.iter() {
+            let mut covered = vec![false; run.len()];
+            for (position, window) in run.windows(FRAGMENT_LENGTH).enumerate() {
+                if window_counts[window] > 1 {
+                    covered[position..position + FRAGMENT_LENGTH].fill(true);
+                }
+            }
+            let mut position = 0usize;
+            while let Some(start) = covered[position..].iter().position(|covered| *covered) {
+                let start = position + start;
+                let length = covered[start..]
+                    .iter()
+                    .take_while(|covered| **covered)
+                    .count();
+                segment_patches
+                    .entry(&run[start..start + length])
+                    .or_default()
+                    .insert(patch);
+                position = start + length;
+            }
+        }
+    }
+
+    let mut segments: Vec<(&[u8], usize)> = segment_patches
+        .into_iter()
+        .map(|(segment, patches)| (segment, patches.len()))
+        .collect();
+    segments.sort_unstable_by(|(a, a_count), (b, b_count)| {
+        (b_count * b.len())
+            .cmp(&(a_count * a.len()))
+            .then_with(|| a.cmp(b))
+    });
+    let mut bytes: Vec<u8> = Vec::new();
+    for (segment, _) in segments {
+        if bytes.len() + segment.len() > max_size
+            || bytes.windows(segment.len()).any(|window| window == segment)
+        {
+            continue;
+        }
+        bytes.extend_from_slice(segment);
+    }
+    Dictionary::from_bytes(bytes)
+}
+
+fn push_add(bytes: &mut Vec<u8>, content: &[u8]) {
+    let mut builder = PatchBuilder::default();
+    builder.add(content);
+    bytes.extend(builder.build().to_bytes());
+}
+
+fn push_run(bytes: &mut Vec<u8>, run: &[u8], dictionary: &Dictionary) {
+    let mut literal_start = 0usize;
+    let mut position = 0usize;
+    while position < run.len() {
+        let Some((offset, length)) = dictionary.longest_match(&run[position..]) else {
+            position += 1;
+            continue;
+        };
+        push_add(bytes, &run[literal_start..position]);
+        let mut reference = offset;
+        let end = offset + length;
+        while reference < end {
+            let chunk = (end - reference).min(u8::MAX as usize);
+            bytes.push(DICTIONARY_SIGN);
+            bytes.push(chunk as u8);
+            bytes.extend((reference as u32).to_be_bytes());
+            reference += chunk;
+        }
+        position += length;
+        literal_start = position;
+    }
+    push_add(bytes, &run[literal_start..]);
+}
+
+impl Patch {
+    pub fn to_bytes_with_dictionary(&self, dictionary: &Dictionary) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(5 + self.byte_length());
+        bytes.push(DICTIONARY_HEADER_SIGN);
+        bytes.extend(dictionary.id().to_be_bytes());
+        let mut run: Vec<u8> = Vec::new();
+        for instruction in self.instructions() {
+            match instruction {
+                DeltaInstruction::Add(add) => run.extend_from_slice(add.content()),
+                _ => {
+                    push_run(&mut bytes, &run, dictionary);
+                    run.clear();
+                    bytes.extend(instruction.to_bytes());
+                }
+            }
+        }
+        push_run(&mut bytes, &run, dictionary);
+        bytes
+    }
+
+    pub fn try_from_bytes_with_dictionary(bytes: &[u8], dictionary: &Dictionary) -> Result<Self> {
+        match bytes.first() {
+            Some(&DICTIONARY_HEADER_SIGN) => (),
+            Some(_) => return Err(InstructionError::InvalidSign),
+            None => return Err(InstructionError::MissignSign),
+        }
+        let id = bytes.get(1..5).ok_or(InstructionError::MissingLength)?;
+        if u32::from_be_bytes(id.try_into().unwrap()) != dictionary.id() {
+            return Err(InstructionError::InvalidContent);
+        }
+        let mut builder = PatchBuilder::default();
+        let mut bytes_iter = bytes[5..].iter().peekable();
+        while let Some(&&sign) = bytes_iter.peek() {
+            if sign != DICTIONARY_SIGN {
+                match DeltaInstruction::try_from_bytes(&mut bytes_iter)? {
+                    DeltaInstruction::Add(add) => builder.add(add.content()),
+                    instruction => builder.instruction(instruction),
+                }
+                continue;
+            }
+            bytes_iter.next();
+            let length = *bytes_iter.next().ok_or(InstructionError::MissingLength)? as usize;
+            let offset: Vec<u8> = bytes_iter.by_ref().take(4).copied().collect();
+            let offset: [u8; 4] = offset
+                .try_into()
+                .map_err(|_| InstructionError::MissingContent)?;
+            let offset = u32::from_be_bytes(offset) as usize;
+            let content = offset
+                .checked_add(length)
+                .and_then(|end| dictionary.as_bytes().get(offset..end))
+                .ok_or(InstructionError::InvalidContent)?;
+            builder.add(content);
+        }
+        let patch = builder.build();
+        Self::validate_target_copies(patch.instructions())?;
+        Ok(patch)
+    }
+}
+
+#[cfg(test)]
+mod dictionary_tests {
+    use super::*;
+
+    fn fleet() -> (Vec<Vec<u8>>, Vec<Patch>) {
+        let source = b"[config]\nname = device\nlevel = 1\n".to_vec();
+        let targets: Vec<Vec<u8>> = (0..8)
+            .map(|device| {
+                format!(
+                    "[config]\nname = device-{}\nlevel = 1\nfirmware_url = https://updates.example.com/firmware/stable/latest.bin\n",
+                    device
+                )
+                .into_bytes()
+            })
+            .collect();
+        let patches = targets
+            .iter()
+            .map(|target| Patch::new(&source, target))
+            .collect();
+        (vec![source], patches)
+    }
+
+    #[test]
+    fn train_dictionary() {
+        let (_, patches) = fleet();
+        let dictionary = super::train_dictionary(&patches);
+        assert!(!dictionary.is_empty());
+        let bytes = String::from_utf8_lossy(dictionary.as_bytes()).into_owned();
+        assert!(bytes.contains("updates.example.com/firmware/stable"));
+        assert!(train_dictionary_with(&patches, 8).is_empty());
+        assert!(super::train_dictionary(&patches[..1]).is_empty());
+        assert_eq!(
+            super::train_dictionary(&patches),
+            Dictionary::from_bytes(dictionary.as_bytes().to_vec())
+        );
+    }
+
+    #[test]
+    fn bytes_with_dictionary() {
+        let (sources, patches) = fleet();
+        let dictionary = super::train_dictionary(&patches[..4]);
+        for patch in patches.iter() {
+            let bytes = patch.to_bytes_with_dictionary(&dictionary);
+            assert!(bytes.len() + 32 < patch.to_bytes().len());
+            let decoded = Patch::try_from_bytes_with_dictionary(&bytes, &dictionary).unwrap();
+            assert_eq!(&decoded, patch);
+            assert_eq!(decoded.apply(&sources[0]), patch.apply(&sources[0]));
+        }
+
+        let bytes = patches[0].to_bytes_with_dictionary(&dictionary);
+        assert_eq!(
+            Patch::try_from_bytes_with_dictionary(&bytes, &Dictionary::default()),
+            Err(InstructionError::InvalidContent)
+        );
+        assert_eq!(
+            Patch::try_from_bytes_with_dictionary(&patches[0].to_bytes(), &dictionary),
+            Err(InstructionError::InvalidSign)
+        );
+        let empty = Dictionary::default();
+        let bytes = patches[0].to_bytes_with_dictionary(&empty);
+        assert_eq!(bytes[5..], patches[0].to_bytes());
+        let reference = [b'D', 0, 0, 0, 0, DICTIONARY_SIGN, 1, 0, 0, 0, 0];
+        assert_eq!(
+            Patch::try_from_bytes_with_dictionary(&reference, &empty),
+            Err(InstructionError::InvalidContent)
+        );
+        assert_eq!(
+            Patch::try_from_bytes_with_dictionary(&reference[..8], &empty),
+            Err(InstructionError::MissingContent)
+        );
+        let reference = [
+            b'D',
+            0,
+            0,
+            0,
+            0,
+            DICTIONARY_SIGN,
+            u8::MAX,
+            0xff,
+            0xff,
+            0xff,
+            0xff,
+        ];
+        assert_eq!(
+            Patch::try_from_bytes_with_dictionary(&reference, &empty),
+            Err(InstructionError::InvalidContent)
+        );
+    }
+}
@@ -0,0 +1,73 @@
+pub trait Digest: Default {
+    type Output: AsRef<[u8]>;
+
+    fn update(&mut self, bytes: &[u8]);
+
+    fn finalize(self) -> Self::Output;
+
+    fn digest(bytes: &[u8]) -> Self::Output {
+        let mut hasher = Self::default();
+        hasher.update(bytes);
+        hasher.finalize()
+    }
+}
+
+#[cfg(feature = "manifest")]
+impl Digest for sha2::Sha256 {
+    type Output = [u8; 32];
+
+    fn update(&mut self, bytes: &[u8]) {
+        sha2::Digest::update(self, bytes);
+    }
+
+    fn finalize(self) -> Self::Output {
+        sha2::Digest::finalize(self).into()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod digest_tests {
+    use super::*;
+
+    #[derive(Debug)]
+    pub(crate) struct Fnv1a(u64);
+
+    impl Default for Fnv1a {
+        fn default() -> Self {
+            Self(0xcbf29ce484222325)
+        }
+    }
+
+    impl Digest for Fnv1a {
+        type Output = [u8; 8];
+
+        fn update(&mut self, bytes: &[u8]) {
+            for byte in bytes {
+                self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100000001b3);
+            }
+        }
+
+        fn finalize(self) -> Self::Output {
+            self.0.to_be_bytes()
+        }
+    }
+
+    #[test]
+    fn digest() {
+        assert_eq!(Fnv1a::digest(b""), 0xcbf29ce484222325u64.to_be_bytes());
+        assert_eq!(Fnv1a::digest(b"a"), 0xaf63dc4c8601ec8cu64.to_be_bytes());
+        let mut hasher = Fnv1a::default();
+        hasher.update(b"foo");
+        hasher.update(b"bar");
+        assert_eq!(hasher.finalize(), Fnv1a::digest(b"foobar"));
+    }
+
+    #[cfg(feature = "manifest")]
+    #[test]
+    fn digest_sha256() {
+        assert_eq!(
+            <sha2::Sha256 as Digest>::digest(b"abc"),
+            <[u8; 32]>::from(<sha2::Sha256 as sha2::Digest>::digest(b"abc"))
+        );
+    }
+}
@@ -0,0 +1,279 @@
+use std::error::Error;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Edit {
+    Insert {
+        offset: usize,
+        content: Vec<u8>,
+    },
+    Delete {
+        offset: usize,
+        length: usize,
+    },
+    Replace {
+        offset: usize,
+        length: usize,
+        content: Vec<u8>,
+    },
+}
+
+impl Edit {
+    pub(crate) fn range(&self) -> (usize, usize) {
+        match self {
+            Edit::Insert { offset, .. } => (*offset, 0),
+            Edit::Delete { offset, length } | Edit::Replace { offset, length, .. } => {
+                (*offset, *length)
+            }
+        }
+    }
+
+    pub(crate) fn content(&self) -> &[u8] {
+        match self {
+            Edit::Insert { content, .. } | Edit::Replace { content, .. } => content,
+            Edit::Delete { .. } => &[],
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum EditError {
+    OutOfBounds {
+        edit: usize,
+        offset: usize,
+        length: usize,
+    },
+    /// A target copy at this target offset reads bytes the edit replaces.
+    TargetCopyConflict(usize),
+}
+
+impl std::fmt::Display for EditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EditError::OutOfBounds {
+                edit,
+                offset,
+                length,
+            } => write!(
+                f,
+                "Edit {} covers {}..{} which is outside the document",
+                edit,
+                offset,
+                offset + length
+            ),
+            EditError::TargetCopyConflict(offset) => write!(
+                f,
+                "Target copy at {} reads bytes replaced by the edit",
+                offset
+            ),
+        }
+    }
+}
+
+impl Error for EditError {}
+
+#[derive(Debug, Default)]
+pub(crate) struct EditCollector {
+    edits: Vec<Edit>,
+    offset: usize,
+    length: usize,
+    content: Vec<u8>,
+}
+
+impl EditCollector {
+    pub(crate) fn keep(&mut self, length: usize) {
+        self.flush();
+        self.offset += length;
+    }
+
+    pub(crate) fn delete(&mut self, length: usize) {
+        self.length += length;
+    }
+
+    pub(crate) fn insert(&mut self, content: &[u8]) {
+        self.content.extend(content);
+    }
+
+    fn flush(&mut self) {
+        let offset = self.offset;
+        let length = std::mem::take(&mut self.length);
+        let content = std::mem::take(&mut self.content);
+        self.offset += content.len();
+        match (length, content.is_empty()) {
+            (0, true) => (),
+            (0, false) => self.edits.push(Edit::Insert { offset, content }),
+            (_, true) => self.edits.push(Edit::Delete { offset, length }),
+            (_, false) => self.edits.push(Edit::Replace {
+                offset,
+                length,
+                content,
+            }),
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> Vec<Edit> {
+        self.flush();
+        self.edits
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum Segment {
+    Source { start: usize, end: usize },
+    Inserted(Vec<u8>),
+}
+
+impl Segment {
+    fn len(&self) -> usize {
+        match self {
+            Segment::Source { start, end } => end - start,
+            Segment::Inserted(content) => content.len(),
+        }
+    }
+
+    fn split_off(&mut self, at: usize) -> Segment {
+        match self {
+            Segment::Source { start, end } => {
+                let tail = Segment::Source {
+                    start: *start + at,
+                    end: *end,
+                };
+                *end = *start + at;
+                tail
+            }
+            Segment::Inserted(content) => Segment::Inserted(content.split_off(at)),
+        }
+    }
+}
+
+fn boundary(segments: &mut Vec<Segment>, position: usize) -> usize {
+    let mut offset = 0usize;
+    for index in 0..segments.len() {
+        let length = segments[index].len();
+        if position == offset {
+            return index;
+        }
+        if position < offset + length {
+            let tail = segments[index].split_off(position - offset);
+            segments.insert(index + 1, tail);
+            return index + 1;
+        }
+        offset += length;
+    }
+    segments.len()
+}
+
+pub(crate) fn segments(source_length: usize, edits: &[Edit]) -> Result<Vec<Segment>, EditError> {
+    let mut segments: Vec<Segment> = Vec::new();
+    if source_length > 0 {
+        segments.push(Segment::Source {
+            start: 0,
+            end: source_length,
+        });
+    }
+    let mut document_length = source_length;
+    for (index, edit) in edits.iter().enumerate() {
+        let (offset, length) = edit.range();
+        if offset
+            .checked_add(length)
+            .is_none_or(|end| end > document_length)
+        {
+            return Err(EditError::OutOfBounds {
+                edit: index,
+                offset,
+                length,
+            });
+        }
+        let start = boundary(&mut segments, offset);
+        let end = boundary(&mut segments, offset + length);
+        segments.drain(start..end);
+        if !edit.content().is_empty() {
+            segments.insert(start, Segment::Inserted(edit.content().to_vec()));
+        }
+        document_length = document_length - length + edit.content().len();
+    }
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod edit_tests {
+    use super::*;
+
+    #[test]
+    fn segments() {
+        let edits = vec![
+            Edit::Insert {
+                offset: 0,
+                content: b"AB".to_vec(),
+            },
+            Edit::Delete {
+                offset: 3,
+                length: 2,
+            },
+            Edit::Replace {
+                offset: 1,
+                length: 2,
+                content: b"C".to_vec(),
+            },
+        ];
+        assert_eq!(
+            super::segments(10, &edits).unwrap(),
+            vec![
+                Segment::Inserted(b"A".to_vec()),
+                Segment::Inserted(b"C".to_vec()),
+                Segment::Source { start: 3, end: 10 },
+            ]
+        );
+        assert_eq!(super::segments(0, &[]).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn edit_collector() {
+        let mut collector = EditCollector::default();
+        collector.keep(2);
+        collector.delete(1);
+        collector.insert(b"AB");
+        collector.keep(1);
+        collector.insert(b"C");
+        collector.keep(1);
+        collector.delete(3);
+        assert_eq!(
+            collector.finish(),
+            vec![
+                Edit::Replace {
+                    offset: 2,
+                    length: 1,
+                    content: b"AB".to_vec()
+                },
+                Edit::Insert {
+                    offset: 5,
+                    content: b"C".to_vec()
+                },
+                Edit::Delete {
+                    offset: 7,
+                    length: 3
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn segments_err() {
+        let edits = vec![Edit::Delete {
+            offset: 2,
+            length: 2,
+        }];
+        assert_eq!(
+            super::segments(3, &edits),
+            Err(EditError::OutOfBounds {
+                edit: 0,
+                offset: 2,
+                length: 2
+            })
+        );
+        let edits = vec![Edit::Insert {
+            offset: usize::MAX,
+            content: Vec::new(),
+        }];
+        assert!(super::segments(3, &edits).is_err());
+    }
+}
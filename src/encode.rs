@@ -0,0 +1,1140 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    mem,
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    instructions::{delta_instruction::DeltaInstruction, InstructionError},
+    patch::{Patch, PatchBuilder},
+    telemetry,
+};
+
+const BLOCK_SIZE: usize = 8;
+/// Source positions checked per block lookup. Hash collisions can't make a lookup scan more
+/// than this, which keeps block hashing linear in the target for any input.
+const MAX_CANDIDATES: usize = 64;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EncodeAlgorithm {
+    Lcs,
+    BlockHash,
+    Append,
+}
+
+impl EncodeAlgorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EncodeAlgorithm::Lcs => "lcs",
+            EncodeAlgorithm::BlockHash => "block_hash",
+            EncodeAlgorithm::Append => "append",
+        }
+    }
+}
+
+/// Thresholds used by [`auto_select_with`].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SelectionThresholds {
+    /// Largest `source_len * target_len` LCS table to build before switching to block hashing.
+    pub max_lcs_cells: usize,
+    /// Below this similarity LCS rarely finds more than block hashing, so block hashing is used.
+    pub min_lcs_similarity: f64,
+    /// At or above this similarity a growing target is assumed to append to the source.
+    pub append_similarity: f64,
+}
+
+impl Default for SelectionThresholds {
+    fn default() -> Self {
+        Self {
+            max_lcs_cells: 1 << 24,
+            min_lcs_similarity: 0.05,
+            append_similarity: 0.99,
+        }
+    }
+}
+
+/// Picks an encoder for the given input sizes using the default [`SelectionThresholds`].
+///
+/// `similarity_hint` is the caller's estimate of the fraction of the target shared with the
+/// source, if known. [`Patch::new`] calls this without a hint.
+///
+/// LCS is only picked while `source_len * target_len` stays within
+/// [`SelectionThresholds::max_lcs_cells`], so its quadratic time and memory are bounded by
+/// that product. Larger inputs go to block hashing, which takes `O(source_len + target_len)`
+/// time whatever the contents.
+pub fn auto_select(
+    source_len: usize,
+    target_len: usize,
+    similarity_hint: Option<f64>,
+) -> EncodeAlgorithm {
+    auto_select_with(
+        source_len,
+        target_len,
+        similarity_hint,
+        &SelectionThresholds::default(),
+    )
+}
+
+/// Like [`auto_select`], with caller supplied thresholds.
+pub fn auto_select_with(
+    source_len: usize,
+    target_len: usize,
+    similarity_hint: Option<f64>,
+    thresholds: &SelectionThresholds,
+) -> EncodeAlgorithm {
+    if let Some(similarity) = similarity_hint {
+        if similarity >= thresholds.append_similarity && target_len >= source_len {
+            return EncodeAlgorithm::Append;
+        }
+        if similarity < thresholds.min_lcs_similarity {
+            return EncodeAlgorithm::BlockHash;
+        }
+    }
+    match source_len.checked_mul(target_len) {
+        Some(cells) if cells <= thresholds.max_lcs_cells => EncodeAlgorithm::Lcs,
+        _ => EncodeAlgorithm::BlockHash,
+    }
+}
+
+/// Add content at or above this many bits per byte is treated as already compressed.
+const DENSE_ENTROPY: f64 = 7.5;
+
+fn entropy<'a>(chunks: impl Iterator<Item = &'a [u8]>) -> (usize, f64) {
+    let mut counts = [0usize; 256];
+    for chunk in chunks {
+        for byte in chunk {
+            counts[*byte as usize] += 1;
+        }
+    }
+    let total: usize = counts.iter().sum();
+    let bits = counts
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let probability = *count as f64 / total as f64;
+            -probability * probability.log2()
+        })
+        .sum();
+    (total, bits)
+}
+
+/// Records how [`Patch::encode_reported`] produced a patch.
+#[derive(Debug, PartialEq, Clone)]
+pub struct EncodeReport {
+    pub algorithm: EncodeAlgorithm,
+    pub thresholds: SelectionThresholds,
+    pub similarity_hint: Option<f64>,
+    pub block_size: usize,
+    pub source_length: usize,
+    pub target_length: usize,
+    pub patch_length: usize,
+    pub added_bytes: usize,
+    /// Shannon entropy of the add content in bits per byte, 0 without adds.
+    pub add_entropy: f64,
+    pub selection_duration: Duration,
+    pub encode_duration: Duration,
+}
+
+impl EncodeReport {
+    /// Hash of everything that decides the patch bytes for given inputs: the crate version,
+    /// algorithm and parameters. Timings and lengths aren't included.
+    pub fn config_hash(&self) -> u64 {
+        let mut config: Vec<u8> = Vec::new();
+        config.extend(env!("CARGO_PKG_VERSION").as_bytes());
+        config.push(0);
+        config.extend(self.algorithm.as_str().as_bytes());
+        config.push(0);
+        config.extend((self.thresholds.max_lcs_cells as u64).to_be_bytes());
+        config.extend(self.thresholds.min_lcs_similarity.to_bits().to_be_bytes());
+        config.extend(self.thresholds.append_similarity.to_bits().to_be_bytes());
+        match self.similarity_hint {
+            Some(hint) => {
+                config.push(1);
+                config.extend(hint.to_bits().to_be_bytes());
+            }
+            None => config.push(0),
+        }
+        config.extend((self.block_size as u64).to_be_bytes());
+        fnv1a(&config)
+    }
+
+    /// Whether an outer compressor is likely to shrink the patch. Patch structure and copy
+    /// deltas compress well, so only patches mostly made of dense add content are skipped.
+    pub fn benefits_from_compression(&self) -> bool {
+        self.added_bytes * 2 < self.patch_length || self.add_entropy < DENSE_ENTROPY
+    }
+
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"version\":\"{}\",\"algorithm\":\"{}\",\"max_lcs_cells\":{},\"min_lcs_similarity\":{},\"append_similarity\":{},\"similarity_hint\":{},\"block_size\":{},\"source_length\":{},\"target_length\":{},\"patch_length\":{},\"added_bytes\":{},\"add_entropy\":{:.3},\"benefits_from_compression\":{},\"selection_ns\":{},\"encode_ns\":{},\"config_hash\":\"{:016x}\"}}",
+            env!("CARGO_PKG_VERSION"),
+            self.algorithm.as_str(),
+            self.thresholds.max_lcs_cells,
+            self.thresholds.min_lcs_similarity,
+            self.thresholds.append_similarity,
+            self.similarity_hint
+                .map_or_else(|| String::from("null"), |hint| hint.to_string()),
+            self.block_size,
+            self.source_length,
+            self.target_length,
+            self.patch_length,
+            self.added_bytes,
+            self.add_entropy,
+            self.benefits_from_compression(),
+            self.selection_duration.as_nanos(),
+            self.encode_duration.as_nanos(),
+            self.config_hash(),
+        )
+    }
+}
+
+const INDEX_MAGIC: [u8; 4] = *b"DIDX";
+const INDEX_HEADER_LENGTH: usize = 21;
+
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum SourceIndexError {
+    Truncated,
+    InvalidMagic,
+    InvalidBlockSize,
+    InvalidPosition,
+    /// A signature delta didn't apply to the previous index.
+    InvalidDiff,
+}
+
+impl std::fmt::Display for SourceIndexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SourceIndexError::Truncated => write!(f, "Source index ended unexpectedly"),
+            SourceIndexError::InvalidMagic => write!(f, "Source index magic didn't match"),
+            SourceIndexError::InvalidBlockSize => {
+                write!(f, "Source index block size isn't {} bytes", BLOCK_SIZE)
+            }
+            SourceIndexError::InvalidPosition => {
+                write!(f, "Source index position lies outside the source")
+            }
+            SourceIndexError::InvalidDiff => {
+                write!(f, "Source index delta doesn't apply to the previous index")
+            }
+        }
+    }
+}
+
+impl Error for SourceIndexError {}
+
+struct IndexReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> IndexReader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], SourceIndexError> {
+        let taken = self
+            .bytes
+            .get(self.offset..self.offset + length)
+            .ok_or(SourceIndexError::Truncated)?;
+        self.offset += length;
+        Ok(taken)
+    }
+
+    fn u64(&mut self) -> Result<u64, SourceIndexError> {
+        self.take(8)
+            .map(|bytes| u64::from_be_bytes(bytes.try_into().unwrap()))
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SourceIndex {
+    source_length: usize,
+    blocks: HashMap<u64, Vec<usize>>,
+}
+
+impl SourceIndex {
+    pub fn new(source: &[u8]) -> Self {
+        let mut blocks: HashMap<u64, Vec<usize>> = HashMap::new();
+        for (block, bytes) in source.chunks_exact(BLOCK_SIZE).enumerate() {
+            blocks
+                .entry(fnv1a(bytes))
+                .or_default()
+                .push(block * BLOCK_SIZE);
+        }
+        Self {
+            source_length: source.len(),
+            blocks,
+        }
+    }
+
+    pub fn source_length(&self) -> usize {
+        self.source_length
+    }
+
+    /// Approximate bytes allocated by the index: the hash table plus every position list.
+    pub fn heap_size(&self) -> usize {
+        self.blocks.capacity() * (mem::size_of::<(u64, Vec<usize>)>() + 1)
+            + self
+                .blocks
+                .values()
+                .map(|positions| positions.capacity() * mem::size_of::<usize>())
+                .sum::<usize>()
+    }
+
+    fn find(&self, source: &[u8], block: &[u8], from: usize) -> Option<usize> {
+        let positions = self.blocks.get(&fnv1a(block))?;
+        let first = positions.partition_point(|position| *position < from);
+        positions[first..]
+            .iter()
+            .copied()
+            .take(MAX_CANDIDATES)
+            .find(|position| &source[*position..*position + BLOCK_SIZE] == block)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut entries: Vec<(&u64, &Vec<usize>)> = self.blocks.iter().collect();
+        entries.sort_unstable_by_key(|(hash, _)| **hash);
+        let mut bytes: Vec<u8> = Vec::with_capacity(
+            INDEX_HEADER_LENGTH + self.blocks.len() * 12 + self.source_length / BLOCK_SIZE * 8,
+        );
+        bytes.extend(INDEX_MAGIC);
+        bytes.push(BLOCK_SIZE as u8);
+        bytes.extend((self.source_length as u64).to_be_bytes());
+        bytes.extend((entries.len() as u64).to_be_bytes());
+        for (hash, positions) in entries {
+            bytes.extend(hash.to_be_bytes());
+            bytes.extend((positions.len() as u32).to_be_bytes());
+            for position in positions {
+                bytes.extend((*position as u64).to_be_bytes());
+            }
+        }
+        bytes
+    }
+
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, SourceIndexError> {
+        let mut reader = IndexReader { bytes, offset: 0 };
+        if reader.take(4)? != INDEX_MAGIC {
+            return Err(SourceIndexError::InvalidMagic);
+        }
+        if reader.take(1)? != [BLOCK_SIZE as u8] {
+            return Err(SourceIndexError::InvalidBlockSize);
+        }
+        let source_length = reader.u64()? as usize;
+        let entries = reader.u64()?;
+        let mut blocks: HashMap<u64, Vec<usize>> = HashMap::new();
+        for _ in 0..entries {
+            let hash = reader.u64()?;
+            let count = u32::from_be_bytes(reader.take(4)?.try_into().unwrap());
+            let mut positions: Vec<usize> = Vec::new();
+            for _ in 0..count {
+                let position = reader.u64()? as usize;
+                if !position.is_multiple_of(BLOCK_SIZE)
+                    || position
+                        .checked_add(BLOCK_SIZE)
+                        .is_none_or(|end| end > source_length)
+                    || positions.last().is_some_and(|last| *last >= position)
+                {
+                    return Err(SourceIndexError::InvalidPosition);
+                }
+                positions.push(position);
+            }
+            blocks.insert(hash, positions);
+        }
+        Ok(Self {
+            source_length,
+            blocks,
+        })
+    }
+
+    /// Encodes this index as an entropy coded patch against `previous`, the index a client
+    /// already has.
+    ///
+    /// Entries are serialized sorted by block hash, so blocks that didn't change keep their
+    /// bytes. The unchanged copies code to almost nothing, which keeps the delta small when
+    /// most of the source is untouched.
+    pub fn diff_from(&self, previous: &SourceIndex) -> Vec<u8> {
+        Patch::new(&previous.to_bytes(), &self.to_bytes()).to_entropy_coded()
+    }
+
+    /// Rebuilds the index a delta from [`SourceIndex::diff_from`] was encoded for, with `self`
+    /// as the previous index.
+    pub fn apply_diff(&self, delta: &[u8]) -> Result<Self, SourceIndexError> {
+        let bytes = Patch::try_from_entropy_coded(delta)
+            .ok()
+            .and_then(|patch| patch.apply(&self.to_bytes()))
+            .ok_or(SourceIndexError::InvalidDiff)?;
+        Self::try_from_bytes(&bytes)
+    }
+}
+
+fn block_hash(index: &SourceIndex, source: &[u8], target: &[u8]) -> Patch {
+    ResumableEncoder {
+        index,
+        source,
+        target,
+        source_offset: 0,
+        target_offset: 0,
+        added_from: 0,
+        builder: PatchBuilder::default(),
+    }
+    .finish()
+}
+
+const CHECKPOINT_MAGIC: [u8; 4] = *b"DECK";
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum EncoderCheckpointError {
+    Truncated,
+    InvalidMagic,
+    InvalidPatch(InstructionError),
+}
+
+impl std::fmt::Display for EncoderCheckpointError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncoderCheckpointError::Truncated => write!(f, "Encoder checkpoint ended unexpectedly"),
+            EncoderCheckpointError::InvalidMagic => {
+                write!(f, "Encoder checkpoint magic didn't match")
+            }
+            EncoderCheckpointError::InvalidPatch(error) => {
+                write!(f, "Encoder checkpoint holds an invalid patch: {}", error)
+            }
+        }
+    }
+}
+
+impl Error for EncoderCheckpointError {}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct EncoderCheckpoint {
+    source_length: usize,
+    target_length: usize,
+    source_offset: usize,
+    target_offset: usize,
+    added_from: usize,
+    patch: Patch,
+}
+
+impl EncoderCheckpoint {
+    pub fn target_offset(&self) -> usize {
+        self.target_offset
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(44 + self.patch.byte_length());
+        bytes.extend(CHECKPOINT_MAGIC);
+        for value in [
+            self.source_length,
+            self.target_length,
+            self.source_offset,
+            self.target_offset,
+            self.added_from,
+        ] {
+            bytes.extend((value as u64).to_be_bytes());
+        }
+        bytes.extend(self.patch.to_bytes());
+        bytes
+    }
+
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, EncoderCheckpointError> {
+        if bytes.get(..4) != Some(&CHECKPOINT_MAGIC[..]) {
+            return match bytes.len() < 4 {
+                true => Err(EncoderCheckpointError::Truncated),
+                false => Err(EncoderCheckpointError::InvalidMagic),
+            };
+        }
+        let mut reader = IndexReader { bytes, offset: 4 };
+        let mut values = [0usize; 5];
+        for value in values.iter_mut() {
+            *value = reader
+                .u64()
+                .map_err(|_| EncoderCheckpointError::Truncated)? as usize;
+        }
+        let [source_length, target_length, source_offset, target_offset, added_from] = values;
+        let patch = Patch::try_from_bytes(&bytes[reader.offset..])
+            .map_err(EncoderCheckpointError::InvalidPatch)?;
+        Ok(Self {
+            source_length,
+            target_length,
+            source_offset,
+            target_offset,
+            added_from,
+            patch,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ResumableEncoder<'a> {
+    index: &'a SourceIndex,
+    source: &'a [u8],
+    target: &'a [u8],
+    source_offset: usize,
+    target_offset: usize,
+    added_from: usize,
+    builder: PatchBuilder,
+}
+
+impl<'a> ResumableEncoder<'a> {
+    pub fn new(index: &'a SourceIndex, source: &'a [u8], target: &'a [u8]) -> Option<Self> {
+        Self::resume(
+            index,
+            source,
+            target,
+            EncoderCheckpoint {
+                source_length: source.len(),
+                target_length: target.len(),
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn resume(
+        index: &'a SourceIndex,
+        source: &'a [u8],
+        target: &'a [u8],
+        checkpoint: EncoderCheckpoint,
+    ) -> Option<Self> {
+        if index.source_length() != source.len()
+            || checkpoint.source_length != source.len()
+            || checkpoint.target_length != target.len()
+            || checkpoint.source_offset > source.len()
+            || checkpoint.added_from > checkpoint.target_offset
+            || checkpoint.target_offset > target.len()
+            || checkpoint.patch.source_length() != checkpoint.source_offset
+            || checkpoint.patch.target_length() != checkpoint.added_from
+            || checkpoint
+                .patch
+                .apply(&source[..checkpoint.source_offset])
+                .as_deref()
+                != Some(&target[..checkpoint.added_from])
+        {
+            return None;
+        }
+        Some(Self {
+            index,
+            source,
+            target,
+            source_offset: checkpoint.source_offset,
+            target_offset: checkpoint.target_offset,
+            added_from: checkpoint.added_from,
+            builder: PatchBuilder::from_patch(checkpoint.patch),
+        })
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.target_offset + BLOCK_SIZE > self.target.len()
+    }
+
+    pub fn step(&mut self, budget: usize) -> bool {
+        let stop = self.target_offset.saturating_add(budget);
+        while self.target_offset < stop && !self.is_done() {
+            self.advance();
+        }
+        self.is_done()
+    }
+
+    fn advance(&mut self) {
+        let (source, target) = (self.source, self.target);
+        let block = &target[self.target_offset..self.target_offset + BLOCK_SIZE];
+        let Some(mut source_start) = self.index.find(source, block, self.source_offset) else {
+            self.target_offset += 1;
+            return;
+        };
+        let mut target_start = self.target_offset;
+        while source_start > self.source_offset
+            && target_start > self.added_from
+            && source[source_start - 1] == target[target_start - 1]
+        {
+            source_start -= 1;
+            target_start -= 1;
+        }
+        let length = source[source_start..]
+            .iter()
+            .zip(&target[target_start..])
+            .take_while(|(source, target)| source == target)
+            .count();
+        self.builder.add(&target[self.added_from..target_start]);
+        self.builder.remove(source_start - self.source_offset);
+        self.builder.copy_unchanged(length);
+        self.source_offset = source_start + length;
+        self.target_offset = target_start + length;
+        self.added_from = self.target_offset;
+    }
+
+    pub fn checkpoint(&self) -> EncoderCheckpoint {
+        EncoderCheckpoint {
+            source_length: self.source.len(),
+            target_length: self.target.len(),
+            source_offset: self.source_offset,
+            target_offset: self.target_offset,
+            added_from: self.added_from,
+            patch: self.builder.clone().build(),
+        }
+    }
+
+    pub fn finish(mut self) -> Patch {
+        while !self.is_done() {
+            self.advance();
+        }
+        self.builder.add(&self.target[self.added_from..]);
+        self.builder.remove(self.source.len() - self.source_offset);
+        self.builder.build()
+    }
+}
+
+impl Patch {
+    pub fn encode_with(source: &[u8], target: &[u8], algorithm: EncodeAlgorithm) -> Self {
+        let start = Instant::now();
+        let patch = match algorithm {
+            EncodeAlgorithm::Lcs => Self::new_lcs(source, target),
+            EncodeAlgorithm::BlockHash => block_hash(&SourceIndex::new(source), source, target),
+            EncodeAlgorithm::Append if target.starts_with(source) => {
+                let mut builder = PatchBuilder::default();
+                builder.copy_unchanged(source.len());
+                builder.add(&target[source.len()..]);
+                builder.build()
+            }
+            EncodeAlgorithm::Append => match auto_select(source.len(), target.len(), None) {
+                EncodeAlgorithm::Lcs => Self::new_lcs(source, target),
+                _ => block_hash(&SourceIndex::new(source), source, target),
+            },
+        };
+        telemetry::record_encode(target.len(), patch.byte_length(), start.elapsed());
+        patch
+    }
+
+    /// Like [`Patch::new`] with caller supplied selection inputs, also returning how the patch
+    /// was produced.
+    pub fn encode_reported(
+        source: &[u8],
+        target: &[u8],
+        similarity_hint: Option<f64>,
+        thresholds: &SelectionThresholds,
+    ) -> (Self, EncodeReport) {
+        let start = Instant::now();
+        let algorithm = auto_select_with(source.len(), target.len(), similarity_hint, thresholds);
+        let selection_duration = start.elapsed();
+        let start = Instant::now();
+        let patch = Self::encode_with(source, target, algorithm);
+        let encode_duration = start.elapsed();
+        let (added_bytes, add_entropy) = entropy(patch.instructions().iter().filter_map(
+            |instruction| match instruction {
+                DeltaInstruction::Add(add) => Some(add.content()),
+                _ => None,
+            },
+        ));
+        let report = EncodeReport {
+            algorithm,
+            thresholds: *thresholds,
+            similarity_hint,
+            block_size: BLOCK_SIZE,
+            source_length: source.len(),
+            target_length: target.len(),
+            patch_length: patch.byte_length(),
+            added_bytes,
+            add_entropy,
+            selection_duration,
+            encode_duration,
+        };
+        (patch, report)
+    }
+
+    pub fn new_with_hints(source: &[u8], target: &[u8], dirty_ranges: &[Range<usize>]) -> Self {
+        let start = Instant::now();
+        let mut ranges: Vec<Range<usize>> = dirty_ranges
+            .iter()
+            .map(|range| range.start.min(target.len())..range.end.min(target.len()))
+            .filter(|range| !range.is_empty())
+            .collect();
+        ranges.push(source.len().min(target.len())..target.len());
+        ranges.sort_unstable_by_key(|range| range.start);
+        let mut builder = PatchBuilder::default();
+        let mut offset = 0usize;
+        for range in ranges {
+            if range.end <= offset {
+                continue;
+            }
+            let range = range.start.max(offset)..range.end;
+            builder.copy_unchanged(range.start - offset);
+            let shared = range.end.min(source.len()).max(range.start);
+            builder.copy(&source[range.start..shared], &target[range.start..shared]);
+            builder.add(&target[shared..range.end]);
+            offset = range.end;
+        }
+        builder.copy_unchanged(target.len() - offset);
+        builder.remove(source.len().saturating_sub(target.len()));
+        let patch = builder.build();
+        telemetry::record_encode(target.len(), patch.byte_length(), start.elapsed());
+        patch
+    }
+
+    /// Re-encodes `target` reusing `previous`, a patch from `source` to an earlier target.
+    ///
+    /// The instructions for the prefix and suffix `target` still shares with the earlier
+    /// target are replayed from `previous`, so only the changed window between them is
+    /// diffed. Falls back to [`Patch::new`] if `previous` doesn't apply to `source`.
+    pub fn new_with_previous(source: &[u8], target: &[u8], previous: &Patch) -> Self {
+        let Some(previous_target) = previous.apply(source) else {
+            return Self::new(source, target);
+        };
+        let start = Instant::now();
+        let prefix = previous_target
+            .iter()
+            .zip(target)
+            .take_while(|(old, new)| old == new)
+            .count();
+        let suffix = previous_target
+            .iter()
+            .rev()
+            .zip(target.iter().rev())
+            .take_while(|(old, new)| old == new)
+            .count()
+            .min(previous_target.len().min(target.len()) - prefix);
+        let resume = previous_target.len() - suffix;
+        let window = prefix..target.len() - suffix;
+
+        let encode_window = |builder: &mut PatchBuilder, source_range: Range<usize>| {
+            let (source, target) = (&source[source_range], &target[window.clone()]);
+            let patch = match auto_select(source.len(), target.len(), None) {
+                EncodeAlgorithm::Lcs => Self::new_lcs(source, target),
+                _ => block_hash(&SourceIndex::new(source), source, target),
+            };
+            for instruction in patch.instructions() {
+                builder.instruction(instruction.clone());
+            }
+        };
+
+        let mut builder = PatchBuilder::default();
+        let (mut source_offset, mut target_offset) = (0usize, 0usize);
+        let mut window_source: Option<usize> = None;
+        let mut window_encoded = false;
+        for instruction in previous.instructions() {
+            let (consumes, produces) = match instruction {
+                DeltaInstruction::Remove(_) => (true, false),
+                DeltaInstruction::Copy(_) => (true, true),
+                DeltaInstruction::Add(_) => (false, true),
+                DeltaInstruction::TargetCopy(_) => (false, true),
+            };
+            for _ in 0..instruction.len_usize() {
+                let in_suffix = match produces {
+                    true => target_offset >= resume,
+                    false => target_offset > resume,
+                };
+                if target_offset >= prefix && !in_suffix {
+                    window_source.get_or_insert(source_offset);
+                } else {
+                    if in_suffix && !window_encoded {
+                        let start = window_source.unwrap_or(source_offset);
+                        encode_window(&mut builder, start..source_offset);
+                        window_encoded = true;
+                    }
+                    let position = match in_suffix {
+                        true => target_offset - resume + window.end,
+                        false => target_offset,
+                    };
+                    match instruction {
+                        DeltaInstruction::Remove(_) => builder.remove(1),
+                        DeltaInstruction::Copy(_) => builder.copy(
+                            &source[source_offset..=source_offset],
+                            &target[position..=position],
+                        ),
+                        DeltaInstruction::TargetCopy(copy)
+                            if !in_suffix || target_offset - copy.distance() as usize >= resume =>
+                        {
+                            builder.target_copy(copy.distance() as usize, 1)
+                        }
+                        _ => builder.add(&target[position..=position]),
+                    }
+                }
+                source_offset += consumes as usize;
+                target_offset += produces as usize;
+            }
+        }
+        if !window_encoded {
+            let start = window_source.unwrap_or(source_offset);
+            encode_window(&mut builder, start..source_offset);
+        }
+        let patch = builder.build();
+        telemetry::record_encode(target.len(), patch.byte_length(), start.elapsed());
+        patch
+    }
+
+    pub fn new_with_index(index: &SourceIndex, source: &[u8], target: &[u8]) -> Option<Self> {
+        if index.source_length() != source.len() {
+            return None;
+        }
+        let start = Instant::now();
+        let patch = block_hash(index, source, target);
+        telemetry::record_encode(target.len(), patch.byte_length(), start.elapsed());
+        Some(patch)
+    }
+}
+
+#[cfg(test)]
+mod encode_tests {
+    use std::fs;
+
+    use super::*;
+    use crate::instructions::{delta_instruction::DeltaInstruction, InstructionInfo};
+
+    #[test]
+    fn auto_select() {
+        assert_eq!(super::auto_select(100, 100, None), EncodeAlgorithm::Lcs);
+        assert_eq!(
+            super::auto_select(1 << 20, 1 << 20, None),
+            EncodeAlgorithm::BlockHash
+        );
+        assert_eq!(
+            super::auto_select(usize::MAX, 2, None),
+            EncodeAlgorithm::BlockHash
+        );
+        assert_eq!(
+            super::auto_select(100, 120, Some(1.0)),
+            EncodeAlgorithm::Append
+        );
+        assert_eq!(
+            super::auto_select(120, 100, Some(1.0)),
+            EncodeAlgorithm::Lcs
+        );
+        assert_eq!(
+            super::auto_select(100, 100, Some(0.0)),
+            EncodeAlgorithm::BlockHash
+        );
+
+        let thresholds = SelectionThresholds {
+            max_lcs_cells: 0,
+            ..Default::default()
+        };
+        assert_eq!(
+            auto_select_with(1, 1, None, &thresholds),
+            EncodeAlgorithm::BlockHash
+        );
+    }
+
+    #[test]
+    fn encode_with() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        for algorithm in [
+            EncodeAlgorithm::Lcs,
+            EncodeAlgorithm::BlockHash,
+            EncodeAlgorithm::Append,
+        ] {
+            let patch = Patch::encode_with(&source, &target, algorithm);
+            assert_eq!(patch.apply(&source), Some(target.clone()));
+        }
+        assert_eq!(
+            Patch::encode_with(&source, &target, EncodeAlgorithm::Lcs),
+            Patch::new(&source, &target)
+        );
+
+        let mut appended = source.clone();
+        appended.extend(b"appended");
+        let patch = Patch::encode_with(&source, &appended, EncodeAlgorithm::Append);
+        assert_eq!(patch.apply(&source), Some(appended));
+        assert!(matches!(
+            patch.instructions().last(),
+            Some(DeltaInstruction::Add(add)) if add.len() == 8
+        ));
+    }
+
+    #[test]
+    fn block_hash() {
+        let source: Vec<u8> = (0..=255).cycle().take(4096).collect();
+        let mut target = source.clone();
+        target[1000] ^= 0xff;
+        target.splice(2000..2000, b"inserted".iter().copied());
+        target.drain(3000..3100);
+        let patch = Patch::encode_with(&source, &target, EncodeAlgorithm::BlockHash);
+        assert_eq!(patch.apply(&source), Some(target.clone()));
+        assert!(patch.to_bytes().len() < source.len() + 64);
+
+        for (source, target) in [(&b""[..], &b"short"[..]), (b"short", b""), (b"", b"")] {
+            let patch = Patch::encode_with(source, target, EncodeAlgorithm::BlockHash);
+            assert_eq!(patch.apply(source), Some(target.to_vec()));
+        }
+    }
+
+    #[test]
+    fn source_index() {
+        let source: Vec<u8> = (0..=255).cycle().take(4096).collect();
+        let mut target = source.clone();
+        target.splice(100..100, b"inserted".iter().copied());
+        let index = SourceIndex::new(&source);
+        let bytes = index.to_bytes();
+        let loaded = SourceIndex::try_from_bytes(&bytes).unwrap();
+        assert_eq!(loaded, index);
+        assert_eq!(loaded.source_length(), source.len());
+
+        let patch = Patch::new_with_index(&loaded, &source, &target).unwrap();
+        assert_eq!(
+            patch,
+            Patch::encode_with(&source, &target, EncodeAlgorithm::BlockHash)
+        );
+        assert_eq!(patch.apply(&source), Some(target.clone()));
+        assert_eq!(Patch::new_with_index(&loaded, &source[1..], &target), None);
+
+        let other: Vec<u8> = (0..source.len()).map(|item| (item % 7) as u8).collect();
+        let patch = Patch::new_with_index(&index, &other, &target).unwrap();
+        assert_eq!(patch.apply(&other), Some(target));
+
+        assert_eq!(
+            SourceIndex::try_from_bytes(&bytes[..bytes.len() - 1]),
+            Err(SourceIndexError::Truncated)
+        );
+        assert_eq!(
+            SourceIndex::try_from_bytes(b"XXXX"),
+            Err(SourceIndexError::InvalidMagic)
+        );
+        let mut invalid = SourceIndex::new(b"12345678").to_bytes();
+        let last = invalid.len() - 1;
+        invalid[last] = 1;
+        assert_eq!(
+            SourceIndex::try_from_bytes(&invalid),
+            Err(SourceIndexError::InvalidPosition)
+        );
+    }
+
+    #[test]
+    fn source_index_diff() {
+        let mut state = 1u64;
+        let mut source: Vec<u8> = (0..64 * 1024)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                (state >> 56) as u8
+            })
+            .collect();
+        let previous = SourceIndex::new(&source);
+        source[30_000..30_016].fill(0);
+        let index = SourceIndex::new(&source);
+        let delta = index.diff_from(&previous);
+        assert!(delta.len() * 100 < index.to_bytes().len());
+        assert_eq!(previous.apply_diff(&delta), Ok(index.clone()));
+        assert_eq!(
+            SourceIndex::new(&source[..100]).apply_diff(&delta),
+            Err(SourceIndexError::InvalidDiff)
+        );
+        assert_eq!(
+            previous.apply_diff(b"?"),
+            Err(SourceIndexError::InvalidDiff)
+        );
+    }
+
+    #[test]
+    fn resumable_encoder() {
+        let source: Vec<u8> = (0..=255).cycle().take(8192).collect();
+        let mut target = source.clone();
+        target[1000] ^= 0xff;
+        target.splice(2000..2000, b"inserted".iter().copied());
+        target.drain(5000..5100);
+        target.extend(b"appended");
+        let index = SourceIndex::new(&source);
+        let expected = Patch::new_with_index(&index, &source, &target).unwrap();
+
+        let mut encoder = ResumableEncoder::new(&index, &source, &target).unwrap();
+        let mut saved: Vec<Vec<u8>> = Vec::new();
+        while !encoder.step(512) {
+            let bytes = encoder.checkpoint().to_bytes();
+            let checkpoint = EncoderCheckpoint::try_from_bytes(&bytes).unwrap();
+            assert_eq!(checkpoint, encoder.checkpoint());
+            encoder = ResumableEncoder::resume(&index, &source, &target, checkpoint).unwrap();
+            saved.push(bytes);
+        }
+        assert!(saved.len() > 2);
+        assert_eq!(encoder.finish(), expected);
+
+        let checkpoint = EncoderCheckpoint::try_from_bytes(&saved[1]).unwrap();
+        assert!(checkpoint.target_offset() > 0);
+        let resumed = ResumableEncoder::resume(&index, &source, &target, checkpoint.clone());
+        assert_eq!(resumed.unwrap().finish(), expected);
+        let mut other = target.clone();
+        other[0] ^= 1;
+        assert!(ResumableEncoder::resume(&index, &source, &other, checkpoint.clone()).is_none());
+        assert!(ResumableEncoder::resume(&index, &source, &target[1..], checkpoint).is_none());
+
+        assert_eq!(
+            EncoderCheckpoint::try_from_bytes(&saved[0][..20]),
+            Err(EncoderCheckpointError::Truncated)
+        );
+        assert_eq!(
+            EncoderCheckpoint::try_from_bytes(b"DIDX"),
+            Err(EncoderCheckpointError::InvalidMagic)
+        );
+        let mut invalid = saved[0].clone();
+        invalid.push(b'?');
+        assert_eq!(
+            EncoderCheckpoint::try_from_bytes(&invalid),
+            Err(EncoderCheckpointError::InvalidPatch(
+                InstructionError::InvalidSign
+            ))
+        );
+    }
+
+    #[test]
+    fn new_with_previous() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        for previous in [
+            Patch::new(&source, &target),
+            Patch::new_with_target_copies(&source, &target),
+        ] {
+            let mut edited = target.clone();
+            edited[target.len() / 2] ^= 0xff;
+            edited.splice(100..100, *b"inserted");
+            let mut shrunk = target.clone();
+            shrunk.drain(200..300);
+            for next in [
+                target.clone(),
+                edited,
+                shrunk,
+                target[..50].to_vec(),
+                target[50..].to_vec(),
+                Vec::new(),
+                b"unrelated".to_vec(),
+            ] {
+                let patch = Patch::new_with_previous(&source, &next, &previous);
+                assert_eq!(patch.apply(&source), Some(next));
+            }
+            assert!(
+                Patch::new_with_previous(&source, &target, &previous).byte_length()
+                    <= previous.byte_length() + 2
+            );
+        }
+
+        let unrelated = Patch::new(b"abc", b"abcd");
+        assert_eq!(
+            Patch::new_with_previous(&source, &target, &unrelated).apply(&source),
+            Some(target)
+        );
+    }
+
+    #[test]
+    fn new_with_hints() {
+        let source: Vec<u8> = (0..=255).cycle().take(16 * 4096).collect();
+        let mut target = source.clone();
+        target[4096..4200].fill(7);
+        target[40_000] = 1;
+        let dirty = [40_000..40_001, 4096..8192, 4100..4200];
+        let patch = Patch::new_with_hints(&source, &target, &dirty);
+        assert_eq!(patch.apply(&source), Some(target.clone()));
+        assert_eq!(patch.target_length(), target.len());
+
+        target.truncate(30_000);
+        target.extend(b"grown");
+        let patch = Patch::new_with_hints(&source[..20_000], &target, &[4096..4200, 0..0]);
+        assert_eq!(patch.apply(&source[..20_000]), Some(target.clone()));
+
+        let patch = Patch::new_with_hints(&source, &target[..100], &[50..usize::MAX, 0..0]);
+        assert_eq!(patch.apply(&source), Some(target[..100].to_vec()));
+        assert_eq!(
+            Patch::new_with_hints(&source, &source, &[]).apply_cow(&source),
+            Some(std::borrow::Cow::Borrowed(&source[..]))
+        );
+    }
+
+    #[test]
+    fn encode_reported() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let thresholds = SelectionThresholds::default();
+        let (patch, report) = Patch::encode_reported(&source, &target, None, &thresholds);
+        assert_eq!(patch, Patch::new(&source, &target));
+        assert_eq!(report.algorithm, EncodeAlgorithm::Lcs);
+        assert_eq!(report.patch_length, patch.to_bytes().len());
+        assert_eq!(
+            (report.source_length, report.target_length),
+            (source.len(), target.len())
+        );
+
+        let (_, again) = Patch::encode_reported(&source, &target, None, &thresholds);
+        assert_eq!(again.config_hash(), report.config_hash());
+        let strict = SelectionThresholds {
+            max_lcs_cells: 16,
+            ..thresholds
+        };
+        let (patch, hinted) = Patch::encode_reported(&source, &target, Some(0.5), &strict);
+        assert_eq!(hinted.algorithm, EncodeAlgorithm::BlockHash);
+        assert_eq!(patch.apply(&source), Some(target));
+        assert_ne!(hinted.config_hash(), report.config_hash());
+
+        let json = hinted.to_json();
+        assert!(json.starts_with(&format!(
+            "{{\"version\":\"{}\",\"algorithm\":\"block_hash\",\"max_lcs_cells\":16,",
+            env!("CARGO_PKG_VERSION")
+        )));
+        assert!(json.contains("\"similarity_hint\":0.5,\"block_size\":8,"));
+        assert!(json.ends_with(&format!(
+            "\"config_hash\":\"{:016x}\"}}",
+            hinted.config_hash()
+        )));
+        assert!(report.to_json().contains("\"similarity_hint\":null,"));
+    }
+
+    #[test]
+    fn compression_hint() {
+        assert_eq!(entropy([&b""[..]].into_iter()), (0, 0.0));
+        assert_eq!(entropy([&b"aaaa"[..]].into_iter()), (4, 0.0));
+        assert_eq!(entropy([&b"ab"[..], b"cd"].into_iter()), (4, 2.0));
+
+        let thresholds = SelectionThresholds::default();
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let (_, report) = Patch::encode_reported(&source, &target, None, &thresholds);
+        assert!(report.benefits_from_compression());
+        assert!(report
+            .to_json()
+            .contains("\"benefits_from_compression\":true,"));
+
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let random: Vec<u8> = (0..64 * 1024)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        let (_, report) = Patch::encode_reported(b"", &random, None, &thresholds);
+        assert_eq!(report.added_bytes, random.len());
+        assert!(report.add_entropy > 7.9);
+        assert!(!report.benefits_from_compression());
+        assert!(report
+            .to_json()
+            .contains("\"benefits_from_compression\":false,"));
+
+        let (_, report) = Patch::encode_reported(b"", &[0u8; 4096], None, &thresholds);
+        assert!(report.benefits_from_compression());
+    }
+
+    #[test]
+    fn find_candidates() {
+        let source: Vec<u8> = (0..200u32)
+            .flat_map(|block| [block as u8; BLOCK_SIZE])
+            .collect();
+        let block = |value: u8| [value; BLOCK_SIZE];
+        let positions: Vec<usize> = (0..200).map(|block| block * BLOCK_SIZE).collect();
+        let index = SourceIndex {
+            source_length: source.len(),
+            blocks: [10, 100]
+                .into_iter()
+                .map(|value| (fnv1a(&block(value)), positions.clone()))
+                .collect(),
+        };
+        assert_eq!(index.find(&source, &block(10), 0), Some(10 * BLOCK_SIZE));
+        assert_eq!(index.find(&source, &block(100), 0), None);
+        assert_eq!(
+            index.find(&source, &block(100), 90 * BLOCK_SIZE),
+            Some(100 * BLOCK_SIZE)
+        );
+
+        let source = vec![0u8; 1 << 20];
+        let target: Vec<u8> = (0..1 << 20).map(|index| (index % 7 == 0) as u8).collect();
+        let patch = Patch::new(&source, &target);
+        assert_eq!(patch.apply(&source), Some(target));
+    }
+}
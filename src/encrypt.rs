@@ -0,0 +1,174 @@
+use crate::{
+    instructions::{
+        delta_instruction::DeltaInstruction, InstructionError, InstructionInfo, Result,
+    },
+    patch::Patch,
+};
+
+/// Marks [`EncryptedPatch::to_bytes`], so ciphertext is never mistaken for a plain patch.
+pub const ENCRYPTED_HEADER_SIGN: u8 = b'A';
+
+/// Stream cipher for the add content of a patch, seeked to a byte position before each add.
+pub trait AddCipher {
+    fn apply_keystream(&mut self, position: u64, content: &mut [u8]);
+}
+
+#[cfg(feature = "chacha20")]
+impl AddCipher for chacha20::ChaCha20 {
+    fn apply_keystream(&mut self, position: u64, content: &mut [u8]) {
+        use chacha20::cipher::{StreamCipher, StreamCipherSeek};
+        self.seek(position);
+        StreamCipher::apply_keystream(self, content);
+    }
+}
+
+/// A patch whose add content is encrypted while removes, copy deltas and instruction lengths
+/// stay in the clear for size accounting.
+///
+/// Copy deltas are the difference between source and target bytes, so anyone holding the
+/// source still learns bytes modified in place; only inserted bytes are confidential. The
+/// keystream must never be reused: use a fresh key or nonce for every patch. There is no
+/// MAC, so tampering goes unnoticed until the target is checked, e.g. against a signed
+/// digest.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct EncryptedPatch {
+    patch: Patch,
+}
+
+fn apply_add_keystream<C: AddCipher>(patch: &mut Patch, cipher: &mut C) {
+    let mut position = 0u64;
+    for instruction in patch.instructions_mut() {
+        if let DeltaInstruction::Add(instruction) = instruction {
+            let content = instruction.content_mut();
+            cipher.apply_keystream(position, content);
+            position += content.len() as u64;
+        }
+    }
+}
+
+impl EncryptedPatch {
+    pub fn decrypt<C: AddCipher>(&self, cipher: &mut C) -> Patch {
+        let mut patch = self.patch.clone();
+        apply_add_keystream(&mut patch, cipher);
+        patch
+    }
+
+    pub fn instructions(&self) -> &[DeltaInstruction] {
+        self.patch.instructions()
+    }
+
+    pub fn source_length(&self) -> usize {
+        self.patch.source_length()
+    }
+
+    pub fn target_length(&self) -> usize {
+        self.patch.target_length()
+    }
+
+    pub fn added_length(&self) -> usize {
+        self.instructions()
+            .iter()
+            .filter(|instruction| matches!(instruction, DeltaInstruction::Add(_)))
+            .map(|instruction| instruction.len() as usize)
+            .sum()
+    }
+
+    /// The patch bytes behind an [`ENCRYPTED_HEADER_SIGN`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![ENCRYPTED_HEADER_SIGN];
+        bytes.extend(self.patch.to_bytes());
+        bytes
+    }
+
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self> {
+        match bytes.first() {
+            Some(&ENCRYPTED_HEADER_SIGN) => (),
+            Some(_) => return Err(InstructionError::InvalidSign),
+            None => return Err(InstructionError::MissignSign),
+        }
+        Ok(Self {
+            patch: Patch::try_from_bytes(&bytes[1..])?,
+        })
+    }
+}
+
+impl Patch {
+    pub fn encrypt_adds<C: AddCipher>(&self, cipher: &mut C) -> EncryptedPatch {
+        let mut patch = self.clone();
+        apply_add_keystream(&mut patch, cipher);
+        EncryptedPatch { patch }
+    }
+}
+
+#[cfg(test)]
+mod encrypt_tests {
+    use std::fs;
+
+    use super::*;
+
+    struct XorCipher(u8);
+
+    impl AddCipher for XorCipher {
+        fn apply_keystream(&mut self, position: u64, content: &mut [u8]) {
+            for (index, byte) in content.iter_mut().enumerate() {
+                *byte ^= self.0.wrapping_add((position as usize + index) as u8);
+            }
+        }
+    }
+
+    #[test]
+    fn encrypt_adds() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+        let encrypted = patch.encrypt_adds(&mut XorCipher(0x5A));
+
+        assert_ne!(encrypted.instructions(), patch.instructions());
+        assert_eq!(encrypted.target_length(), patch.target_length());
+        assert_eq!(encrypted.source_length(), patch.source_length());
+        assert_eq!(encrypted.to_bytes().len(), patch.to_bytes().len() + 1);
+        assert_eq!(
+            Patch::try_from_bytes(&encrypted.to_bytes()),
+            Err(InstructionError::InvalidSign)
+        );
+        assert_eq!(
+            EncryptedPatch::try_from_bytes(&patch.to_bytes()),
+            Err(InstructionError::InvalidSign)
+        );
+        assert_eq!(
+            EncryptedPatch::try_from_bytes(b""),
+            Err(InstructionError::MissignSign)
+        );
+        for (encrypted, plain) in encrypted.instructions().iter().zip(patch.instructions()) {
+            assert_eq!(encrypted.len(), plain.len());
+            if !matches!(plain, DeltaInstruction::Add(_)) {
+                assert_eq!(encrypted, plain);
+            }
+        }
+
+        let encrypted = EncryptedPatch::try_from_bytes(&encrypted.to_bytes()).unwrap();
+        assert_eq!(encrypted.decrypt(&mut XorCipher(0x5A)), patch);
+        assert_ne!(encrypted.decrypt(&mut XorCipher(0x00)), patch);
+    }
+
+    #[test]
+    fn added_length() {
+        let patch = Patch::new(b"Hello world", b"Hello brave new world");
+        let encrypted = patch.encrypt_adds(&mut XorCipher(1));
+        assert_eq!(encrypted.added_length(), 10);
+    }
+
+    #[cfg(feature = "chacha20")]
+    #[test]
+    fn encrypt_adds_chacha20() {
+        use chacha20::{cipher::KeyIvInit, ChaCha20};
+
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+        let cipher = || ChaCha20::new(&[7u8; 32].into(), &[3u8; 12].into());
+        let encrypted = patch.encrypt_adds(&mut cipher());
+        assert_ne!(encrypted.instructions(), patch.instructions());
+        assert_eq!(encrypted.decrypt(&mut cipher()), patch);
+    }
+}
@@ -0,0 +1,100 @@
+use crate::patch::Patch;
+
+const BLOCK_SIZE: usize = 256;
+const CONFIDENCE_Z: f64 = 1.96;
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct SizeEstimate {
+    pub bytes: usize,
+    pub lower: usize,
+    pub upper: usize,
+    pub sampled_blocks: usize,
+    pub total_blocks: usize,
+}
+
+fn block(bytes: &[u8], index: usize, count: usize) -> &[u8] {
+    &bytes[index * bytes.len() / count..(index + 1) * bytes.len() / count]
+}
+
+pub fn estimate_patch_size(source: &[u8], target: &[u8], sample_rate: f64) -> SizeEstimate {
+    let total_blocks = source.len().max(target.len()).div_ceil(BLOCK_SIZE);
+    if total_blocks == 0 {
+        return SizeEstimate::default();
+    }
+    let stride = match sample_rate {
+        rate if rate >= 1.0 => 1,
+        rate if rate > 0.0 => ((1.0 / rate).round() as usize).clamp(1, total_blocks),
+        _ => total_blocks,
+    };
+    let sizes: Vec<f64> = (0..total_blocks)
+        .step_by(stride)
+        .map(|index| {
+            Patch::new(
+                block(source, index, total_blocks),
+                block(target, index, total_blocks),
+            )
+            .to_bytes()
+            .len() as f64
+        })
+        .collect();
+
+    let sampled_blocks = sizes.len();
+    let mean = sizes.iter().sum::<f64>() / sampled_blocks as f64;
+    let variance = match sampled_blocks {
+        1 => mean * mean,
+        _ => {
+            sizes.iter().map(|size| (size - mean).powi(2)).sum::<f64>()
+                / (sampled_blocks - 1) as f64
+        }
+    };
+    let sampled_fraction = sampled_blocks as f64 / total_blocks as f64;
+    let margin = CONFIDENCE_Z
+        * total_blocks as f64
+        * (variance * (1.0 - sampled_fraction) / sampled_blocks as f64).sqrt();
+    let estimate = mean * total_blocks as f64;
+    SizeEstimate {
+        bytes: estimate.round() as usize,
+        lower: (estimate - margin).max(0.0).floor() as usize,
+        upper: (estimate + margin).ceil() as usize,
+        sampled_blocks,
+        total_blocks,
+    }
+}
+
+#[cfg(test)]
+mod estimate_tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn estimate_patch_size() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let full = super::estimate_patch_size(&source, &target, 1.0);
+        assert_eq!(full.sampled_blocks, full.total_blocks);
+        assert_eq!(full.lower, full.bytes);
+        assert_eq!(full.upper, full.bytes);
+
+        let sampled = super::estimate_patch_size(&source, &target, 0.25);
+        assert_eq!(sampled.total_blocks, full.total_blocks);
+        assert!(sampled.sampled_blocks < full.sampled_blocks);
+        assert!(sampled.lower <= sampled.bytes && sampled.bytes <= sampled.upper);
+        assert!(sampled.lower <= full.bytes && full.bytes <= sampled.upper);
+
+        let actual = Patch::new(&source, &target).to_bytes().len();
+        assert!(full.bytes.abs_diff(actual) < actual / 10);
+    }
+
+    #[test]
+    fn estimate_patch_size_edge_cases() {
+        assert_eq!(
+            super::estimate_patch_size(b"", b"", 0.5),
+            SizeEstimate::default()
+        );
+        let estimate = super::estimate_patch_size(b"", &[1; 1000], 0.0);
+        assert_eq!(estimate.sampled_blocks, 1);
+        assert_eq!(estimate.total_blocks, 4);
+        assert!(estimate.lower <= estimate.bytes && estimate.bytes <= estimate.upper);
+    }
+}
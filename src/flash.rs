@@ -0,0 +1,479 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    error::Error,
+    ops::Range,
+};
+
+use crate::{
+    instructions::{delta_instruction::DeltaInstruction, InstructionInfo},
+    patch::{Patch, PatchBuilder},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockWrite {
+    pub block: usize,
+    pub offset: usize,
+    pub length: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlashPlan {
+    pub block_size: usize,
+    pub writes: Vec<BlockWrite>,
+    pub stale_source_blocks: Vec<usize>,
+}
+
+/// Source blocks a copy of `source` to `target_offset` reads after the same block of the
+/// target was written. The shift is the same for the whole run, so a block is stale when the
+/// run reaches into its last `shift` bytes.
+fn stale_blocks(
+    source: Range<usize>,
+    target_offset: usize,
+    block_size: usize,
+) -> impl Iterator<Item = usize> {
+    let shift = target_offset.saturating_sub(source.start);
+    let blocks = match shift {
+        0 => 0..0,
+        _ => source.start / block_size..source.end.div_ceil(block_size),
+    };
+    blocks.filter(move |block| {
+        let end = (block + 1) * block_size;
+        let start = end.saturating_sub(shift).max(block * block_size);
+        source.start.max(start) < source.end.min(end)
+    })
+}
+
+impl FlashPlan {
+    pub fn new(patch: &Patch, block_size: usize) -> Self {
+        assert!(block_size > 0, "Block size must be greater than zero");
+        let mut source_offset = 0usize;
+        let mut target_offset = 0usize;
+        let mut stale_source_blocks: BTreeSet<usize> = BTreeSet::new();
+        for instruction in patch.instructions() {
+            let length = instruction.len() as usize;
+            match instruction {
+                DeltaInstruction::Remove(_) => source_offset += length,
+                DeltaInstruction::Add(_) | DeltaInstruction::TargetCopy(_) => {
+                    target_offset += length
+                }
+                DeltaInstruction::Copy(_) => {
+                    stale_source_blocks.extend(stale_blocks(
+                        source_offset..source_offset + length,
+                        target_offset,
+                        block_size,
+                    ));
+                    source_offset += length;
+                    target_offset += length;
+                }
+            }
+        }
+
+        let writes = (0..target_offset.div_ceil(block_size))
+            .map(|block| BlockWrite {
+                block,
+                offset: block * block_size,
+                length: block_size.min(target_offset - block * block_size),
+            })
+            .collect();
+
+        Self {
+            block_size,
+            writes,
+            stale_source_blocks: stale_source_blocks.into_iter().collect(),
+        }
+    }
+
+    pub fn is_in_place_safe(&self) -> bool {
+        self.stale_source_blocks.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlashLayout {
+    /// End offset of every sector.
+    ends: Vec<usize>,
+}
+
+impl FlashLayout {
+    pub fn new(sector_sizes: Vec<usize>) -> Self {
+        assert!(
+            sector_sizes.iter().all(|size| *size > 0),
+            "Sector sizes must be greater than zero"
+        );
+        Self {
+            ends: sector_sizes
+                .iter()
+                .scan(0, |end, size| {
+                    *end += size;
+                    Some(*end)
+                })
+                .collect(),
+        }
+    }
+
+    pub fn uniform(sector_size: usize, sector_count: usize) -> Self {
+        Self::new(vec![sector_size; sector_count])
+    }
+
+    pub fn len(&self) -> usize {
+        self.ends.last().copied().unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    pub fn sector_range(&self, sector: usize) -> Option<Range<usize>> {
+        let end = *self.ends.get(sector)?;
+        let start = sector
+            .checked_sub(1)
+            .map_or(0, |previous| self.ends[previous]);
+        Some(start..end)
+    }
+
+    pub fn sector_of(&self, offset: usize) -> Option<usize> {
+        let sector = self.ends.partition_point(|end| *end <= offset);
+        (sector < self.ends.len()).then_some(sector)
+    }
+
+    /// The sectors holding any byte of `range`.
+    fn sectors_of(&self, range: Range<usize>) -> Range<usize> {
+        if range.is_empty() {
+            return 0..0;
+        }
+        let first = self.ends.partition_point(|end| *end <= range.start);
+        let last = self.ends.partition_point(|end| *end < range.end);
+        first..(last + 1).min(self.ends.len())
+    }
+}
+
+/// A patch producing one target sector from `source[source_range]`, so a bootloader can
+/// apply it into a single sector sized buffer before erasing and programming the sector.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectorPatch {
+    pub sector: usize,
+    pub source_range: Range<usize>,
+    pub patch: Patch,
+    /// Sectors whose sub-patches read this sector's source bytes and so must run first.
+    pub depends_on: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SectorPlanError {
+    SourceMismatch,
+    TargetTooLarge {
+        target_length: usize,
+        flash_length: usize,
+    },
+}
+
+impl std::fmt::Display for SectorPlanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SectorPlanError::SourceMismatch => {
+                write!(f, "Source length doesn't match the patch source length")
+            }
+            SectorPlanError::TargetTooLarge {
+                target_length,
+                flash_length,
+            } => write!(
+                f,
+                "Target of {} bytes doesn't fit in {} bytes of flash",
+                target_length, flash_length
+            ),
+        }
+    }
+}
+
+impl Error for SectorPlanError {}
+
+struct SectorSplitter<'a> {
+    layout: &'a FlashLayout,
+    source: &'a [u8],
+    target: &'a [u8],
+    source_offset: usize,
+    target_offset: usize,
+    sector: Range<usize>,
+    source_start: usize,
+    builder: PatchBuilder,
+    reads: HashSet<usize>,
+    planned: Vec<(SectorPatch, HashSet<usize>)>,
+}
+
+impl SectorSplitter<'_> {
+    fn flush(&mut self) {
+        let Some(sector) = self.layout.sector_of(self.sector.start) else {
+            return;
+        };
+        self.planned.push((
+            SectorPatch {
+                sector,
+                source_range: self.source_start..self.source_offset,
+                patch: std::mem::take(&mut self.builder).build(),
+                depends_on: Vec::new(),
+            },
+            std::mem::take(&mut self.reads),
+        ));
+        self.source_start = self.source_offset;
+    }
+
+    fn next_piece(&mut self, remaining: usize) -> usize {
+        if self.target_offset >= self.sector.end {
+            self.flush();
+            let sector = self.layout.sector_of(self.target_offset).unwrap();
+            self.sector = self.layout.sector_range(sector).unwrap();
+        }
+        remaining.min(self.sector.end - self.target_offset)
+    }
+
+    fn instruction(&mut self, instruction: &DeltaInstruction) {
+        let mut remaining = instruction.len() as usize;
+        if let DeltaInstruction::Remove(_) = instruction {
+            self.builder.remove(remaining);
+            self.source_offset += remaining;
+            return;
+        }
+        while remaining > 0 {
+            let length = self.next_piece(remaining);
+            let target = &self.target[self.target_offset..self.target_offset + length];
+            match instruction {
+                DeltaInstruction::Copy(_) => {
+                    let source = &self.source[self.source_offset..self.source_offset + length];
+                    self.builder.copy(source, target);
+                    self.reads.extend(
+                        self.layout
+                            .sectors_of(self.source_offset..self.source_offset + length),
+                    );
+                    self.source_offset += length;
+                }
+                DeltaInstruction::TargetCopy(target_copy)
+                    if self.target_offset - target_copy.distance() as usize
+                        >= self.sector.start =>
+                {
+                    self.builder
+                        .target_copy(target_copy.distance() as usize, length)
+                }
+                _ => self.builder.add(target),
+            }
+            self.target_offset += length;
+            remaining -= length;
+        }
+    }
+}
+
+impl Patch {
+    /// Splits the patch into per-sector sub-patches for updating `source` in place, ordered so
+    /// that no sector is erased while a later sub-patch still reads its source bytes.
+    pub fn plan_sectors(
+        &self,
+        source: &[u8],
+        layout: &FlashLayout,
+    ) -> Result<Vec<SectorPatch>, SectorPlanError> {
+        let target = self.apply(source).ok_or(SectorPlanError::SourceMismatch)?;
+        if target.len() > layout.len() {
+            return Err(SectorPlanError::TargetTooLarge {
+                target_length: target.len(),
+                flash_length: layout.len(),
+            });
+        }
+        let mut splitter = SectorSplitter {
+            layout,
+            source,
+            target: &target,
+            source_offset: 0,
+            target_offset: 0,
+            sector: layout.sector_range(0).unwrap_or(0..0),
+            source_start: 0,
+            builder: PatchBuilder::default(),
+            reads: HashSet::new(),
+            planned: Vec::new(),
+        };
+        for instruction in self.instructions() {
+            splitter.instruction(instruction);
+        }
+        if !target.is_empty() {
+            splitter.flush();
+        }
+        let mut planned = splitter.planned;
+
+        for index in 0..planned.len() {
+            let sector = planned[index].0.sector;
+            planned[index].0.depends_on = planned
+                .iter()
+                .filter(|(other, reads)| other.sector != sector && reads.contains(&sector))
+                .map(|(other, _)| other.sector)
+                .collect();
+        }
+        let mut pending: BTreeSet<usize> = (0..planned.len()).collect();
+        let mut done: HashSet<usize> = HashSet::new();
+        let mut order: Vec<SectorPatch> = Vec::with_capacity(planned.len());
+        while let Some(index) = pending.iter().copied().find(|index| {
+            planned[*index]
+                .0
+                .depends_on
+                .iter()
+                .all(|sector| done.contains(sector))
+        }) {
+            pending.remove(&index);
+            done.insert(planned[index].0.sector);
+            order.push(planned[index].0.clone());
+        }
+        assert!(
+            pending.is_empty(),
+            "Sector dependencies are acyclic because the source is read in order"
+        );
+        Ok(order)
+    }
+}
+
+#[cfg(test)]
+mod flash_tests {
+    use super::*;
+    use crate::encode::EncodeAlgorithm;
+
+    #[test]
+    fn new() {
+        let patch = Patch::try_from_bytes(&[b'-', 4, b'|', 4, 0, 0, 0, 0, b'+', 2, 1, 2]).unwrap();
+        let plan = FlashPlan::new(&patch, 4);
+        assert_eq!(
+            plan.writes,
+            vec![
+                BlockWrite {
+                    block: 0,
+                    offset: 0,
+                    length: 4
+                },
+                BlockWrite {
+                    block: 1,
+                    offset: 4,
+                    length: 2
+                },
+            ]
+        );
+        assert!(plan.is_in_place_safe());
+
+        let patch = Patch::try_from_bytes(&[b'+', 4, 1, 2, 3, 4, b'|', 4, 0, 0, 0, 0]).unwrap();
+        let plan = FlashPlan::new(&patch, 4);
+        assert_eq!(plan.stale_source_blocks, vec![0]);
+        assert!(!plan.is_in_place_safe());
+
+        assert!(FlashPlan::new(&Patch::default(), 4).writes.is_empty());
+    }
+
+    fn apply_in_place(flash: &mut [u8], plan: &[SectorPatch], layout: &FlashLayout) {
+        let mut buffer: Vec<u8> = Vec::new();
+        for sector_patch in plan {
+            buffer.clear();
+            let source = &flash[sector_patch.source_range.clone()];
+            buffer.extend(sector_patch.patch.apply(source).unwrap());
+            let range = layout.sector_range(sector_patch.sector).unwrap();
+            assert!(buffer.len() <= range.len());
+            flash[range.start..range.start + buffer.len()].copy_from_slice(&buffer);
+        }
+    }
+
+    #[test]
+    fn plan_sectors() {
+        let layout = FlashLayout::new(vec![64, 64, 128, 256]);
+        let source: Vec<u8> = (0..400u32).map(|item| (item * 7 % 251) as u8).collect();
+        let mut target = source.clone();
+        target.splice(10..10, b"inserted near the start".iter().copied());
+        target.drain(300..340);
+        target[200] ^= 0xff;
+        target.extend([0x55; 90]);
+        let patch = Patch::encode_with(&source, &target, EncodeAlgorithm::BlockHash);
+
+        let plan = patch.plan_sectors(&source, &layout).unwrap();
+        let mut sectors: Vec<usize> = plan.iter().map(|sector| sector.sector).collect();
+        sectors.sort_unstable();
+        assert_eq!(sectors, vec![0, 1, 2, 3]);
+        for (index, sector_patch) in plan.iter().enumerate() {
+            assert!(sector_patch
+                .depends_on
+                .iter()
+                .all(|sector| plan[..index].iter().any(|done| done.sector == *sector)));
+        }
+        assert!(plan.iter().any(|sector| !sector.depends_on.is_empty()));
+
+        let mut flash = source.clone();
+        flash.resize(layout.len(), 0xff);
+        apply_in_place(&mut flash, &plan, &layout);
+        assert_eq!(flash[..target.len()], target[..]);
+
+        let patch = Patch::new_with_target_copies(&source, &target);
+        assert!(patch.has_target_copies());
+        let plan = patch.plan_sectors(&source, &layout).unwrap();
+        let mut flash = source.clone();
+        flash.resize(layout.len(), 0xff);
+        apply_in_place(&mut flash, &plan, &layout);
+        assert_eq!(flash[..target.len()], target[..]);
+
+        assert_eq!(
+            patch.plan_sectors(&source[1..], &layout),
+            Err(SectorPlanError::SourceMismatch)
+        );
+        assert_eq!(
+            patch.plan_sectors(&source, &FlashLayout::uniform(64, 2)),
+            Err(SectorPlanError::TargetTooLarge {
+                target_length: target.len(),
+                flash_length: 128
+            })
+        );
+        assert_eq!(
+            Patch::new(&source, b"").plan_sectors(&source, &layout),
+            Ok(Vec::new())
+        );
+    }
+
+    #[test]
+    fn flash_layout() {
+        let layout = FlashLayout::new(vec![16, 16, 32]);
+        assert_eq!(layout.len(), 64);
+        assert_eq!(layout.sector_range(2), Some(32..64));
+        assert_eq!(layout.sector_range(3), None);
+        assert_eq!(layout.sector_of(31), Some(1));
+        assert_eq!(layout.sector_of(64), None);
+        assert_eq!(FlashLayout::uniform(8, 3), FlashLayout::new(vec![8, 8, 8]));
+        assert_eq!(layout.sector_range(0), Some(0..16));
+        assert_eq!(layout.sector_of(0), Some(0));
+        assert_eq!(layout.sector_of(16), Some(1));
+        assert_eq!(layout.sectors_of(15..17), 0..2);
+        assert_eq!(layout.sectors_of(20..64), 1..3);
+        assert_eq!(layout.sectors_of(40..100), 2..3);
+        assert_eq!(layout.sectors_of(64..100), 3..3);
+        assert_eq!(layout.sectors_of(5..5), 0..0);
+        assert!(FlashLayout::new(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn stale_blocks() {
+        for (source, target_offset) in [
+            (0..20, 0),
+            (0..20, 3),
+            (5..6, 7),
+            (5..6, 8),
+            (2..30, 9),
+            (0..30, 100),
+            (10..30, 2),
+        ] {
+            let expected: BTreeSet<usize> = source
+                .clone()
+                .filter(|offset| offset / 4 < (target_offset + offset - source.start) / 4)
+                .map(|offset| offset / 4)
+                .collect();
+            assert_eq!(
+                super::stale_blocks(source.clone(), target_offset, 4).collect::<BTreeSet<_>>(),
+                expected,
+                "{:?} to {}",
+                source,
+                target_offset
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn new_zero_block_size() {
+        FlashPlan::new(&Patch::default(), 0);
+    }
+}
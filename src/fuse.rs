@@ -0,0 +1,159 @@
+use std::{
+    ffi::{OsStr, OsString},
+    io,
+    path::Path,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use fuser::{
+    FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry,
+    Request,
+};
+
+use crate::{patch::Patch, view::PatchedView};
+
+const TTL: Duration = Duration::from_secs(1);
+const BLOCK_SIZE: u32 = 512;
+const ROOT_INODE: u64 = 1;
+const FILE_INODE: u64 = 2;
+
+pub struct PatchedFilesystem<'a> {
+    view: PatchedView<'a>,
+    name: OsString,
+}
+
+impl<'a> PatchedFilesystem<'a> {
+    pub fn new(view: PatchedView<'a>, name: impl Into<OsString>) -> Self {
+        Self {
+            view,
+            name: name.into(),
+        }
+    }
+
+    fn attr(&self, ino: u64) -> Option<FileAttr> {
+        let (kind, perm, nlink, size) = match ino {
+            ROOT_INODE => (FileType::Directory, 0o555, 2, 0),
+            FILE_INODE => (FileType::RegularFile, 0o444, 1, self.view.len() as u64),
+            _ => return None,
+        };
+        Some(FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(BLOCK_SIZE as u64),
+            atime: UNIX_EPOCH,
+            mtime: UNIX_EPOCH,
+            ctime: UNIX_EPOCH,
+            crtime: UNIX_EPOCH,
+            kind,
+            perm,
+            nlink,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+            blksize: BLOCK_SIZE,
+            flags: 0,
+        })
+    }
+}
+
+impl Filesystem for PatchedFilesystem<'_> {
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        match self.attr(FILE_INODE) {
+            Some(attr) if parent == ROOT_INODE && name == self.name => reply.entry(&TTL, &attr, 0),
+            _ => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        match self.attr(ino) {
+            Some(attr) => reply.attr(&TTL, &attr),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        if ino != FILE_INODE {
+            return reply.error(libc::ENOENT);
+        }
+        if offset < 0 {
+            return reply.error(libc::EINVAL);
+        }
+        let mut buffer = vec![0; size as usize];
+        let read = self.view.read_at(offset as usize, &mut buffer);
+        reply.data(&buffer[..read]);
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        if ino != ROOT_INODE {
+            return reply.error(libc::ENOENT);
+        }
+        let entries = [
+            (ROOT_INODE, FileType::Directory, OsStr::new(".")),
+            (ROOT_INODE, FileType::Directory, OsStr::new("..")),
+            (FILE_INODE, FileType::RegularFile, self.name.as_os_str()),
+        ];
+        for (index, (ino, kind, name)) in entries.iter().enumerate().skip(offset as usize) {
+            if reply.add(*ino, (index + 1) as i64, *kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+}
+
+pub fn mount<P: AsRef<Path>>(
+    source: &[u8],
+    patch: &Patch,
+    name: impl Into<OsString>,
+    mountpoint: P,
+) -> io::Result<()> {
+    let view = PatchedView::new(source, patch).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Source length doesn't match the patch source length",
+        )
+    })?;
+    fuser::mount2(
+        PatchedFilesystem::new(view, name),
+        mountpoint,
+        &[MountOption::RO, MountOption::FSName(String::from("deltas"))],
+    )
+}
+
+#[cfg(test)]
+mod fuse_tests {
+    use super::*;
+
+    #[test]
+    fn attr() {
+        let patch = Patch::new(b"AAA", b"ABAB");
+        let filesystem =
+            PatchedFilesystem::new(PatchedView::new(b"AAA", &patch).unwrap(), "target");
+        assert_eq!(
+            filesystem.attr(ROOT_INODE).unwrap().kind,
+            FileType::Directory
+        );
+        let file_attr = filesystem.attr(FILE_INODE).unwrap();
+        assert_eq!(file_attr.kind, FileType::RegularFile);
+        assert_eq!(file_attr.size, 4);
+        assert_eq!(file_attr.perm, 0o444);
+        assert!(filesystem.attr(FILE_INODE + 1).is_none());
+    }
+}
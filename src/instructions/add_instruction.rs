@@ -1,11 +1,13 @@
 use std::{iter::Peekable, slice::Iter};
 
+use crate::cbor::{self, MAJOR_BYTES};
+
 use super::{
-    InstructionBytes, InstructionContent, InstructionError, InstructionInfo, Result,
-    ADD_INSTRUCTION_SIGN,
+    InstructionBytes, InstructionCbor, InstructionContent, InstructionError, InstructionInfo,
+    Result, ADD_INSTRUCTION_SIGN,
 };
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Default, PartialEq, Clone)]
 pub struct AddInstruction {
     content: Vec<u8>,
 }
@@ -18,6 +20,23 @@ impl AddInstruction {
         );
         Self { content }
     }
+
+    pub fn content(&self) -> &[u8] {
+        &self.content
+    }
+
+    pub fn into_content(self) -> Vec<u8> {
+        self.content
+    }
+
+    /// Bytes allocated for the content.
+    pub fn heap_size(&self) -> usize {
+        self.content.capacity()
+    }
+
+    pub(crate) fn content_mut(&mut self) -> &mut [u8] {
+        &mut self.content
+    }
 }
 
 impl InstructionInfo for AddInstruction {
@@ -36,6 +55,10 @@ impl InstructionInfo for AddInstruction {
     fn non_default_item_count(&self) -> Option<u8> {
         Some(self.content.iter().filter(|item| **item != 0).count() as u8)
     }
+
+    fn encoded_len(&self) -> usize {
+        self.len() as usize + 2
+    }
 }
 
 impl InstructionContent for AddInstruction {
@@ -69,7 +92,7 @@ impl InstructionBytes for AddInstruction {
     }
 
     fn byte_length(&self) -> usize {
-        self.len() as usize + 2
+        self.encoded_len()
     }
 
     fn to_bytes(&self) -> Vec<u8> {
@@ -98,11 +121,21 @@ impl InstructionBytes for AddInstruction {
     }
 }
 
-impl Default for AddInstruction {
-    fn default() -> Self {
-        Self {
-            content: Vec::new(),
+impl InstructionCbor for AddInstruction {
+    fn to_cbor(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(self.byte_length() + 3);
+        cbor::write_instruction_head(&mut bytes, ADD_INSTRUCTION_SIGN);
+        cbor::write_head(&mut bytes, MAJOR_BYTES, self.len() as u64);
+        bytes.extend(self.content.iter());
+        bytes
+    }
+
+    fn try_from_cbor(bytes: &mut Peekable<Iter<'_, u8>>) -> Result<Self> {
+        if cbor::read_instruction_head(bytes)? != ADD_INSTRUCTION_SIGN {
+            return Err(InstructionError::InvalidSign);
         }
+        let content = cbor::read_content(bytes)?;
+        Ok(Self { content })
     }
 }
 
@@ -158,9 +191,11 @@ mod add_instruction_tests {
 
     #[test]
     fn instruction_info() {
-        let mut instruction = AddInstruction::new(vec![0; u8::MAX.try_into().unwrap()]);
+        let mut instruction = AddInstruction::new(vec![0; u8::MAX.into()]);
         assert_eq!(instruction.len(), u8::MAX);
         assert!(instruction.is_full());
+        assert_eq!(instruction.encoded_len(), instruction.to_bytes().len());
+        assert_eq!(instruction.remaining_capacity(), 0);
 
         instruction = AddInstruction::new(Vec::new());
         assert_eq!(instruction.len(), u8::MIN);
@@ -179,14 +214,14 @@ mod add_instruction_tests {
             assert_eq!(instruction.non_default_item_count().unwrap(), 0);
         }
         for i in 0..(u8::MAX / 2) {
-            instruction.push(0 + 1).unwrap();
+            instruction.push(1).unwrap();
             assert_eq!(instruction.non_default_item_count().unwrap(), i + 1);
         }
     }
 
     #[test]
     fn instruction_content_push() {
-        let mut instruction = AddInstruction::new(vec![0; (u8::MAX - 1).try_into().unwrap()]);
+        let mut instruction = AddInstruction::new(vec![0; (u8::MAX - 1).into()]);
         assert!(instruction.push(0).is_ok());
         assert!(instruction
             .push(0)
@@ -215,7 +250,7 @@ mod add_instruction_tests {
 
     #[test]
     fn instruction_bytes_to_bytes() {
-        let mut instruction = AddInstruction::new(vec![0; u8::MAX.try_into().unwrap()]);
+        let mut instruction = AddInstruction::new(vec![0; u8::MAX.into()]);
         let mut bytes = vec![ADD_INSTRUCTION_SIGN];
         bytes.extend(instruction.len().to_be_bytes());
         bytes.extend(instruction.content.iter());
@@ -229,7 +264,7 @@ mod add_instruction_tests {
 
     #[test]
     fn instruction_bytes_try_from_bytes_ok() {
-        let mut instruction = AddInstruction::new(vec![0; u8::MAX.try_into().unwrap()]);
+        let mut instruction = AddInstruction::new(vec![0; u8::MAX.into()]);
         assert_eq!(
             AddInstruction::try_from_bytes(&mut instruction.to_bytes().iter().peekable()).unwrap(),
             instruction
@@ -265,4 +300,23 @@ mod add_instruction_tests {
             InstructionError::MissingContent
         );
     }
+
+    #[test]
+    fn instruction_cbor() {
+        let instruction = AddInstruction::new(vec![1, 2, 3]);
+        assert_eq!(
+            AddInstruction::try_from_cbor(&mut instruction.to_cbor().iter().peekable()),
+            Ok(instruction.clone())
+        );
+
+        let bytes = instruction.to_cbor();
+        assert_eq!(
+            AddInstruction::try_from_cbor(&mut bytes[..bytes.len() - 1].iter().peekable()),
+            Err(InstructionError::MissingContent)
+        );
+        assert_eq!(
+            AddInstruction::try_from_cbor(&mut instruction.to_bytes().iter().peekable()),
+            Err(InstructionError::InvalidContent)
+        );
+    }
 }
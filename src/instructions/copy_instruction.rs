@@ -1,11 +1,13 @@
 use std::{iter::Peekable, slice::Iter};
 
+use crate::cbor::{self, MAJOR_BYTES};
+
 use super::{
-    InstructionBytes, InstructionContent, InstructionError, InstructionInfo, Result,
-    COPY_INSTRUCTION_SIGN,
+    InstructionBytes, InstructionCbor, InstructionContent, InstructionError, InstructionInfo,
+    Result, COPY_INSTRUCTION_SIGN,
 };
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Default, PartialEq, Clone)]
 pub struct CopyInstruction {
     content: Vec<u8>,
 }
@@ -18,6 +20,19 @@ impl CopyInstruction {
         );
         Self { content }
     }
+
+    pub fn content(&self) -> &[u8] {
+        &self.content
+    }
+
+    pub fn into_content(self) -> Vec<u8> {
+        self.content
+    }
+
+    /// Bytes allocated for the content.
+    pub fn heap_size(&self) -> usize {
+        self.content.capacity()
+    }
 }
 
 impl InstructionInfo for CopyInstruction {
@@ -36,6 +51,10 @@ impl InstructionInfo for CopyInstruction {
     fn non_default_item_count(&self) -> Option<u8> {
         Some(self.content.iter().filter(|item| **item != 0).count() as u8)
     }
+
+    fn encoded_len(&self) -> usize {
+        self.len() as usize + 2
+    }
 }
 
 impl InstructionContent for CopyInstruction {
@@ -78,7 +97,7 @@ impl InstructionBytes for CopyInstruction {
     }
 
     fn byte_length(&self) -> usize {
-        self.len() as usize + 2
+        self.encoded_len()
     }
 
     fn to_bytes(&self) -> Vec<u8> {
@@ -111,11 +130,21 @@ impl InstructionBytes for CopyInstruction {
     }
 }
 
-impl Default for CopyInstruction {
-    fn default() -> Self {
-        Self {
-            content: Vec::new(),
+impl InstructionCbor for CopyInstruction {
+    fn to_cbor(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(self.byte_length() + 3);
+        cbor::write_instruction_head(&mut bytes, COPY_INSTRUCTION_SIGN);
+        cbor::write_head(&mut bytes, MAJOR_BYTES, self.len() as u64);
+        bytes.extend(self.content.iter());
+        bytes
+    }
+
+    fn try_from_cbor(bytes: &mut Peekable<Iter<'_, u8>>) -> Result<Self> {
+        if cbor::read_instruction_head(bytes)? != COPY_INSTRUCTION_SIGN {
+            return Err(InstructionError::InvalidSign);
         }
+        let content = cbor::read_content(bytes)?;
+        Ok(Self { content })
     }
 }
 
@@ -178,6 +207,8 @@ mod copy_instruction_tests {
         instruction = CopyInstruction::new(Vec::new());
         assert_eq!(instruction.len(), u8::MIN);
         assert!(instruction.is_empty());
+        assert_eq!(instruction.encoded_len(), instruction.to_bytes().len());
+        assert_eq!(instruction.remaining_capacity(), u8::MAX);
 
         let default_instruction = CopyInstruction::default();
         assert_eq!(default_instruction, instruction);
@@ -191,14 +222,14 @@ mod copy_instruction_tests {
             assert_eq!(instruction.non_default_item_count().unwrap(), 0);
         }
         for i in 0..(u8::MAX / 2) {
-            instruction.push(0 + 1).unwrap();
+            instruction.push(1).unwrap();
             assert_eq!(instruction.non_default_item_count().unwrap(), i + 1);
         }
     }
 
     #[test]
     fn instruction_content_push() {
-        let mut instruction = CopyInstruction::new(vec![0; (u8::MAX - 1).try_into().unwrap()]);
+        let mut instruction = CopyInstruction::new(vec![0; (u8::MAX - 1).into()]);
         assert!(instruction.push(0).is_ok());
         assert!(instruction
             .push(0)
@@ -225,7 +256,7 @@ mod copy_instruction_tests {
 
     #[test]
     fn instruction_bytes_to_bytes() {
-        let mut instruction = CopyInstruction::new(vec![0; u8::MAX.try_into().unwrap()]);
+        let mut instruction = CopyInstruction::new(vec![0; u8::MAX.into()]);
         let mut bytes = vec![COPY_INSTRUCTION_SIGN];
         bytes.extend(instruction.len().to_be_bytes());
         bytes.extend(instruction.content.iter());
@@ -239,7 +270,7 @@ mod copy_instruction_tests {
 
     #[test]
     fn instruction_bytes_try_from_bytes_ok() {
-        let mut instruction = CopyInstruction::new(vec![0; u8::MAX.try_into().unwrap()]);
+        let mut instruction = CopyInstruction::new(vec![0; u8::MAX.into()]);
         assert_eq!(
             CopyInstruction::try_from_bytes(&mut instruction.to_bytes().iter().peekable()).unwrap(),
             instruction
@@ -275,4 +306,23 @@ mod copy_instruction_tests {
             InstructionError::MissingContent
         );
     }
+
+    #[test]
+    fn instruction_cbor() {
+        let instruction = CopyInstruction::new(vec![0, 0, 1]);
+        assert_eq!(
+            CopyInstruction::try_from_cbor(&mut instruction.to_cbor().iter().peekable()),
+            Ok(instruction.clone())
+        );
+
+        let bytes = instruction.to_cbor();
+        assert_eq!(
+            CopyInstruction::try_from_cbor(&mut bytes[..bytes.len() - 1].iter().peekable()),
+            Err(InstructionError::MissingContent)
+        );
+        assert_eq!(
+            CopyInstruction::try_from_cbor(&mut instruction.to_bytes().iter().peekable()),
+            Err(InstructionError::InvalidContent)
+        );
+    }
 }
@@ -1,16 +1,54 @@
 use std::{iter::Peekable, slice::Iter};
 
+use crate::cbor;
+
 use super::{
     add_instruction::AddInstruction, copy_instruction::CopyInstruction,
-    remove_instruction::RemoveInstruction, InstructionBytes, InstructionContent, InstructionError,
-    InstructionInfo, Result, ADD_INSTRUCTION_SIGN, COPY_INSTRUCTION_SIGN, REMOVE_INSTRUCTION_SIGN,
+    remove_instruction::RemoveInstruction, target_copy_instruction::TargetCopyInstruction,
+    InstructionBytes, InstructionCbor, InstructionContent, InstructionError, InstructionInfo,
+    Result, ADD_INSTRUCTION_SIGN, COPY_INSTRUCTION_SIGN, REMOVE_INSTRUCTION_SIGN,
+    TARGET_COPY_INSTRUCTION_SIGN,
 };
 
 #[derive(Debug, PartialEq, Clone)]
+#[non_exhaustive]
 pub enum DeltaInstruction {
     Remove(RemoveInstruction),
     Add(AddInstruction),
     Copy(CopyInstruction),
+    TargetCopy(TargetCopyInstruction),
+}
+
+impl DeltaInstruction {
+    /// The added bytes of an add or the per byte deltas of a copy.
+    pub fn content(&self) -> Option<&[u8]> {
+        match self {
+            DeltaInstruction::Add(instruction) => Some(instruction.content()),
+            DeltaInstruction::Copy(instruction) => Some(instruction.content()),
+            _ => None,
+        }
+    }
+
+    pub fn into_content(self) -> Option<Vec<u8>> {
+        match self {
+            DeltaInstruction::Add(instruction) => Some(instruction.into_content()),
+            DeltaInstruction::Copy(instruction) => Some(instruction.into_content()),
+            _ => None,
+        }
+    }
+
+    pub fn len_usize(&self) -> usize {
+        self.len() as usize
+    }
+
+    /// Bytes allocated by the instruction, not counting the instruction itself.
+    pub fn heap_size(&self) -> usize {
+        match self {
+            DeltaInstruction::Add(instruction) => instruction.heap_size(),
+            DeltaInstruction::Copy(instruction) => instruction.heap_size(),
+            _ => 0,
+        }
+    }
 }
 
 impl InstructionInfo for DeltaInstruction {
@@ -19,6 +57,7 @@ impl InstructionInfo for DeltaInstruction {
             DeltaInstruction::Remove(instruction) => instruction.len(),
             DeltaInstruction::Add(instruction) => instruction.len(),
             DeltaInstruction::Copy(instruction) => instruction.len(),
+            DeltaInstruction::TargetCopy(instruction) => instruction.len(),
         }
     }
 
@@ -27,6 +66,7 @@ impl InstructionInfo for DeltaInstruction {
             DeltaInstruction::Remove(instruction) => instruction.is_empty(),
             DeltaInstruction::Add(instruction) => instruction.is_empty(),
             DeltaInstruction::Copy(instruction) => instruction.is_empty(),
+            DeltaInstruction::TargetCopy(instruction) => instruction.is_empty(),
         }
     }
 
@@ -35,6 +75,7 @@ impl InstructionInfo for DeltaInstruction {
             DeltaInstruction::Remove(instruction) => instruction.is_full(),
             DeltaInstruction::Add(instruction) => instruction.is_full(),
             DeltaInstruction::Copy(instruction) => instruction.is_full(),
+            DeltaInstruction::TargetCopy(instruction) => instruction.is_full(),
         }
     }
 
@@ -43,6 +84,25 @@ impl InstructionInfo for DeltaInstruction {
             DeltaInstruction::Remove(instruction) => instruction.non_default_item_count(),
             DeltaInstruction::Add(instruction) => instruction.non_default_item_count(),
             DeltaInstruction::Copy(instruction) => instruction.non_default_item_count(),
+            DeltaInstruction::TargetCopy(instruction) => instruction.non_default_item_count(),
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        match self {
+            DeltaInstruction::Remove(instruction) => instruction.encoded_len(),
+            DeltaInstruction::Add(instruction) => instruction.encoded_len(),
+            DeltaInstruction::Copy(instruction) => instruction.encoded_len(),
+            DeltaInstruction::TargetCopy(instruction) => instruction.encoded_len(),
+        }
+    }
+
+    fn remaining_capacity(&self) -> u8 {
+        match self {
+            DeltaInstruction::Remove(instruction) => instruction.remaining_capacity(),
+            DeltaInstruction::Add(instruction) => instruction.remaining_capacity(),
+            DeltaInstruction::Copy(instruction) => instruction.remaining_capacity(),
+            DeltaInstruction::TargetCopy(instruction) => instruction.remaining_capacity(),
         }
     }
 }
@@ -53,6 +113,7 @@ impl InstructionContent for DeltaInstruction {
             DeltaInstruction::Remove(instruction) => instruction.push(content),
             DeltaInstruction::Add(instruction) => instruction.push(content),
             DeltaInstruction::Copy(instruction) => instruction.push(content),
+            DeltaInstruction::TargetCopy(instruction) => instruction.push(content),
         }
     }
 
@@ -66,6 +127,7 @@ impl InstructionContent for DeltaInstruction {
             DeltaInstruction::Remove(instruction) => instruction.fill(lcs, source, target),
             DeltaInstruction::Add(instruction) => instruction.fill(lcs, source, target),
             DeltaInstruction::Copy(instruction) => instruction.fill(lcs, source, target),
+            DeltaInstruction::TargetCopy(instruction) => instruction.fill(lcs, source, target),
         }
     }
 
@@ -74,6 +136,7 @@ impl InstructionContent for DeltaInstruction {
             DeltaInstruction::Remove(instruction) => instruction.apply(source, target),
             DeltaInstruction::Add(instruction) => instruction.apply(source, target),
             DeltaInstruction::Copy(instruction) => instruction.apply(source, target),
+            DeltaInstruction::TargetCopy(instruction) => instruction.apply(source, target),
         }
     }
 }
@@ -84,6 +147,7 @@ impl InstructionBytes for DeltaInstruction {
             DeltaInstruction::Remove(instruction) => instruction.byte_sign(),
             DeltaInstruction::Add(instruction) => instruction.byte_sign(),
             DeltaInstruction::Copy(instruction) => instruction.byte_sign(),
+            DeltaInstruction::TargetCopy(instruction) => instruction.byte_sign(),
         }
     }
 
@@ -92,6 +156,7 @@ impl InstructionBytes for DeltaInstruction {
             DeltaInstruction::Remove(instruction) => instruction.byte_length(),
             DeltaInstruction::Add(instruction) => instruction.byte_length(),
             DeltaInstruction::Copy(instruction) => instruction.byte_length(),
+            DeltaInstruction::TargetCopy(instruction) => instruction.byte_length(),
         }
     }
 
@@ -100,6 +165,7 @@ impl InstructionBytes for DeltaInstruction {
             DeltaInstruction::Remove(instruction) => instruction.to_bytes(),
             DeltaInstruction::Add(instruction) => instruction.to_bytes(),
             DeltaInstruction::Copy(instruction) => instruction.to_bytes(),
+            DeltaInstruction::TargetCopy(instruction) => instruction.to_bytes(),
         }
     }
 
@@ -117,12 +183,44 @@ impl InstructionBytes for DeltaInstruction {
             Some(&&COPY_INSTRUCTION_SIGN) => Ok(DeltaInstruction::Copy(
                 CopyInstruction::try_from_bytes(bytes)?,
             )),
+            Some(&&TARGET_COPY_INSTRUCTION_SIGN) => Ok(DeltaInstruction::TargetCopy(
+                TargetCopyInstruction::try_from_bytes(bytes)?,
+            )),
             None => Err(super::InstructionError::MissignSign),
             _ => Err(super::InstructionError::InvalidSign),
         }
     }
 }
 
+impl InstructionCbor for DeltaInstruction {
+    fn to_cbor(&self) -> Vec<u8> {
+        match self {
+            DeltaInstruction::Remove(instruction) => instruction.to_cbor(),
+            DeltaInstruction::Add(instruction) => instruction.to_cbor(),
+            DeltaInstruction::Copy(instruction) => instruction.to_cbor(),
+            DeltaInstruction::TargetCopy(instruction) => instruction.to_cbor(),
+        }
+    }
+
+    fn try_from_cbor(bytes: &mut Peekable<Iter<'_, u8>>) -> Result<Self> {
+        match cbor::read_instruction_head(&mut bytes.clone())? {
+            ADD_INSTRUCTION_SIGN => {
+                Ok(DeltaInstruction::Add(AddInstruction::try_from_cbor(bytes)?))
+            }
+            REMOVE_INSTRUCTION_SIGN => Ok(DeltaInstruction::Remove(
+                RemoveInstruction::try_from_cbor(bytes)?,
+            )),
+            COPY_INSTRUCTION_SIGN => Ok(DeltaInstruction::Copy(CopyInstruction::try_from_cbor(
+                bytes,
+            )?)),
+            TARGET_COPY_INSTRUCTION_SIGN => Ok(DeltaInstruction::TargetCopy(
+                TargetCopyInstruction::try_from_cbor(bytes)?,
+            )),
+            _ => Err(InstructionError::InvalidSign),
+        }
+    }
+}
+
 impl From<RemoveInstruction> for DeltaInstruction {
     fn from(instruction: RemoveInstruction) -> Self {
         DeltaInstruction::Remove(instruction)
@@ -141,6 +239,56 @@ impl From<CopyInstruction> for DeltaInstruction {
     }
 }
 
+impl From<TargetCopyInstruction> for DeltaInstruction {
+    fn from(instruction: TargetCopyInstruction) -> Self {
+        DeltaInstruction::TargetCopy(instruction)
+    }
+}
+
+impl TryFrom<DeltaInstruction> for RemoveInstruction {
+    type Error = InstructionError;
+
+    fn try_from(value: DeltaInstruction) -> std::result::Result<Self, Self::Error> {
+        match value {
+            DeltaInstruction::Remove(instruction) => Ok(instruction),
+            _ => Err(InstructionError::InvalidSign),
+        }
+    }
+}
+
+impl TryFrom<DeltaInstruction> for AddInstruction {
+    type Error = InstructionError;
+
+    fn try_from(value: DeltaInstruction) -> std::result::Result<Self, Self::Error> {
+        match value {
+            DeltaInstruction::Add(instruction) => Ok(instruction),
+            _ => Err(InstructionError::InvalidSign),
+        }
+    }
+}
+
+impl TryFrom<DeltaInstruction> for CopyInstruction {
+    type Error = InstructionError;
+
+    fn try_from(value: DeltaInstruction) -> std::result::Result<Self, Self::Error> {
+        match value {
+            DeltaInstruction::Copy(instruction) => Ok(instruction),
+            _ => Err(InstructionError::InvalidSign),
+        }
+    }
+}
+
+impl TryFrom<DeltaInstruction> for TargetCopyInstruction {
+    type Error = InstructionError;
+
+    fn try_from(value: DeltaInstruction) -> std::result::Result<Self, Self::Error> {
+        match value {
+            DeltaInstruction::TargetCopy(instruction) => Ok(instruction),
+            _ => Err(InstructionError::InvalidSign),
+        }
+    }
+}
+
 impl From<&DeltaInstruction> for Vec<u8> {
     fn from(value: &DeltaInstruction) -> Self {
         value.to_bytes()
@@ -190,6 +338,63 @@ mod delta_instruction_tests {
 
     use super::*;
 
+    #[test]
+    fn struct_conversions() {
+        let add_instruction = AddInstruction::new(vec![1, 2, 3]);
+        let wrapped: DeltaInstruction = add_instruction.clone().into();
+        assert_eq!(
+            AddInstruction::try_from(wrapped.clone()),
+            Ok(add_instruction)
+        );
+        assert_eq!(
+            CopyInstruction::try_from(wrapped.clone()),
+            Err(InstructionError::InvalidSign)
+        );
+        assert_eq!(
+            RemoveInstruction::try_from(DeltaInstruction::from(RemoveInstruction::new(4))),
+            Ok(RemoveInstruction::new(4))
+        );
+        assert_eq!(
+            TargetCopyInstruction::try_from(wrapped),
+            Err(InstructionError::InvalidSign)
+        );
+    }
+
+    #[test]
+    fn content() {
+        let add: DeltaInstruction = AddInstruction::new(vec![1, 2, 3]).into();
+        let copy: DeltaInstruction = CopyInstruction::new(vec![0, 5]).into();
+        let remove: DeltaInstruction = RemoveInstruction::new(200).into();
+        let target_copy: DeltaInstruction = TargetCopyInstruction::new(4, 1).into();
+        assert_eq!(add.content(), Some(&[1, 2, 3][..]));
+        assert_eq!(copy.content(), Some(&[0, 5][..]));
+        assert_eq!(remove.content(), None);
+        assert_eq!(target_copy.content(), None);
+        assert_eq!(add.len_usize(), 3);
+        assert_eq!(remove.len_usize(), 200);
+        assert_eq!(add.into_content(), Some(vec![1, 2, 3]));
+        assert_eq!(copy.into_content(), Some(vec![0, 5]));
+        assert_eq!(target_copy.into_content(), None);
+    }
+
+    #[test]
+    fn heap_size() {
+        let mut content = Vec::with_capacity(64);
+        content.extend([1, 2, 3]);
+        let add: DeltaInstruction = AddInstruction::new(content).into();
+        assert_eq!(add.heap_size(), 64);
+        let copy: DeltaInstruction = CopyInstruction::new(vec![0; 10]).into();
+        assert_eq!(copy.heap_size(), 10);
+        assert_eq!(
+            DeltaInstruction::from(RemoveInstruction::new(3)).heap_size(),
+            0
+        );
+        assert_eq!(
+            DeltaInstruction::from(TargetCopyInstruction::new(3, 1)).heap_size(),
+            0
+        );
+    }
+
     #[test]
     pub fn instruction_info() {
         let remove_instruction = RemoveInstruction::default();
@@ -227,6 +432,15 @@ mod delta_instruction_tests {
             copy_instruction.is_full()
         );
 
+        assert_eq!(
+            wrapped_add_instruction.encoded_len(),
+            add_instruction.encoded_len()
+        );
+        assert_eq!(
+            wrapped_copy_instruction.remaining_capacity(),
+            copy_instruction.remaining_capacity()
+        );
+
         assert_eq!(
             wrapped_remove_instruction.non_default_item_count(),
             remove_instruction.non_default_item_count()
@@ -350,6 +564,28 @@ mod delta_instruction_tests {
         );
     }
 
+    #[test]
+    fn instruction_cbor() {
+        let instructions: Vec<DeltaInstruction> = vec![
+            RemoveInstruction::new(3).into(),
+            AddInstruction::new(vec![1, 2, 3]).into(),
+            CopyInstruction::new(vec![0, 0, 1]).into(),
+        ];
+        for instruction in instructions {
+            assert_eq!(
+                DeltaInstruction::try_from_cbor(&mut instruction.to_cbor().iter().peekable()),
+                Ok(instruction)
+            );
+        }
+
+        let mut bytes = Vec::new();
+        cbor::write_instruction_head(&mut bytes, b'?');
+        assert_eq!(
+            DeltaInstruction::try_from_cbor(&mut bytes.iter().peekable()),
+            Err(InstructionError::InvalidSign)
+        );
+    }
+
     #[test]
     fn into() {
         let remove_instruction = RemoveInstruction::default();
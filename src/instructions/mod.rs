@@ -4,15 +4,27 @@ pub mod add_instruction;
 pub mod copy_instruction;
 pub mod delta_instruction;
 pub mod remove_instruction;
+pub mod target_copy_instruction;
 
 pub type Result<T> = std::result::Result<T, InstructionError>;
 
-const REMOVE_INSTRUCTION_SIGN: u8 = b'-';
-const ADD_INSTRUCTION_SIGN: u8 = b'+';
-const COPY_INSTRUCTION_SIGN: u8 = b'|';
+pub(crate) const REMOVE_INSTRUCTION_SIGN: u8 = b'-';
+pub(crate) const ADD_INSTRUCTION_SIGN: u8 = b'+';
+pub(crate) const COPY_INSTRUCTION_SIGN: u8 = b'|';
+pub(crate) const TARGET_COPY_INSTRUCTION_SIGN: u8 = b'<';
 
 const NON_ZERO_MAX_COUNT_PERCENT: u8 = 100;
 
+pub(crate) fn is_known_sign(sign: u8) -> bool {
+    matches!(
+        sign,
+        REMOVE_INSTRUCTION_SIGN
+            | ADD_INSTRUCTION_SIGN
+            | COPY_INSTRUCTION_SIGN
+            | TARGET_COPY_INSTRUCTION_SIGN
+    )
+}
+
 use std::{iter::Peekable, slice::Iter};
 
 pub trait InstructionInfo {
@@ -27,6 +39,12 @@ pub trait InstructionInfo {
     }
 
     fn non_default_item_count(&self) -> Option<u8>;
+
+    fn encoded_len(&self) -> usize;
+
+    fn remaining_capacity(&self) -> u8 {
+        u8::MAX - self.len()
+    }
 }
 
 pub trait InstructionContent {
@@ -53,6 +71,14 @@ pub trait InstructionBytes {
         Self: Sized;
 }
 
+pub trait InstructionCbor {
+    fn to_cbor(&self) -> Vec<u8>;
+
+    fn try_from_cbor(bytes: &mut Peekable<Iter<'_, u8>>) -> Result<Self>
+    where
+        Self: Sized;
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum InstructionError {
     ContentOverflow,
@@ -77,8 +103,11 @@ impl std::fmt::Display for InstructionError {
             InstructionError::MissignSign => write!(f, "No instruction sign found"),
             InstructionError::InvalidSign => write!(
                 f,
-                "Instruction sign didn't match: {}, {} or {}",
-                REMOVE_INSTRUCTION_SIGN, ADD_INSTRUCTION_SIGN, COPY_INSTRUCTION_SIGN
+                "Instruction sign didn't match: {}, {}, {} or {}",
+                REMOVE_INSTRUCTION_SIGN,
+                ADD_INSTRUCTION_SIGN,
+                COPY_INSTRUCTION_SIGN,
+                TARGET_COPY_INSTRUCTION_SIGN
             ),
             InstructionError::MissingLength => write!(f, "No length value found"),
             InstructionError::MissingContent => {
@@ -104,10 +133,7 @@ impl Error for InstructionError {}
 mod instruction_mod_tests {
     use super::*;
 
-    fn threshold(
-        len: u8,
-        non_zero_max_count_percent: u8,
-    ) -> u8 {
+    fn threshold(len: u8, non_zero_max_count_percent: u8) -> u8 {
         ((len as f32 * non_zero_max_count_percent as f32) / 100f32) as u8
     }
 
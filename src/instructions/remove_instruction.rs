@@ -1,8 +1,10 @@
 use std::{iter::Peekable, slice::Iter};
 
+use crate::cbor::{self, MAJOR_UNSIGNED};
+
 use super::{
-    InstructionBytes, InstructionContent, InstructionError, InstructionInfo, Result,
-    REMOVE_INSTRUCTION_SIGN,
+    InstructionBytes, InstructionCbor, InstructionContent, InstructionError, InstructionInfo,
+    Result, REMOVE_INSTRUCTION_SIGN,
 };
 
 #[derive(Debug, PartialEq, Clone)]
@@ -32,6 +34,10 @@ impl InstructionInfo for RemoveInstruction {
     fn non_default_item_count(&self) -> Option<u8> {
         None
     }
+
+    fn encoded_len(&self) -> usize {
+        2
+    }
 }
 
 impl InstructionContent for RemoveInstruction {
@@ -67,7 +73,7 @@ impl InstructionBytes for RemoveInstruction {
     }
 
     fn byte_length(&self) -> usize {
-        2
+        self.encoded_len()
     }
 
     fn to_bytes(&self) -> Vec<u8> {
@@ -90,6 +96,29 @@ impl InstructionBytes for RemoveInstruction {
     }
 }
 
+impl InstructionCbor for RemoveInstruction {
+    fn to_cbor(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(self.byte_length() + 3);
+        cbor::write_instruction_head(&mut bytes, REMOVE_INSTRUCTION_SIGN);
+        cbor::write_head(&mut bytes, MAJOR_UNSIGNED, self.len() as u64);
+        bytes
+    }
+
+    fn try_from_cbor(bytes: &mut Peekable<Iter<'_, u8>>) -> Result<Self> {
+        if cbor::read_instruction_head(bytes)? != REMOVE_INSTRUCTION_SIGN {
+            return Err(InstructionError::InvalidSign);
+        }
+        let length = match cbor::read_head(bytes) {
+            Ok((MAJOR_UNSIGNED, length)) => {
+                u8::try_from(length).map_err(|_| InstructionError::InvalidLength)?
+            }
+            Ok(_) => return Err(InstructionError::InvalidLength),
+            Err(_) => return Err(InstructionError::MissingLength),
+        };
+        Ok(Self { length })
+    }
+}
+
 impl Default for RemoveInstruction {
     fn default() -> Self {
         Self::new(u8::MIN)
@@ -155,6 +184,8 @@ mod remove_instruction_tests {
         instruction = RemoveInstruction::new(u8::MIN);
         assert_eq!(instruction.len(), u8::MIN);
         assert!(instruction.is_empty());
+        assert_eq!(instruction.encoded_len(), instruction.to_bytes().len());
+        assert_eq!(instruction.remaining_capacity(), u8::MAX);
 
         let default_instruction = RemoveInstruction::default();
         assert_eq!(default_instruction, instruction);
@@ -235,4 +266,23 @@ mod remove_instruction_tests {
             Err(InstructionError::MissingLength)
         );
     }
+
+    #[test]
+    fn instruction_cbor() {
+        let instruction = RemoveInstruction::new(u8::MAX);
+        assert_eq!(
+            RemoveInstruction::try_from_cbor(&mut instruction.to_cbor().iter().peekable()),
+            Ok(instruction.clone())
+        );
+
+        let bytes = instruction.to_cbor();
+        assert_eq!(
+            RemoveInstruction::try_from_cbor(&mut bytes[..bytes.len() - 1].iter().peekable()),
+            Err(InstructionError::MissingLength)
+        );
+        assert_eq!(
+            RemoveInstruction::try_from_cbor(&mut instruction.to_bytes().iter().peekable()),
+            Err(InstructionError::InvalidContent)
+        );
+    }
 }
@@ -0,0 +1,313 @@
+use std::{iter::Peekable, slice::Iter};
+
+use crate::cbor::{self, MAJOR_ARRAY, MAJOR_UNSIGNED};
+
+use super::{
+    InstructionBytes, InstructionCbor, InstructionContent, InstructionError, InstructionInfo,
+    Result, TARGET_COPY_INSTRUCTION_SIGN,
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TargetCopyInstruction {
+    length: u8,
+    distance: u32,
+}
+
+impl TargetCopyInstruction {
+    pub fn new(length: u8, distance: u32) -> Self {
+        assert!(
+            distance > 0,
+            "Target copy distance must be greater than zero"
+        );
+        Self { length, distance }
+    }
+
+    pub fn distance(&self) -> u32 {
+        self.distance
+    }
+}
+
+impl InstructionInfo for TargetCopyInstruction {
+    fn len(&self) -> u8 {
+        self.length
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == u8::MIN
+    }
+
+    fn is_full(&self) -> bool {
+        self.len() == u8::MAX
+    }
+
+    fn non_default_item_count(&self) -> Option<u8> {
+        None
+    }
+
+    fn encoded_len(&self) -> usize {
+        6
+    }
+}
+
+impl InstructionContent for TargetCopyInstruction {
+    fn push(&mut self, _: u8) -> Result<()> {
+        if self.is_full() {
+            return Err(InstructionError::ContentOverflow);
+        }
+        self.length += 1;
+        Ok(())
+    }
+
+    fn fill(
+        &mut self,
+        _: &mut Peekable<Iter<'_, u8>>,
+        _: &mut Peekable<Iter<'_, u8>>,
+        _: &mut Peekable<Iter<'_, u8>>,
+    ) {
+    }
+
+    fn apply(&self, _: &mut Iter<'_, u8>, target: &mut Vec<u8>) {
+        assert!(
+            self.distance as usize <= target.len() || self.is_empty(),
+            "Target copy reaches before the start of the target"
+        );
+        for _ in 0..self.len() {
+            target.push(target[target.len() - self.distance as usize]);
+        }
+    }
+}
+
+impl InstructionBytes for TargetCopyInstruction {
+    fn byte_sign(&self) -> u8 {
+        TARGET_COPY_INSTRUCTION_SIGN
+    }
+
+    fn byte_length(&self) -> usize {
+        self.encoded_len()
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(self.byte_length());
+        bytes.push(self.byte_sign());
+        bytes.push(self.len());
+        bytes.extend(self.distance.to_be_bytes());
+        bytes
+    }
+
+    fn try_from_bytes(bytes: &mut Peekable<Iter<'_, u8>>) -> Result<Self> {
+        match bytes.next() {
+            Some(&TARGET_COPY_INSTRUCTION_SIGN) => (),
+            Some(_) => return Err(InstructionError::InvalidSign),
+            None => return Err(InstructionError::MissignSign),
+        };
+
+        let length = *bytes.next().ok_or(InstructionError::MissingLength)?;
+        let mut distance = [0u8; 4];
+        for item in distance.iter_mut() {
+            *item = *bytes.next().ok_or(InstructionError::MissingContent)?;
+        }
+        match u32::from_be_bytes(distance) {
+            0 => Err(InstructionError::InvalidContent),
+            distance => Ok(Self { length, distance }),
+        }
+    }
+}
+
+impl InstructionCbor for TargetCopyInstruction {
+    fn to_cbor(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(self.byte_length() + 5);
+        cbor::write_instruction_head(&mut bytes, TARGET_COPY_INSTRUCTION_SIGN);
+        cbor::write_head(&mut bytes, MAJOR_ARRAY, 2);
+        cbor::write_head(&mut bytes, MAJOR_UNSIGNED, self.len() as u64);
+        cbor::write_head(&mut bytes, MAJOR_UNSIGNED, self.distance as u64);
+        bytes
+    }
+
+    fn try_from_cbor(bytes: &mut Peekable<Iter<'_, u8>>) -> Result<Self> {
+        if cbor::read_instruction_head(bytes)? != TARGET_COPY_INSTRUCTION_SIGN {
+            return Err(InstructionError::InvalidSign);
+        }
+        match cbor::read_head(bytes) {
+            Ok((MAJOR_ARRAY, 2)) => (),
+            Ok(_) => return Err(InstructionError::InvalidContent),
+            Err(_) => return Err(InstructionError::MissingLength),
+        };
+        let length = match cbor::read_head(bytes) {
+            Ok((MAJOR_UNSIGNED, length)) => {
+                u8::try_from(length).map_err(|_| InstructionError::InvalidLength)?
+            }
+            Ok(_) => return Err(InstructionError::InvalidLength),
+            Err(_) => return Err(InstructionError::MissingLength),
+        };
+        let distance = match cbor::read_head(bytes)? {
+            (MAJOR_UNSIGNED, distance) => {
+                u32::try_from(distance).map_err(|_| InstructionError::InvalidContent)?
+            }
+            _ => return Err(InstructionError::InvalidContent),
+        };
+        if distance == 0 {
+            return Err(InstructionError::InvalidContent);
+        }
+        Ok(Self { length, distance })
+    }
+}
+
+impl Default for TargetCopyInstruction {
+    fn default() -> Self {
+        Self::new(u8::MIN, 1)
+    }
+}
+
+impl From<&TargetCopyInstruction> for Vec<u8> {
+    fn from(value: &TargetCopyInstruction) -> Self {
+        value.to_bytes()
+    }
+}
+
+impl From<TargetCopyInstruction> for Vec<u8> {
+    fn from(value: TargetCopyInstruction) -> Self {
+        value.to_bytes()
+    }
+}
+
+impl TryFrom<&mut Peekable<Iter<'_, u8>>> for TargetCopyInstruction {
+    type Error = InstructionError;
+
+    fn try_from(value: &mut Peekable<Iter<'_, u8>>) -> std::result::Result<Self, Self::Error> {
+        TargetCopyInstruction::try_from_bytes(value)
+    }
+}
+
+impl TryFrom<Peekable<Iter<'_, u8>>> for TargetCopyInstruction {
+    type Error = InstructionError;
+
+    fn try_from(mut value: Peekable<Iter<'_, u8>>) -> std::result::Result<Self, Self::Error> {
+        TargetCopyInstruction::try_from_bytes(&mut value)
+    }
+}
+
+impl TryFrom<Vec<u8>> for TargetCopyInstruction {
+    type Error = InstructionError;
+
+    fn try_from(value: Vec<u8>) -> std::result::Result<Self, Self::Error> {
+        TargetCopyInstruction::try_from_bytes(&mut value.iter().peekable())
+    }
+}
+
+impl TryFrom<&[u8]> for TargetCopyInstruction {
+    type Error = InstructionError;
+
+    fn try_from(value: &[u8]) -> std::result::Result<Self, Self::Error> {
+        TargetCopyInstruction::try_from_bytes(&mut value.iter().peekable())
+    }
+}
+
+#[cfg(test)]
+mod target_copy_instruction_tests {
+    use super::*;
+
+    #[test]
+    fn instruction_info() {
+        let mut instruction = TargetCopyInstruction::new(u8::MAX, 3);
+        assert_eq!(instruction.len(), u8::MAX);
+        assert_eq!(instruction.distance(), 3);
+        assert!(instruction.is_full());
+        assert_eq!(instruction.encoded_len(), instruction.to_bytes().len());
+        assert_eq!(instruction.remaining_capacity(), 0);
+
+        instruction = TargetCopyInstruction::new(u8::MIN, 1);
+        assert!(instruction.is_empty());
+        assert_eq!(TargetCopyInstruction::default(), instruction);
+    }
+
+    #[test]
+    fn instruction_content_push() {
+        let mut instruction = TargetCopyInstruction::new(u8::MAX - 1, 1);
+        assert!(instruction.push(0).is_ok());
+        assert_eq!(instruction.push(0), Err(InstructionError::ContentOverflow));
+    }
+
+    #[test]
+    fn instruction_content_apply() {
+        let mut target = b"ABC".to_vec();
+        TargetCopyInstruction::new(2, 3).apply(&mut [].iter(), &mut target);
+        assert_eq!(target, b"ABCAB");
+        TargetCopyInstruction::new(5, 1).apply(&mut [].iter(), &mut target);
+        assert_eq!(target, b"ABCABBBBBB");
+        TargetCopyInstruction::new(4, 2).apply(&mut [].iter(), &mut target);
+        assert_eq!(target, b"ABCABBBBBBBBBB");
+    }
+
+    #[test]
+    #[should_panic]
+    fn instruction_content_apply_out_of_range() {
+        TargetCopyInstruction::new(2, 4).apply(&mut [].iter(), &mut b"ABC".to_vec());
+    }
+
+    #[test]
+    fn instruction_bytes_to_bytes() {
+        let instruction = TargetCopyInstruction::new(u8::MAX, 0x01020304);
+        assert_eq!(
+            instruction.to_bytes(),
+            vec![TARGET_COPY_INSTRUCTION_SIGN, u8::MAX, 1, 2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn instruction_bytes_try_from_bytes_ok() {
+        let instruction = TargetCopyInstruction::new(7, u32::MAX);
+        let bytes = instruction.to_bytes();
+        assert_eq!(
+            TargetCopyInstruction::try_from_bytes(&mut bytes.iter().peekable()),
+            Ok(instruction)
+        );
+    }
+
+    #[test]
+    fn instruction_bytes_try_from_bytes_err() {
+        let mut bytes: Vec<u8> = vec![];
+        assert_eq!(
+            TargetCopyInstruction::try_from_bytes(&mut bytes.iter().peekable()),
+            Err(InstructionError::MissignSign)
+        );
+        bytes = vec![b'A'];
+        assert_eq!(
+            TargetCopyInstruction::try_from_bytes(&mut bytes.iter().peekable()),
+            Err(InstructionError::InvalidSign)
+        );
+        bytes = vec![TARGET_COPY_INSTRUCTION_SIGN];
+        assert_eq!(
+            TargetCopyInstruction::try_from_bytes(&mut bytes.iter().peekable()),
+            Err(InstructionError::MissingLength)
+        );
+        bytes = vec![TARGET_COPY_INSTRUCTION_SIGN, 1, 0, 0];
+        assert_eq!(
+            TargetCopyInstruction::try_from_bytes(&mut bytes.iter().peekable()),
+            Err(InstructionError::MissingContent)
+        );
+        bytes = vec![TARGET_COPY_INSTRUCTION_SIGN, 1, 0, 0, 0, 0];
+        assert_eq!(
+            TargetCopyInstruction::try_from_bytes(&mut bytes.iter().peekable()),
+            Err(InstructionError::InvalidContent)
+        );
+    }
+
+    #[test]
+    fn instruction_cbor() {
+        let instruction = TargetCopyInstruction::new(u8::MAX, 70_000);
+        assert_eq!(
+            TargetCopyInstruction::try_from_cbor(&mut instruction.to_cbor().iter().peekable()),
+            Ok(instruction.clone())
+        );
+
+        let bytes = instruction.to_cbor();
+        assert_eq!(
+            TargetCopyInstruction::try_from_cbor(&mut bytes[..bytes.len() - 1].iter().peekable()),
+            Err(InstructionError::MissingContent)
+        );
+        assert_eq!(
+            TargetCopyInstruction::try_from_cbor(&mut instruction.to_bytes().iter().peekable()),
+            Err(InstructionError::InvalidContent)
+        );
+    }
+}
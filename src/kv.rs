@@ -0,0 +1,181 @@
+use std::error::Error;
+
+use crate::{
+    instructions::{delta_instruction::DeltaInstruction, InstructionError, InstructionInfo},
+    patch::Patch,
+};
+
+const FULL_TAG: u8 = b'F';
+const DELTA_TAG: u8 = b'D';
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub enum AccessHint {
+    ReadHeavy,
+    #[default]
+    Balanced,
+    WriteHeavy,
+}
+
+impl AccessHint {
+    fn max_delta_ratio(&self) -> f64 {
+        match self {
+            AccessHint::ReadHeavy => 0.25,
+            AccessHint::Balanced => 0.5,
+            AccessHint::WriteHeavy => 0.9,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum StoredValue {
+    Full(Vec<u8>),
+    Delta(Vec<u8>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum StoredValueError {
+    MissingTag,
+    InvalidTag,
+    InvalidPatch(InstructionError),
+    BaseMismatch,
+}
+
+impl std::fmt::Display for StoredValueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StoredValueError::MissingTag => write!(f, "No stored value tag found"),
+            StoredValueError::InvalidTag => write!(
+                f,
+                "Invalid stored value tag found. Valid tags: '{}', '{}'",
+                FULL_TAG as char, DELTA_TAG as char
+            ),
+            StoredValueError::InvalidPatch(err) => write!(f, "Invalid stored delta: {}", err),
+            StoredValueError::BaseMismatch => {
+                write!(f, "Base value doesn't match the stored delta")
+            }
+        }
+    }
+}
+
+impl Error for StoredValueError {}
+
+impl StoredValue {
+    pub fn is_delta(&self) -> bool {
+        matches!(self, StoredValue::Delta(_))
+    }
+
+    pub fn resolve(&self, old: &[u8]) -> Result<Vec<u8>, StoredValueError> {
+        match self {
+            StoredValue::Full(value) => Ok(value.clone()),
+            StoredValue::Delta(patch) => Patch::try_from(patch.as_slice())
+                .map_err(StoredValueError::InvalidPatch)?
+                .apply(old)
+                .ok_or(StoredValueError::BaseMismatch),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let (tag, bytes) = match self {
+            StoredValue::Full(value) => (FULL_TAG, value),
+            StoredValue::Delta(patch) => (DELTA_TAG, patch),
+        };
+        let mut encoded = Vec::with_capacity(bytes.len() + 1);
+        encoded.push(tag);
+        encoded.extend(bytes);
+        encoded
+    }
+
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, StoredValueError> {
+        match bytes.split_first() {
+            Some((&FULL_TAG, value)) => Ok(StoredValue::Full(value.to_vec())),
+            Some((&DELTA_TAG, patch)) => Ok(StoredValue::Delta(patch.to_vec())),
+            Some(_) => Err(StoredValueError::InvalidTag),
+            None => Err(StoredValueError::MissingTag),
+        }
+    }
+}
+
+fn delta_cost(patch: &Patch) -> usize {
+    patch
+        .instructions()
+        .iter()
+        .map(|instruction| {
+            2 + match instruction {
+                DeltaInstruction::Remove(_) => 0,
+                DeltaInstruction::Add(_) => instruction.len() as usize,
+                DeltaInstruction::Copy(_) => instruction.non_default_item_count().unwrap() as usize,
+                DeltaInstruction::TargetCopy(_) => 4,
+            }
+        })
+        .sum()
+}
+
+pub fn compact(old: Option<&[u8]>, new: &[u8], hint: AccessHint) -> StoredValue {
+    let Some(old) = old else {
+        return StoredValue::Full(new.to_vec());
+    };
+    let patch = Patch::new(old, new);
+    match delta_cost(&patch) as f64 <= hint.max_delta_ratio() * new.len() as f64 {
+        true => StoredValue::Delta(patch.into()),
+        false => StoredValue::Full(new.to_vec()),
+    }
+}
+
+#[cfg(test)]
+mod kv_tests {
+    use super::*;
+
+    #[test]
+    fn compact() {
+        let old = vec![b'A'; 400];
+        let mut new = old.clone();
+        new.extend(b"tail");
+
+        let stored = super::compact(Some(&old), &new, AccessHint::Balanced);
+        assert!(stored.is_delta());
+        assert_eq!(stored.resolve(&old).unwrap(), new);
+
+        let sparse: Vec<u8> = (0..old.len())
+            .map(|index| match index % 3 {
+                0 => b'B',
+                _ => b'A',
+            })
+            .collect();
+        let stored = super::compact(Some(&old), &sparse, AccessHint::Balanced);
+        assert!(stored.is_delta());
+        assert_eq!(stored.resolve(&old).unwrap(), sparse);
+        let stored = super::compact(Some(&old), &sparse, AccessHint::ReadHeavy);
+        assert_eq!(stored, StoredValue::Full(sparse.clone()));
+
+        let stored = super::compact(None, &new, AccessHint::WriteHeavy);
+        assert_eq!(stored, StoredValue::Full(new.clone()));
+
+        let stored = super::compact(Some(b"abc"), b"xyz", AccessHint::WriteHeavy);
+        assert_eq!(stored, StoredValue::Full(b"xyz".to_vec()));
+    }
+
+    #[test]
+    fn stored_value_bytes() {
+        let stored = super::compact(Some(b"Hello world"), b"Hello there", AccessHint::default());
+        let bytes = stored.to_bytes();
+        assert_eq!(StoredValue::try_from_bytes(&bytes), Ok(stored.clone()));
+        assert_eq!(
+            StoredValue::try_from_bytes(&[]),
+            Err(StoredValueError::MissingTag)
+        );
+        assert_eq!(
+            StoredValue::try_from_bytes(b"X"),
+            Err(StoredValueError::InvalidTag)
+        );
+        assert_eq!(
+            StoredValue::Delta(vec![b'?']).resolve(b""),
+            Err(StoredValueError::InvalidPatch(
+                InstructionError::InvalidSign
+            ))
+        );
+        assert_eq!(
+            StoredValue::Delta(Patch::new(b"AB", b"AC").into()).resolve(b"A"),
+            Err(StoredValueError::BaseMismatch)
+        );
+    }
+}
@@ -1,12 +1,267 @@
-mod instructions;
+#[cfg(all(test, feature = "alloc-counter"))]
+mod alloc_counter;
+pub mod apply;
+pub mod armor;
+pub mod audit;
+pub mod batch;
+pub mod bitdiff;
+#[cfg(all(unix, feature = "block-device"))]
+pub mod block_device;
+#[cfg(feature = "bytes")]
+pub mod buf;
+pub mod cache;
+pub mod capabilities;
+mod cbor;
+pub mod cell;
+pub mod checkpoint;
+pub mod chunk;
+pub mod dictionary;
+pub mod digest;
+pub mod edit;
+pub mod encode;
+pub mod encrypt;
+pub mod estimate;
+pub mod flash;
+#[cfg(feature = "fuse")]
+pub mod fuse;
+pub mod instructions;
+pub mod kv;
 mod lcs;
+pub mod lint;
+#[cfg(feature = "manifest")]
+pub mod manifest;
+pub mod matcher;
+pub mod merkle;
+pub mod micro;
 pub mod patch;
+pub mod profile;
+pub mod progressive;
+pub mod range_coder;
+pub mod redact;
+pub mod risk;
+#[cfg(feature = "ropey")]
+pub mod rope;
+#[cfg(all(target_os = "linux", feature = "sandbox"))]
+pub mod sandbox;
+#[cfg(test)]
+mod sim;
+pub mod slot;
+pub mod snapshot;
+pub mod stats;
+pub mod telemetry;
+pub mod text;
+pub mod transparency;
+pub mod update;
+pub mod vectors;
+pub mod version;
+pub mod view;
+pub mod xor;
+
+use std::{error::Error, ops::Range};
+
+use instructions::{
+    InstructionError, ADD_INSTRUCTION_SIGN, COPY_INSTRUCTION_SIGN, REMOVE_INSTRUCTION_SIGN,
+    TARGET_COPY_INSTRUCTION_SIGN,
+};
+use patch::Patch;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ApplyError {
+    InvalidPatch(InstructionError),
+    SourceMismatch,
+    OutputTooSmall(usize),
+}
+
+impl std::fmt::Display for ApplyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApplyError::InvalidPatch(error) => write!(f, "Invalid patch: {}", error),
+            ApplyError::SourceMismatch => {
+                write!(f, "Source length doesn't match the patch source length")
+            }
+            ApplyError::OutputTooSmall(length) => {
+                write!(f, "Output buffer is too small for {} target bytes", length)
+            }
+        }
+    }
+}
+
+impl Error for ApplyError {}
+
+impl From<InstructionError> for ApplyError {
+    fn from(error: InstructionError) -> Self {
+        ApplyError::InvalidPatch(error)
+    }
+}
+
+/// Encodes the changes from `source` to `target` as patch bytes.
+///
+/// Encoding can't fail; use [`Patch::new`] to inspect the instructions instead.
+pub fn diff<S: AsRef<[u8]>, T: AsRef<[u8]>>(source: S, target: T) -> Vec<u8> {
+    Patch::new(source.as_ref(), target.as_ref()).to_bytes()
+}
+
+/// Encodes `target` against `source` inspecting only `dirty_ranges` of the target.
+///
+/// Bytes outside the ranges are trusted to be unchanged and copied from the same offset in
+/// `source`, so encoding is linear in the dirty bytes rather than the input size.
+pub fn diff_with_hints<S: AsRef<[u8]>, T: AsRef<[u8]>>(
+    source: S,
+    target: T,
+    dirty_ranges: &[Range<usize>],
+) -> Vec<u8> {
+    Patch::new_with_hints(source.as_ref(), target.as_ref(), dirty_ranges).to_bytes()
+}
+
+/// Encodes `target` against `source`, starting from `previous_patch`, the last patch encoded
+/// from `source`.
+///
+/// Only the window where `target` differs from the previous target is diffed, so encoding
+/// a target that changed slightly since the last diff is linear in the input size. An
+/// invalid or mismatched `previous_patch` falls back to [`diff`].
+pub fn diff_with_hint<S: AsRef<[u8]>, T: AsRef<[u8]>, P: AsRef<[u8]>>(
+    source: S,
+    target: T,
+    previous_patch: P,
+) -> Vec<u8> {
+    let (source, target) = (source.as_ref(), target.as_ref());
+    match Patch::try_from_bytes(previous_patch.as_ref()) {
+        Ok(previous) => Patch::new_with_previous(source, target, &previous).to_bytes(),
+        Err(_) => Patch::new(source, target).to_bytes(),
+    }
+}
+
+/// Decodes `patch` and applies it to `source`.
+///
+/// Fails with [`ApplyError::InvalidPatch`] if the bytes aren't a valid patch and with
+/// [`ApplyError::SourceMismatch`] if `source` isn't the length the patch was made for.
+pub fn apply<S: AsRef<[u8]>, P: AsRef<[u8]>>(source: S, patch: P) -> Result<Vec<u8>, ApplyError> {
+    Patch::try_from_bytes(patch.as_ref())?
+        .apply(source.as_ref())
+        .ok_or(ApplyError::SourceMismatch)
+}
+
+/// Like [`apply`], for patches and sources that are known to match.
+///
+/// # Panics
+///
+/// Panics if [`apply`] would return an error.
+pub fn apply_unchecked<S: AsRef<[u8]>, P: AsRef<[u8]>>(source: S, patch: P) -> Vec<u8> {
+    match apply(source, patch) {
+        Ok(target) => target,
+        Err(error) => panic!("{}", error),
+    }
+}
+
+/// Walks raw patch bytes as `(sign, length, content)` without decoding them into a [`Patch`].
+struct RawInstructions<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Iterator for RawInstructions<'a> {
+    type Item = Result<(u8, usize, &'a [u8]), InstructionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&sign, rest) = self.bytes.split_first()?;
+        let content_length = match sign {
+            REMOVE_INSTRUCTION_SIGN => Some(0),
+            ADD_INSTRUCTION_SIGN | COPY_INSTRUCTION_SIGN => None,
+            TARGET_COPY_INSTRUCTION_SIGN => Some(4),
+            _ => {
+                self.bytes = &[];
+                return Some(Err(InstructionError::InvalidSign));
+            }
+        };
+        let Some((&length, rest)) = rest.split_first() else {
+            self.bytes = &[];
+            return Some(Err(InstructionError::MissingLength));
+        };
+        let content_length = content_length.unwrap_or(length as usize);
+        if rest.len() < content_length {
+            self.bytes = &[];
+            return Some(Err(InstructionError::MissingContent));
+        }
+        let (content, rest) = rest.split_at(content_length);
+        self.bytes = rest;
+        Some(Ok((sign, length as usize, content)))
+    }
+}
+
+/// Applies `patch` to `source` into `out` without allocating, returning the target length.
+///
+/// The patch is validated completely before anything is written, so `out` is left untouched
+/// on error. Fails with [`ApplyError::OutputTooSmall`] if the target doesn't fit in `out`.
+///
+/// No scratch buffer is needed: adds and copies read from `patch` and `source`, and target
+/// copies read back from the part of `out` already written.
+pub fn apply_no_alloc<S: AsRef<[u8]>, P: AsRef<[u8]>>(
+    source: S,
+    patch: P,
+    out: &mut [u8],
+) -> Result<usize, ApplyError> {
+    let (source, patch) = (source.as_ref(), patch.as_ref());
+    let (mut source_length, mut target_length) = (0usize, 0usize);
+    for instruction in (RawInstructions { bytes: patch }) {
+        let (sign, length, content) = instruction?;
+        match sign {
+            REMOVE_INSTRUCTION_SIGN => source_length += length,
+            COPY_INSTRUCTION_SIGN => source_length += length,
+            TARGET_COPY_INSTRUCTION_SIGN => {
+                let distance = u32::from_be_bytes(content.try_into().unwrap()) as usize;
+                if distance == 0 || distance > target_length {
+                    return Err(InstructionError::InvalidContent.into());
+                }
+            }
+            _ => (),
+        }
+        if sign != REMOVE_INSTRUCTION_SIGN {
+            target_length += length;
+        }
+    }
+    if source_length != source.len() {
+        return Err(ApplyError::SourceMismatch);
+    }
+    if target_length > out.len() {
+        return Err(ApplyError::OutputTooSmall(target_length));
+    }
+
+    let (mut source_offset, mut target_offset) = (0usize, 0usize);
+    for instruction in (RawInstructions { bytes: patch }).flatten() {
+        match instruction {
+            (REMOVE_INSTRUCTION_SIGN, length, _) => source_offset += length,
+            (ADD_INSTRUCTION_SIGN, length, content) => {
+                out[target_offset..target_offset + length].copy_from_slice(content);
+                target_offset += length;
+            }
+            (COPY_INSTRUCTION_SIGN, length, content) => {
+                let sources = &source[source_offset..source_offset + length];
+                for ((item, source), delta) in out[target_offset..target_offset + length]
+                    .iter_mut()
+                    .zip(sources)
+                    .zip(content)
+                {
+                    *item = source.wrapping_add(*delta);
+                }
+                source_offset += length;
+                target_offset += length;
+            }
+            (_, length, content) => {
+                let distance = u32::from_be_bytes(content.try_into().unwrap()) as usize;
+                for _ in 0..length {
+                    out[target_offset] = out[target_offset - distance];
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+    Ok(target_offset)
+}
 
 #[cfg(test)]
 mod tests {
     use std::fs;
 
-    use crate::patch::Patch;
+    use crate::{instructions::InstructionError, patch::Patch};
 
     #[test]
     fn speed() {
@@ -35,4 +290,107 @@ mod tests {
         let constructed_patch = Patch::try_from_bytes(&patch_bytes).unwrap();
         assert_eq!(patch, constructed_patch);
     }
+
+    #[test]
+    fn diff_apply() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = crate::diff(&source, &target);
+        assert_eq!(crate::apply(&source, &patch), Ok(target.clone()));
+        assert_eq!(crate::apply_unchecked(&source, &patch), target);
+        assert_eq!(
+            crate::apply(
+                &source,
+                crate::diff_with_hints(&source, &target, &[0..10, 10..target.len()])
+            ),
+            Ok(target.clone())
+        );
+        let mut next = target.clone();
+        next[10] = b'!';
+        for previous in [&patch[..], b"?"] {
+            assert_eq!(
+                crate::apply(&source, crate::diff_with_hint(&source, &next, previous)),
+                Ok(next.clone())
+            );
+        }
+        assert_eq!(
+            crate::apply(&source[1..], &patch),
+            Err(crate::ApplyError::SourceMismatch)
+        );
+        assert_eq!(
+            crate::apply(&source, b"?"),
+            Err(crate::ApplyError::InvalidPatch(
+                InstructionError::InvalidSign
+            ))
+        );
+    }
+
+    #[test]
+    fn apply_no_alloc() {
+        let source = fs::read("files/source.txt").unwrap();
+        let mut target = fs::read("files/target.txt").unwrap();
+        target.extend(b"0123456789".repeat(40));
+        let patch = Patch::new_with_target_copies(&source, &target).to_bytes();
+        let mut out = vec![0xaa; target.len() + 8];
+        assert_eq!(
+            crate::apply_no_alloc(&source, &patch, &mut out),
+            Ok(target.len())
+        );
+        assert_eq!(out[..target.len()], target);
+        assert_eq!(out[target.len()..], [0xaa; 8]);
+
+        assert_eq!(
+            crate::apply_no_alloc(&source, &patch, &mut out[..target.len() - 1]),
+            Err(crate::ApplyError::OutputTooSmall(target.len()))
+        );
+        assert_eq!(
+            crate::apply_no_alloc(&source[1..], &patch, &mut out),
+            Err(crate::ApplyError::SourceMismatch)
+        );
+        for (bytes, error) in [
+            (&b"?"[..], InstructionError::InvalidSign),
+            (b"+", InstructionError::MissingLength),
+            (b"+\x03ab", InstructionError::MissingContent),
+            (
+                b"+\x01a<\x01\x00\x00\x00\x02",
+                InstructionError::InvalidContent,
+            ),
+        ] {
+            assert_eq!(crate::apply(b"", bytes), Err(error.clone().into()));
+            assert_eq!(
+                crate::apply_no_alloc(b"", bytes, &mut out),
+                Err(error.into())
+            );
+        }
+    }
+
+    #[test]
+    fn diff_apply_as_ref() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read_to_string("files/target.txt").unwrap();
+        let patch = crate::diff(source.clone(), &target);
+        assert_eq!(
+            crate::apply(&source, patch.clone()),
+            Ok(target.clone().into_bytes())
+        );
+        assert_eq!(
+            crate::apply_unchecked(source.as_slice(), &patch),
+            target.as_bytes()
+        );
+        assert_eq!(
+            crate::apply([], crate::diff(b"", [1, 2, 3])),
+            Ok(vec![1, 2, 3])
+        );
+        let mut out = vec![0u8; target.len()];
+        assert_eq!(
+            crate::apply_no_alloc(source, patch, &mut out),
+            Ok(target.len())
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn apply_unchecked_panics() {
+        crate::apply_unchecked(b"source", b"?");
+    }
 }
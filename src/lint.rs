@@ -0,0 +1,169 @@
+use crate::{
+    instructions::{delta_instruction::DeltaInstruction, InstructionInfo},
+    patch::Patch,
+};
+
+const FRAMING_OVERHEAD: usize = 2;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum LintWarning {
+    EmptyInstruction {
+        instruction: usize,
+    },
+    ShortCopy {
+        instruction: usize,
+        length: usize,
+    },
+    MergeableInstructions {
+        instruction: usize,
+    },
+    RemoveBeyondSource {
+        instruction: usize,
+        source_offset: usize,
+    },
+}
+
+impl std::fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LintWarning::EmptyInstruction { instruction } => {
+                write!(f, "Instruction {} is empty", instruction)
+            }
+            LintWarning::ShortCopy {
+                instruction,
+                length,
+            } => write!(
+                f,
+                "Copy {} of {} bytes is shorter than its {} bytes of framing overhead",
+                instruction, length, FRAMING_OVERHEAD
+            ),
+            LintWarning::MergeableInstructions { instruction } => write!(
+                f,
+                "Instruction {} could be merged into the previous instruction",
+                instruction
+            ),
+            LintWarning::RemoveBeyondSource {
+                instruction,
+                source_offset,
+            } => write!(
+                f,
+                "Instruction {} reads past the declared source length at offset {}",
+                instruction, source_offset
+            ),
+        }
+    }
+}
+
+fn mergeable(previous: &DeltaInstruction, instruction: &DeltaInstruction) -> bool {
+    if previous.is_full() {
+        return false;
+    }
+    match (previous, instruction) {
+        (DeltaInstruction::Remove(_), DeltaInstruction::Remove(_))
+        | (DeltaInstruction::Add(_), DeltaInstruction::Add(_))
+        | (DeltaInstruction::Copy(_), DeltaInstruction::Copy(_)) => true,
+        (DeltaInstruction::TargetCopy(previous), DeltaInstruction::TargetCopy(instruction)) => {
+            previous.distance() == instruction.distance()
+        }
+        _ => false,
+    }
+}
+
+impl Patch {
+    pub fn lint(&self) -> Vec<LintWarning> {
+        let mut warnings: Vec<LintWarning> = Vec::new();
+        let instructions = self.instructions();
+        for (index, instruction) in instructions.iter().enumerate() {
+            let length = instruction.len() as usize;
+            if instruction.is_empty() {
+                warnings.push(LintWarning::EmptyInstruction { instruction: index });
+                continue;
+            }
+            if matches!(instruction, DeltaInstruction::Copy(_)) && length < FRAMING_OVERHEAD {
+                warnings.push(LintWarning::ShortCopy {
+                    instruction: index,
+                    length,
+                });
+            }
+            if index > 0 && mergeable(&instructions[index - 1], instruction) {
+                warnings.push(LintWarning::MergeableInstructions { instruction: index });
+            }
+        }
+        warnings
+    }
+
+    pub fn lint_for_source(&self, source_length: usize) -> Vec<LintWarning> {
+        let mut warnings = self.lint();
+        let mut source_offset = 0usize;
+        for (index, instruction) in self.instructions().iter().enumerate() {
+            if let DeltaInstruction::Remove(_) | DeltaInstruction::Copy(_) = instruction {
+                source_offset += instruction.len() as usize;
+                if source_offset > source_length {
+                    warnings.push(LintWarning::RemoveBeyondSource {
+                        instruction: index,
+                        source_offset,
+                    });
+                    break;
+                }
+            }
+        }
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod lint_tests {
+    use std::fs;
+
+    use super::*;
+    use crate::instructions::{
+        add_instruction::AddInstruction, copy_instruction::CopyInstruction,
+        remove_instruction::RemoveInstruction, InstructionBytes,
+    };
+
+    fn patch(instructions: Vec<DeltaInstruction>) -> Patch {
+        let bytes: Vec<u8> = instructions
+            .iter()
+            .flat_map(|instruction| instruction.to_bytes())
+            .collect();
+        Patch::try_from_bytes(&bytes).unwrap()
+    }
+
+    #[test]
+    fn lint() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        assert!(Patch::new(&source, &target)
+            .lint()
+            .iter()
+            .all(|warning| matches!(warning, LintWarning::ShortCopy { .. })));
+
+        let patch = patch(vec![
+            AddInstruction::new(vec![1, 2]).into(),
+            AddInstruction::new(vec![3]).into(),
+            CopyInstruction::new(vec![0]).into(),
+            RemoveInstruction::new(0).into(),
+            RemoveInstruction::new(4).into(),
+        ]);
+        assert_eq!(
+            patch.lint(),
+            vec![
+                LintWarning::MergeableInstructions { instruction: 1 },
+                LintWarning::ShortCopy {
+                    instruction: 2,
+                    length: 1
+                },
+                LintWarning::EmptyInstruction { instruction: 3 },
+                LintWarning::MergeableInstructions { instruction: 4 },
+            ]
+        );
+        assert_eq!(patch.lint_for_source(5), patch.lint());
+        assert_eq!(
+            patch.lint_for_source(3).last(),
+            Some(&LintWarning::RemoveBeyondSource {
+                instruction: 4,
+                source_offset: 5
+            })
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use sha2::Sha256;
+
+use crate::{digest::Digest, instructions::InstructionBytes, patch::Patch};
+
+pub const DIGEST_LENGTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest<O = [u8; DIGEST_LENGTH]> {
+    pub source_digest: O,
+    pub target_digest: O,
+    pub patch_digest: O,
+    pub target_size: usize,
+    pub patch_size: usize,
+}
+
+impl Manifest {
+    pub fn encode(source: &[u8], target: &[u8]) -> (Vec<u8>, Self) {
+        Self::encode_with::<Sha256>(source, target)
+    }
+}
+
+impl<O> Manifest<O> {
+    pub fn encode_with<D: Digest<Output = O>>(source: &[u8], target: &[u8]) -> (Vec<u8>, Self) {
+        let patch = Patch::new(source, target);
+        let mut patch_hasher = D::default();
+        let mut bytes: Vec<u8> = Vec::new();
+        for instruction in patch.instructions() {
+            let instruction_bytes = instruction.to_bytes();
+            patch_hasher.update(&instruction_bytes);
+            bytes.extend(instruction_bytes);
+        }
+        let manifest = Self {
+            source_digest: D::digest(source),
+            target_digest: D::digest(target),
+            patch_digest: patch_hasher.finalize(),
+            target_size: target.len(),
+            patch_size: bytes.len(),
+        };
+        (bytes, manifest)
+    }
+}
+
+#[cfg(test)]
+mod manifest_tests {
+    use std::fs;
+
+    use super::*;
+    use crate::digest::digest_tests::Fnv1a;
+
+    #[test]
+    fn encode() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let (bytes, manifest) = Manifest::encode(&source, &target);
+
+        assert_eq!(bytes, Patch::new(&source, &target).to_bytes());
+        assert_eq!(
+            manifest.source_digest,
+            <[u8; 32]>::from(<Sha256 as sha2::Digest>::digest(&source))
+        );
+        assert_eq!(
+            manifest.target_digest,
+            <[u8; 32]>::from(<Sha256 as sha2::Digest>::digest(&target))
+        );
+        assert_eq!(
+            manifest.patch_digest,
+            <[u8; 32]>::from(<Sha256 as sha2::Digest>::digest(&bytes))
+        );
+        assert_eq!(manifest.target_size, target.len());
+        assert_eq!(manifest.patch_size, bytes.len());
+    }
+
+    #[test]
+    fn encode_with() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let (bytes, manifest) = Manifest::encode_with::<Fnv1a>(&source, &target);
+        assert_eq!(manifest.source_digest, Fnv1a::digest(&source));
+        assert_eq!(manifest.target_digest, Fnv1a::digest(&target));
+        assert_eq!(manifest.patch_digest, Fnv1a::digest(&bytes));
+    }
+}
@@ -0,0 +1,285 @@
+use std::{error::Error, ffi::c_void};
+
+use crate::patch::{Patch, PatchBuilder};
+
+pub const MATCHER_ABI_VERSION: u32 = 1;
+
+#[repr(C)]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct MatchSpan {
+    pub source_start: u64,
+    pub target_start: u64,
+    pub length: u64,
+}
+
+pub trait Matcher {
+    fn find_matches(&mut self, source: &[u8], target: &[u8]) -> Vec<MatchSpan>;
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MatcherVTable {
+    pub abi_version: u32,
+    pub state: *mut c_void,
+    pub find_matches: unsafe extern "C" fn(
+        state: *mut c_void,
+        source: *const u8,
+        source_length: usize,
+        target: *const u8,
+        target_length: usize,
+        spans: *mut MatchSpan,
+        spans_capacity: usize,
+    ) -> usize,
+    pub destroy: Option<unsafe extern "C" fn(state: *mut c_void)>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum PluginError {
+    UnsupportedAbi(u32),
+    Load(String),
+}
+
+impl std::fmt::Display for PluginError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PluginError::UnsupportedAbi(version) => write!(
+                f,
+                "Matcher ABI version {} isn't supported, expected {}",
+                version, MATCHER_ABI_VERSION
+            ),
+            PluginError::Load(error) => write!(f, "Couldn't load matcher plugin: {}", error),
+        }
+    }
+}
+
+impl Error for PluginError {}
+
+#[derive(Debug)]
+pub struct CMatcher {
+    vtable: MatcherVTable,
+    #[cfg(feature = "plugin")]
+    _library: Option<libloading::Library>,
+}
+
+impl CMatcher {
+    /// # Safety
+    ///
+    /// `vtable.find_matches` must be safe to call with `vtable.state`, must only read
+    /// `source_length` and `target_length` bytes from the inputs, must write at most
+    /// `spans_capacity` spans and must return the total number of spans it found. If set,
+    /// `vtable.destroy` is called once with `vtable.state` when the matcher is dropped.
+    pub unsafe fn from_vtable(vtable: MatcherVTable) -> Result<Self, PluginError> {
+        if vtable.abi_version != MATCHER_ABI_VERSION {
+            return Err(PluginError::UnsupportedAbi(vtable.abi_version));
+        }
+        Ok(Self {
+            vtable,
+            #[cfg(feature = "plugin")]
+            _library: None,
+        })
+    }
+
+    /// Loads a matcher from a shared library exporting
+    /// `extern "C" fn deltas_matcher_create() -> MatcherVTable`.
+    ///
+    /// # Safety
+    ///
+    /// Loading runs the library's initialisers, and the returned vtable must uphold the
+    /// contract of [`CMatcher::from_vtable`].
+    #[cfg(feature = "plugin")]
+    pub unsafe fn load(path: impl AsRef<std::ffi::OsStr>) -> Result<Self, PluginError> {
+        let library =
+            libloading::Library::new(path).map_err(|error| PluginError::Load(error.to_string()))?;
+        let create: libloading::Symbol<unsafe extern "C" fn() -> MatcherVTable> = library
+            .get(b"deltas_matcher_create")
+            .map_err(|error| PluginError::Load(error.to_string()))?;
+        let mut matcher = Self::from_vtable(create())?;
+        matcher._library = Some(library);
+        Ok(matcher)
+    }
+}
+
+impl Matcher for CMatcher {
+    fn find_matches(&mut self, source: &[u8], target: &[u8]) -> Vec<MatchSpan> {
+        let mut spans: Vec<MatchSpan> = Vec::new();
+        loop {
+            let found = unsafe {
+                (self.vtable.find_matches)(
+                    self.vtable.state,
+                    source.as_ptr(),
+                    source.len(),
+                    target.as_ptr(),
+                    target.len(),
+                    spans.as_mut_ptr(),
+                    spans.capacity(),
+                )
+            };
+            if found <= spans.capacity() {
+                unsafe { spans.set_len(found) };
+                return spans;
+            }
+            spans.reserve_exact(found);
+        }
+    }
+}
+
+impl Drop for CMatcher {
+    fn drop(&mut self) {
+        if let Some(destroy) = self.vtable.destroy {
+            unsafe { destroy(self.vtable.state) };
+        }
+    }
+}
+
+impl Patch {
+    pub fn new_with_matcher<M: Matcher + ?Sized>(
+        source: &[u8],
+        target: &[u8],
+        matcher: &mut M,
+    ) -> Self {
+        let mut builder = PatchBuilder::default();
+        let mut source_offset = 0usize;
+        let mut target_offset = 0usize;
+        for span in matcher.find_matches(source, target) {
+            let (Ok(source_start), Ok(target_start), Ok(length)) = (
+                usize::try_from(span.source_start),
+                usize::try_from(span.target_start),
+                usize::try_from(span.length),
+            ) else {
+                continue;
+            };
+            if length == 0
+                || source_start < source_offset
+                || target_start < target_offset
+                || source_start
+                    .checked_add(length)
+                    .is_none_or(|end| end > source.len())
+                || target_start
+                    .checked_add(length)
+                    .is_none_or(|end| end > target.len())
+            {
+                continue;
+            }
+            builder.add(&target[target_offset..target_start]);
+            builder.remove(source_start - source_offset);
+            builder.copy(
+                &source[source_start..source_start + length],
+                &target[target_start..target_start + length],
+            );
+            source_offset = source_start + length;
+            target_offset = target_start + length;
+        }
+        builder.add(&target[target_offset..]);
+        builder.remove(source.len() - source_offset);
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod matcher_tests {
+    use std::slice;
+
+    use super::*;
+
+    struct PrefixMatcher;
+
+    impl Matcher for PrefixMatcher {
+        fn find_matches(&mut self, source: &[u8], target: &[u8]) -> Vec<MatchSpan> {
+            let length = source
+                .iter()
+                .zip(target)
+                .take_while(|(source, target)| source == target)
+                .count() as u64;
+            vec![
+                MatchSpan {
+                    source_start: 0,
+                    target_start: 0,
+                    length,
+                },
+                MatchSpan {
+                    source_start: 0,
+                    target_start: length,
+                    length: 1,
+                },
+                MatchSpan {
+                    source_start: u64::MAX,
+                    target_start: length,
+                    length: 1,
+                },
+            ]
+        }
+    }
+
+    #[test]
+    fn new_with_matcher() {
+        let source = b"shared prefix, old tail".to_vec();
+        let target = b"shared prefix, new ending".to_vec();
+        let patch = Patch::new_with_matcher(&source, &target, &mut PrefixMatcher);
+        assert_eq!(patch.apply(&source), Some(target));
+        let wrong = Patch::new_with_matcher(b"abcd", b"wxyz", &mut PrefixMatcher);
+        assert_eq!(wrong.apply(b"abcd"), Some(b"wxyz".to_vec()));
+    }
+
+    unsafe extern "C" fn every_block(
+        state: *mut c_void,
+        source: *const u8,
+        source_length: usize,
+        _: *const u8,
+        target_length: usize,
+        spans: *mut MatchSpan,
+        spans_capacity: usize,
+    ) -> usize {
+        *(state as *mut usize) += 1;
+        let _ = slice::from_raw_parts(source, source_length);
+        let count = source_length.min(target_length) / 4;
+        for index in 0..count.min(spans_capacity) {
+            *spans.add(index) = MatchSpan {
+                source_start: index as u64 * 4,
+                target_start: index as u64 * 4,
+                length: 4,
+            };
+        }
+        count
+    }
+
+    unsafe extern "C" fn destroy(state: *mut c_void) {
+        *(state as *mut usize) = usize::MAX;
+    }
+
+    #[test]
+    fn c_matcher() {
+        let mut calls = 0usize;
+        let vtable = MatcherVTable {
+            abi_version: MATCHER_ABI_VERSION,
+            state: &mut calls as *mut usize as *mut c_void,
+            find_matches: every_block,
+            destroy: Some(destroy),
+        };
+        let mut matcher = unsafe { CMatcher::from_vtable(vtable) }.unwrap();
+        let source = b"0123456789abcdef".to_vec();
+        let target = b"0123456789ABCDEF!".to_vec();
+        let patch = Patch::new_with_matcher(&source, &target, &mut matcher);
+        assert_eq!(patch.apply(&source), Some(target));
+        drop(matcher);
+        assert_eq!(calls, usize::MAX);
+
+        let vtable = MatcherVTable {
+            abi_version: 0,
+            destroy: None,
+            ..vtable
+        };
+        assert_eq!(
+            unsafe { CMatcher::from_vtable(vtable) }.unwrap_err(),
+            PluginError::UnsupportedAbi(0)
+        );
+    }
+
+    #[cfg(feature = "plugin")]
+    #[test]
+    fn load_missing() {
+        assert!(matches!(
+            unsafe { CMatcher::load("/nonexistent/libmatcher.so") },
+            Err(PluginError::Load(_))
+        ));
+    }
+}
@@ -0,0 +1,326 @@
+use std::{error::Error, ops::Range};
+
+use crate::{
+    digest::Digest, instructions::InstructionInfo, patch::Patch, progressive::ProgressiveApplier,
+    ApplyError,
+};
+
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum MerkleError {
+    /// The segment at this index didn't hash to the root with its proof.
+    InvalidSegment(usize),
+    /// More segments were fed than the tree has.
+    TooManySegments,
+    /// The patch ended before every segment was fed.
+    MissingSegments,
+    Apply(ApplyError),
+}
+
+impl std::fmt::Display for MerkleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MerkleError::InvalidSegment(index) => {
+                write!(f, "Patch segment {} doesn't match the Merkle root", index)
+            }
+            MerkleError::TooManySegments => write!(f, "Received more segments than expected"),
+            MerkleError::MissingSegments => write!(f, "Patch ended before its last segment"),
+            MerkleError::Apply(error) => write!(f, "Verified segment failed to apply: {}", error),
+        }
+    }
+}
+
+impl Error for MerkleError {}
+
+impl From<ApplyError> for MerkleError {
+    fn from(error: ApplyError) -> Self {
+        MerkleError::Apply(error)
+    }
+}
+
+pub(crate) fn leaf<D: Digest>(segment: &[u8]) -> D::Output {
+    let mut hasher = D::default();
+    hasher.update(&[LEAF_PREFIX]);
+    hasher.update(segment);
+    hasher.finalize()
+}
+
+pub(crate) fn node<D: Digest>(left: &D::Output, right: &D::Output) -> D::Output {
+    let mut hasher = D::default();
+    hasher.update(&[NODE_PREFIX]);
+    hasher.update(left.as_ref());
+    hasher.update(right.as_ref());
+    hasher.finalize()
+}
+
+/// Merkle tree over segments of whole instructions in the patch bytes.
+///
+/// Leaves and inner nodes are hashed with different prefixes, and an unpaired node is carried
+/// up a level unchanged. Only the root and the segment count need to be trusted, e.g. by
+/// signing them in a header; each segment is then checked with its [`MerkleTree::proof`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct MerkleTree<O> {
+    segments: Vec<Range<usize>>,
+    levels: Vec<Vec<O>>,
+}
+
+impl<O: AsRef<[u8]> + Clone> MerkleTree<O> {
+    pub fn new<D: Digest<Output = O>>(patch: &Patch, instructions_per_segment: usize) -> Self {
+        let bytes = patch.to_bytes();
+        let mut segments: Vec<Range<usize>> = Vec::new();
+        let mut start = 0usize;
+        for instructions in patch.instructions().chunks(instructions_per_segment.max(1)) {
+            let end = start
+                + instructions
+                    .iter()
+                    .map(|instruction| instruction.encoded_len())
+                    .sum::<usize>();
+            segments.push(start..end);
+            start = end;
+        }
+        if segments.is_empty() {
+            segments.push(0..0);
+        }
+        let leaves = segments
+            .iter()
+            .map(|range| leaf::<D>(&bytes[range.clone()]))
+            .collect();
+        Self {
+            segments,
+            levels: levels::<D>(leaves),
+        }
+    }
+
+    pub fn root(&self) -> &O {
+        &self.levels.last().unwrap()[0]
+    }
+
+    /// Byte ranges of the segments in [`Patch::to_bytes`].
+    pub fn segments(&self) -> &[Range<usize>] {
+        &self.segments
+    }
+
+    /// Sibling hashes from the leaf of segment `index` up to the root.
+    pub fn proof(&self, index: usize) -> Vec<O> {
+        proof(&self.levels, index)
+    }
+}
+
+/// Every level of the tree over `leaves`, from the leaves up to the root. An unpaired node is
+/// carried up unchanged, which gives the same tree as RFC 6962.
+pub(crate) fn levels<D: Digest>(leaves: Vec<D::Output>) -> Vec<Vec<D::Output>>
+where
+    D::Output: Clone,
+{
+    let mut levels: Vec<Vec<D::Output>> = vec![leaves];
+    while levels.last().unwrap().len() > 1 {
+        let next = levels
+            .last()
+            .unwrap()
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => node::<D>(left, right),
+                [single] => single.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+        levels.push(next);
+    }
+    levels
+}
+
+pub(crate) fn proof<O: Clone>(levels: &[Vec<O>], index: usize) -> Vec<O> {
+    let mut proof: Vec<O> = Vec::new();
+    let mut index = index;
+    for level in &levels[..levels.len() - 1] {
+        if let Some(sibling) = level.get(index ^ 1) {
+            proof.push(sibling.clone());
+        }
+        index /= 2;
+    }
+    proof
+}
+
+/// Hashes `leaf` up with `proof` as leaf `index` of `leaf_count`, returning `None` if the
+/// proof has the wrong number of hashes.
+pub(crate) fn root_from_proof<D: Digest>(
+    leaf: D::Output,
+    leaf_count: usize,
+    index: usize,
+    proof: &[D::Output],
+) -> Option<D::Output> {
+    if index >= leaf_count {
+        return None;
+    }
+    let mut hash = leaf;
+    let mut proof = proof.iter();
+    let (mut index, mut length) = (index, leaf_count);
+    while length > 1 {
+        if index ^ 1 < length {
+            let sibling = proof.next()?;
+            hash = match index % 2 {
+                0 => node::<D>(&hash, sibling),
+                _ => node::<D>(sibling, &hash),
+            };
+        }
+        index /= 2;
+        length = length.div_ceil(2);
+    }
+    proof.next().is_none().then_some(hash)
+}
+
+/// Checks that `segment` is segment `index` of `segment_count` under `root`.
+pub fn verify_segment<D: Digest>(
+    root: &[u8],
+    segment_count: usize,
+    index: usize,
+    segment: &[u8],
+    proof: &[D::Output],
+) -> bool {
+    root_from_proof::<D>(leaf::<D>(segment), segment_count, index, proof)
+        .is_some_and(|hash| hash.as_ref() == root)
+}
+
+/// A [`ProgressiveApplier`] that only applies segments proven against a trusted root, so
+/// segments can be fetched from untrusted mirrors and applied as they arrive.
+#[derive(Debug)]
+pub struct VerifiedApplier<'a, D: Digest> {
+    applier: ProgressiveApplier<'a>,
+    root: D::Output,
+    segment_count: usize,
+    next: usize,
+}
+
+impl<'a, D: Digest> VerifiedApplier<'a, D> {
+    pub fn new(source: &'a [u8], root: D::Output, segment_count: usize) -> Self {
+        Self {
+            applier: ProgressiveApplier::new(source),
+            root,
+            segment_count,
+            next: 0,
+        }
+    }
+
+    /// Verifies the next segment and applies it, returning the target bytes it produced. A
+    /// rejected segment leaves the applier unchanged so it can be fetched again.
+    pub fn feed(&mut self, segment: &[u8], proof: &[D::Output]) -> Result<&[u8], MerkleError> {
+        if self.next >= self.segment_count {
+            return Err(MerkleError::TooManySegments);
+        }
+        if !verify_segment::<D>(
+            self.root.as_ref(),
+            self.segment_count,
+            self.next,
+            segment,
+            proof,
+        ) {
+            return Err(MerkleError::InvalidSegment(self.next));
+        }
+        self.next += 1;
+        Ok(self.applier.feed(segment)?)
+    }
+
+    pub fn finish(self) -> Result<Vec<u8>, MerkleError> {
+        if self.next < self.segment_count {
+            return Err(MerkleError::MissingSegments);
+        }
+        Ok(self.applier.finish()?)
+    }
+}
+
+#[cfg(test)]
+mod merkle_tests {
+    use std::fs;
+
+    use super::*;
+    use crate::digest::digest_tests::Fnv1a;
+
+    fn patch() -> (Vec<u8>, Vec<u8>, Patch) {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+        (source, target, patch)
+    }
+
+    #[test]
+    fn merkle_tree() {
+        let (_, _, patch) = patch();
+        let bytes = patch.to_bytes();
+        for per_segment in [1, 3, 7, 1000] {
+            let tree = MerkleTree::new::<Fnv1a>(&patch, per_segment);
+            let segments = tree.segments();
+            assert_eq!(
+                segments.len(),
+                patch.instructions().len().div_ceil(per_segment)
+            );
+            assert_eq!(segments.last().unwrap().end, bytes.len());
+            for (index, range) in segments.iter().enumerate() {
+                let segment = &bytes[range.clone()];
+                let proof = tree.proof(index);
+                assert!(verify_segment::<Fnv1a>(
+                    tree.root(),
+                    segments.len(),
+                    index,
+                    segment,
+                    &proof
+                ));
+                assert!(!verify_segment::<Fnv1a>(
+                    tree.root(),
+                    segments.len(),
+                    index,
+                    &segment[1..],
+                    &proof
+                ));
+                let other = (index + 1) % segments.len();
+                if bytes[segments[other].clone()] != *segment {
+                    assert!(!verify_segment::<Fnv1a>(
+                        tree.root(),
+                        segments.len(),
+                        other,
+                        segment,
+                        &proof
+                    ));
+                }
+            }
+        }
+
+        let empty = MerkleTree::new::<Fnv1a>(&Patch::default(), 4);
+        assert_eq!(empty.segments().len(), 1);
+        assert!(empty.segments()[0].is_empty());
+        assert!(verify_segment::<Fnv1a>(empty.root(), 1, 0, b"", &[]));
+    }
+
+    #[test]
+    fn verified_applier() {
+        let (source, target, patch) = patch();
+        let bytes = patch.to_bytes();
+        let tree = MerkleTree::new::<Fnv1a>(&patch, 5);
+        let count = tree.segments().len();
+        let mut applier = VerifiedApplier::<Fnv1a>::new(&source, *tree.root(), count);
+        let mut output: Vec<u8> = Vec::new();
+        for (index, range) in tree.segments().iter().enumerate() {
+            let mut tampered = bytes[range.clone()].to_vec();
+            tampered[0] ^= 1;
+            assert_eq!(
+                applier.feed(&tampered, &tree.proof(index)),
+                Err(MerkleError::InvalidSegment(index))
+            );
+            output.extend(
+                applier
+                    .feed(&bytes[range.clone()], &tree.proof(index))
+                    .unwrap(),
+            );
+            assert!(target.starts_with(&output));
+        }
+        assert_eq!(applier.feed(b"", &[]), Err(MerkleError::TooManySegments));
+        assert_eq!(applier.finish(), Ok(target));
+
+        let mut applier = VerifiedApplier::<Fnv1a>::new(&source, *tree.root(), count);
+        applier
+            .feed(&bytes[tree.segments()[0].clone()], &tree.proof(0))
+            .unwrap();
+        assert_eq!(applier.finish(), Err(MerkleError::MissingSegments));
+    }
+}
@@ -0,0 +1,173 @@
+use std::error::Error;
+
+use crate::{
+    checkpoint::crc32,
+    instructions::{
+        delta_instruction::DeltaInstruction, InstructionBytes, InstructionError, InstructionInfo,
+        Result, ADD_INSTRUCTION_SIGN, COPY_INSTRUCTION_SIGN, REMOVE_INSTRUCTION_SIGN,
+        TARGET_COPY_INSTRUCTION_SIGN,
+    },
+    patch::Patch,
+};
+
+/// Binary capacity of the largest QR code at the lowest error correction level.
+pub const MAX_MICRO_LENGTH: usize = 2953;
+const MICRO_HEADER: u8 = 0xd0;
+const CHECKSUM_FLAG: u8 = 0x01;
+const CHECKSUM_LENGTH: usize = 2;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MicroError {
+    TooLarge(usize),
+    DistanceTooLarge(u32),
+}
+
+impl std::fmt::Display for MicroError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MicroError::TooLarge(length) => write!(
+                f,
+                "Micro patch of {} bytes exceeds {} bytes",
+                length, MAX_MICRO_LENGTH
+            ),
+            MicroError::DistanceTooLarge(distance) => write!(
+                f,
+                "Target copy distance {} doesn't fit in one byte",
+                distance
+            ),
+        }
+    }
+}
+
+impl Error for MicroError {}
+
+fn checksum(bytes: &[u8]) -> [u8; CHECKSUM_LENGTH] {
+    let crc = crc32(0, bytes).to_be_bytes();
+    [crc[2], crc[3]]
+}
+
+impl Patch {
+    /// Encodes the patch with a one byte header and one byte target copy distances. With
+    /// `checksum`, the low 16 bits of the CRC-32 of everything before them are appended.
+    pub fn to_micro_bytes(&self, checksum: bool) -> std::result::Result<Vec<u8>, MicroError> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(1 + self.byte_length() + CHECKSUM_LENGTH);
+        bytes.push(MICRO_HEADER | if checksum { CHECKSUM_FLAG } else { 0 });
+        for instruction in self.instructions() {
+            match instruction {
+                DeltaInstruction::TargetCopy(target_copy) => {
+                    let distance = u8::try_from(target_copy.distance())
+                        .map_err(|_| MicroError::DistanceTooLarge(target_copy.distance()))?;
+                    bytes.extend([TARGET_COPY_INSTRUCTION_SIGN, instruction.len(), distance]);
+                }
+                _ => bytes.extend(instruction.to_bytes()),
+            }
+        }
+        if checksum {
+            bytes.extend(self::checksum(&bytes));
+        }
+        match bytes.len() {
+            length if length > MAX_MICRO_LENGTH => Err(MicroError::TooLarge(length)),
+            _ => Ok(bytes),
+        }
+    }
+
+    pub fn try_from_micro_bytes(bytes: &[u8]) -> Result<Self> {
+        let header = *bytes.first().ok_or(InstructionError::MissignSign)?;
+        if header & !CHECKSUM_FLAG != MICRO_HEADER {
+            return Err(InstructionError::InvalidSign);
+        }
+        let mut body = &bytes[1..];
+        if header & CHECKSUM_FLAG != 0 {
+            let split = body
+                .len()
+                .checked_sub(CHECKSUM_LENGTH)
+                .ok_or(InstructionError::MissingContent)?;
+            if checksum(&bytes[..1 + split]) != body[split..] {
+                return Err(InstructionError::InvalidContent);
+            }
+            body = &body[..split];
+        }
+        let mut translated: Vec<u8> = Vec::with_capacity(body.len() + body.len() / 3 * 3);
+        let mut offset = 0usize;
+        while offset < body.len() {
+            let sign = body[offset];
+            let length = *body
+                .get(offset + 1)
+                .ok_or(InstructionError::MissingLength)?;
+            let content_length = match sign {
+                REMOVE_INSTRUCTION_SIGN => 0,
+                ADD_INSTRUCTION_SIGN | COPY_INSTRUCTION_SIGN => length as usize,
+                TARGET_COPY_INSTRUCTION_SIGN => 1,
+                _ => return Err(InstructionError::InvalidSign),
+            };
+            let content = body
+                .get(offset + 2..offset + 2 + content_length)
+                .ok_or(InstructionError::MissingContent)?;
+            translated.extend([sign, length]);
+            if sign == TARGET_COPY_INSTRUCTION_SIGN {
+                translated.extend((content[0] as u32).to_be_bytes());
+            } else {
+                translated.extend_from_slice(content);
+            }
+            offset += 2 + content_length;
+        }
+        Patch::try_from_bytes(&translated)
+    }
+}
+
+#[cfg(test)]
+mod micro_tests {
+    use super::*;
+
+    #[test]
+    fn micro_bytes() {
+        let source = b"ssid=office\npsk=hunter2\nchannel=6\n".to_vec();
+        let target = b"ssid=office\npsk=correct horse\nchannel=11\nchannel=11\n".to_vec();
+        let patch = Patch::new_with_target_copies(&source, &target);
+        for checksum in [false, true] {
+            let bytes = patch.to_micro_bytes(checksum).unwrap();
+            assert!(bytes.len() <= patch.to_bytes().len() + 1 + CHECKSUM_LENGTH);
+            let decoded = Patch::try_from_micro_bytes(&bytes).unwrap();
+            assert_eq!(decoded, patch);
+            assert_eq!(decoded.apply(&source), Some(target.clone()));
+        }
+
+        let repeated = b"0123456789".repeat(4);
+        let patch = Patch::new_with_target_copies(b"", &repeated);
+        assert!(patch.has_target_copies());
+        let bytes = patch.to_micro_bytes(false).unwrap();
+        assert_eq!(bytes.len() + 3, patch.to_bytes().len() + 1);
+        assert_eq!(Patch::try_from_micro_bytes(&bytes), Ok(patch));
+    }
+
+    #[test]
+    fn micro_bytes_err() {
+        let wide: Vec<u8> = (0..=255).chain(0..=255).collect();
+        let patch = Patch::new_with_target_copies(b"", &wide);
+        assert_eq!(
+            patch.to_micro_bytes(false),
+            Err(MicroError::DistanceTooLarge(256))
+        );
+        let large = Patch::new(b"", &[7u8; MAX_MICRO_LENGTH]);
+        assert!(matches!(
+            large.to_micro_bytes(true),
+            Err(MicroError::TooLarge(_))
+        ));
+
+        let mut bytes = Patch::new(b"abc", b"abd").to_micro_bytes(true).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert_eq!(
+            Patch::try_from_micro_bytes(&bytes),
+            Err(InstructionError::InvalidContent)
+        );
+        assert_eq!(
+            Patch::try_from_micro_bytes(b"+"),
+            Err(InstructionError::InvalidSign)
+        );
+        assert_eq!(
+            Patch::try_from_micro_bytes(&[MICRO_HEADER, b'<', 3]),
+            Err(InstructionError::MissingContent)
+        );
+    }
+}
@@ -1,21 +1,272 @@
-use std::{iter::Peekable, slice::Iter};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, VecDeque},
+    io::{self, IoSlice, Write},
+    iter::Peekable,
+    mem,
+    ops::Range,
+    slice::Iter,
+    time::Instant,
+};
 
 use crate::{
+    apply::{ApplyOptions, ChangeTracker, Throttle, YieldPoint},
+    cbor::{self, MAJOR_ARRAY},
+    edit::{self, Edit, EditCollector, EditError, Segment},
+    encode,
+    flash::FlashPlan,
     instructions::{
-        add_instruction::AddInstruction, copy_instruction::CopyInstruction,
+        self, add_instruction::AddInstruction, copy_instruction::CopyInstruction,
         delta_instruction::DeltaInstruction, remove_instruction::RemoveInstruction,
-        InstructionBytes, InstructionContent, InstructionError, InstructionInfo, Result,
+        target_copy_instruction::TargetCopyInstruction, InstructionBytes, InstructionCbor,
+        InstructionContent, InstructionError, InstructionInfo, Result,
     },
     lcs::Lcs,
+    telemetry,
+    version::VersionError,
 };
 
+const MIN_TARGET_COPY: usize = 8;
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub enum DecodePolicy {
+    #[default]
+    Strict,
+    SkipUnknown,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SkippedInstruction {
+    pub instruction: usize,
+    pub sign: u8,
+    pub content: Vec<u8>,
+}
+
 #[derive(Debug, Default, PartialEq, Clone)]
 pub struct Patch {
     instructions: Vec<DeltaInstruction>,
 }
 
+/// Up front reservation for [`TargetHistory`]. Distances can reach `u32::MAX`, so longer
+/// histories grow as the target is produced instead.
+const MAX_HISTORY_RESERVATION: usize = 64 * 1024;
+
+#[derive(Debug, Default)]
+pub(crate) struct TargetHistory {
+    bytes: Vec<u8>,
+    window: usize,
+}
+
+impl TargetHistory {
+    pub(crate) fn new(patch: &Patch) -> Self {
+        let window = patch.max_target_distance();
+        Self {
+            bytes: Vec::with_capacity(Self::limit(window).min(MAX_HISTORY_RESERVATION)),
+            window,
+        }
+    }
+
+    /// History length at which old bytes are dropped.
+    fn limit(window: usize) -> usize {
+        window.max(u8::MAX as usize).saturating_mul(2)
+    }
+
+    pub(crate) fn apply(
+        &mut self,
+        instruction: &DeltaInstruction,
+        source: &mut Iter<'_, u8>,
+    ) -> &[u8] {
+        if self.bytes.len() >= Self::limit(self.window) {
+            self.bytes.drain(..self.bytes.len() - self.window);
+        }
+        let start = self.bytes.len();
+        instruction.apply(source, &mut self.bytes);
+        &self.bytes[start..]
+    }
+}
+
+const MAX_IO_SLICES: usize = 64;
+
+enum OutputPiece<'a> {
+    Source(Range<usize>),
+    Added(&'a [u8]),
+    Computed(Range<usize>),
+}
+
+fn write_pieces<W: Write>(
+    target: &mut W,
+    source: &[u8],
+    computed: &[u8],
+    pieces: &[OutputPiece<'_>],
+) -> io::Result<()> {
+    let mut slices: Vec<IoSlice<'_>> = pieces
+        .iter()
+        .map(|piece| match piece {
+            OutputPiece::Source(range) => IoSlice::new(&source[range.clone()]),
+            OutputPiece::Added(content) => IoSlice::new(content),
+            OutputPiece::Computed(range) => IoSlice::new(&computed[range.clone()]),
+        })
+        .collect();
+    let mut slices = &mut slices[..];
+    while !slices.is_empty() {
+        match target.write_vectored(slices) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(written) => IoSlice::advance_slices(&mut slices, written),
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => (),
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Default, Clone)]
+pub(crate) struct PatchBuilder {
+    instructions: Vec<DeltaInstruction>,
+}
+
+impl PatchBuilder {
+    pub(crate) fn from_patch(patch: Patch) -> Self {
+        Self {
+            instructions: patch.instructions,
+        }
+    }
+
+    fn same_distance(last: &DeltaInstruction, empty: &DeltaInstruction) -> bool {
+        match (last, empty) {
+            (DeltaInstruction::TargetCopy(last), DeltaInstruction::TargetCopy(empty)) => {
+                last.distance() == empty.distance()
+            }
+            _ => true,
+        }
+    }
+
+    fn push(&mut self, empty: DeltaInstruction, item: u8) {
+        match self.instructions.last_mut() {
+            Some(last)
+                if std::mem::discriminant(last) == std::mem::discriminant(&empty)
+                    && !last.is_full()
+                    && Self::same_distance(last, &empty) =>
+            {
+                last.push(item).unwrap()
+            }
+            _ => {
+                let mut instruction = empty;
+                instruction.push(item).unwrap();
+                self.instructions.push(instruction);
+            }
+        }
+    }
+
+    pub(crate) fn remove(&mut self, length: usize) {
+        for _ in 0..length {
+            self.push(RemoveInstruction::default().into(), 0);
+        }
+    }
+
+    pub(crate) fn add(&mut self, content: &[u8]) {
+        for item in content {
+            self.push(AddInstruction::default().into(), *item);
+        }
+    }
+
+    pub(crate) fn copy(&mut self, source: &[u8], target: &[u8]) {
+        for (source, target) in source.iter().zip(target) {
+            self.push(
+                CopyInstruction::default().into(),
+                target.wrapping_sub(*source),
+            );
+        }
+    }
+
+    pub(crate) fn copy_deltas(&mut self, deltas: &[u8]) {
+        for delta in deltas {
+            self.push(CopyInstruction::default().into(), *delta);
+        }
+    }
+
+    pub(crate) fn copy_unchanged(&mut self, length: usize) {
+        for _ in 0..length {
+            self.push(CopyInstruction::default().into(), 0);
+        }
+    }
+
+    pub(crate) fn target_copy(&mut self, distance: usize, length: usize) {
+        for _ in 0..length {
+            self.push(TargetCopyInstruction::new(0, distance as u32).into(), 0);
+        }
+    }
+
+    pub(crate) fn instruction(&mut self, instruction: DeltaInstruction) {
+        self.instructions.push(instruction);
+    }
+
+    pub(crate) fn build(self) -> Patch {
+        Patch {
+            instructions: self.instructions,
+        }
+    }
+}
+
+struct TargetMatcher<'a> {
+    target: &'a [u8],
+    positions: HashMap<&'a [u8], usize>,
+    indexed: usize,
+}
+
+impl<'a> TargetMatcher<'a> {
+    fn new(target: &'a [u8]) -> Self {
+        Self {
+            target,
+            positions: HashMap::new(),
+            indexed: 0,
+        }
+    }
+
+    fn index_until(&mut self, position: usize) {
+        while self.indexed < position {
+            if self.indexed + MIN_TARGET_COPY <= self.target.len() {
+                self.positions.insert(
+                    &self.target[self.indexed..self.indexed + MIN_TARGET_COPY],
+                    self.indexed,
+                );
+            }
+            self.indexed += 1;
+        }
+    }
+
+    fn encode(&mut self, builder: &mut PatchBuilder, start: usize, end: usize) {
+        let mut position = start;
+        while position < end {
+            self.index_until(position);
+            let candidate = match end - position >= MIN_TARGET_COPY {
+                true => self
+                    .positions
+                    .get(&self.target[position..position + MIN_TARGET_COPY])
+                    .copied()
+                    .filter(|candidate| position - candidate <= u32::MAX as usize),
+                false => None,
+            };
+            let Some(candidate) = candidate else {
+                builder.add(&[self.target[position]]);
+                position += 1;
+                continue;
+            };
+            let length = (0..end - position)
+                .take_while(|index| self.target[candidate + index] == self.target[position + index])
+                .count();
+            builder.target_copy(position - candidate, length);
+            position += length;
+        }
+    }
+}
+
 impl Patch {
     pub fn new(source: &[u8], target: &[u8]) -> Self {
+        let algorithm = encode::auto_select(source.len(), target.len(), None);
+        Self::encode_with(source, target, algorithm)
+    }
+
+    pub(crate) fn new_lcs(source: &[u8], target: &[u8]) -> Self {
         let lcs = Lcs::new(source, target).subsequence();
         let mut lcs_iter = lcs.iter().peekable();
         let mut source_iter = source.iter().peekable();
@@ -29,6 +280,227 @@ impl Patch {
         }
     }
 
+    pub fn new_with_target_copies(source: &[u8], target: &[u8]) -> Self {
+        let patch = Self::new(source, target);
+        let mut builder = PatchBuilder::default();
+        let mut matcher = TargetMatcher::new(target);
+        let mut target_offset = 0usize;
+        let mut added_from = 0usize;
+        for instruction in patch.instructions {
+            if let DeltaInstruction::Add(_) = instruction {
+                target_offset += instruction.len() as usize;
+                continue;
+            }
+            matcher.encode(&mut builder, added_from, target_offset);
+            if let DeltaInstruction::Copy(_) = instruction {
+                target_offset += instruction.len() as usize;
+            }
+            added_from = target_offset;
+            builder.instruction(instruction);
+        }
+        matcher.encode(&mut builder, added_from, target_offset);
+        builder.build()
+    }
+
+    pub fn from_edits(
+        source_length: usize,
+        edits: &[Edit],
+    ) -> std::result::Result<Self, EditError> {
+        let mut builder = PatchBuilder::default();
+        let mut source_offset = 0usize;
+        for segment in edit::segments(source_length, edits)? {
+            match segment {
+                Segment::Source { start, end } => {
+                    builder.remove(start - source_offset);
+                    builder.copy_unchanged(end - start);
+                    source_offset = end;
+                }
+                Segment::Inserted(content) => builder.add(&content),
+            }
+        }
+        builder.remove(source_length - source_offset);
+        Ok(builder.build())
+    }
+
+    pub fn to_edits(&self, source: &[u8]) -> Option<Vec<Edit>> {
+        if source.len() != self.source_length() {
+            return None;
+        }
+        let mut collector = EditCollector::default();
+        let mut source_offset = 0usize;
+        let mut history = TargetHistory::new(self);
+        for instruction in self.instructions.iter() {
+            let length = instruction.len() as usize;
+            let output = history.apply(instruction, &mut source[source_offset..].iter());
+            match instruction {
+                DeltaInstruction::Remove(_) => {
+                    collector.delete(length);
+                    source_offset += length;
+                }
+                DeltaInstruction::Add(_) | DeltaInstruction::TargetCopy(_) => {
+                    collector.insert(output)
+                }
+                DeltaInstruction::Copy(_) => {
+                    for (source_item, target_item) in source[source_offset..].iter().zip(output) {
+                        if source_item == target_item {
+                            collector.keep(1);
+                        } else {
+                            collector.delete(1);
+                            collector.insert(&[*target_item]);
+                        }
+                    }
+                    source_offset += length;
+                }
+            }
+        }
+        Some(collector.finish())
+    }
+
+    /// Updates the patch in place so it produces its target with `edit` applied, without
+    /// re-encoding. `edit` is in target offsets.
+    ///
+    /// Source bytes that were copied into the edited range are removed and the new content
+    /// is added, so the patch grows by about the edit size. Fails without changing the patch
+    /// if the edit is out of bounds or a later target copy reads bytes the edit replaces.
+    pub fn rebase_target_edit(&mut self, edit: &Edit) -> std::result::Result<(), EditError> {
+        let (offset, length) = edit.range();
+        let target_length = self.target_length();
+        let end = offset
+            .checked_add(length)
+            .filter(|end| *end <= target_length)
+            .ok_or(EditError::OutOfBounds {
+                edit: 0,
+                offset,
+                length,
+            })?;
+        let content = edit.content();
+        let mut builder = PatchBuilder::default();
+        let mut target_offset = 0usize;
+        let mut inserted = false;
+        for instruction in self.instructions.iter() {
+            for index in 0..instruction.len_usize() {
+                if let DeltaInstruction::Remove(_) = instruction {
+                    builder.remove(1);
+                    continue;
+                }
+                let position = target_offset;
+                target_offset += 1;
+                if position >= offset && position < end {
+                    if let DeltaInstruction::Copy(_) = instruction {
+                        builder.remove(1);
+                    }
+                    continue;
+                }
+                if position >= end && !inserted {
+                    builder.add(content);
+                    inserted = true;
+                }
+                match instruction {
+                    DeltaInstruction::Copy(copy) => {
+                        builder.push(CopyInstruction::default().into(), copy.content()[index])
+                    }
+                    DeltaInstruction::Add(add) => builder.add(&add.content()[index..=index]),
+                    DeltaInstruction::TargetCopy(copy) => {
+                        let distance = copy.distance() as usize;
+                        let distance = match position - distance {
+                            _ if position < offset => distance,
+                            read if read >= end => distance,
+                            read if read < offset
+                                && distance + content.len() - length <= u32::MAX as usize =>
+                            {
+                                distance + content.len() - length
+                            }
+                            _ => return Err(EditError::TargetCopyConflict(position)),
+                        };
+                        builder.target_copy(distance, 1);
+                    }
+                    DeltaInstruction::Remove(_) => unreachable!(),
+                }
+            }
+        }
+        if !inserted {
+            builder.add(content);
+        }
+        self.instructions = builder.build().instructions;
+        Ok(())
+    }
+
+    /// Rewrites the patch from `old_source` to apply to `new_source` instead, producing the
+    /// same target. Returns `None` if `old_source` isn't the length the patch was made for.
+    ///
+    /// Meant for a source that changed in one place, like a re-signed stamp: bytes around
+    /// the change keep their copies, bytes inside it are paired up by position with adjusted
+    /// copy deltas, and leftover old bytes are added while leftover new bytes are removed.
+    pub fn rebase_source(&self, new_source: &[u8], old_source: &[u8]) -> Option<Patch> {
+        if old_source.len() != self.source_length() {
+            return None;
+        }
+        let prefix = old_source
+            .iter()
+            .zip(new_source)
+            .take_while(|(old, new)| old == new)
+            .count();
+        let suffix = old_source
+            .iter()
+            .rev()
+            .zip(new_source.iter().rev())
+            .take_while(|(old, new)| old == new)
+            .count()
+            .min(old_source.len().min(new_source.len()) - prefix);
+        let old_end = old_source.len() - suffix;
+        let paired = prefix + (old_end - prefix).min(new_source.len() - suffix - prefix);
+        let new_offset = |old_offset: usize| match old_offset {
+            _ if old_offset < paired => Some(old_offset),
+            _ if old_offset < old_end => None,
+            _ => Some(old_offset - old_end + new_source.len() - suffix),
+        };
+
+        let mut builder = PatchBuilder::default();
+        let (mut old_offset, mut next_offset) = (0usize, 0usize);
+        for instruction in self.instructions.iter() {
+            for index in 0..instruction.len_usize() {
+                let mapped = match instruction {
+                    DeltaInstruction::Remove(_) | DeltaInstruction::Copy(_) => {
+                        new_offset(old_offset)
+                    }
+                    _ => None,
+                };
+                if let Some(mapped) = mapped {
+                    builder.remove(mapped - next_offset);
+                    next_offset = mapped + 1;
+                }
+                match (instruction, mapped) {
+                    (DeltaInstruction::Remove(_), Some(_)) => builder.remove(1),
+                    (DeltaInstruction::Copy(copy), mapped) => {
+                        let target = old_source[old_offset].wrapping_add(copy.content()[index]);
+                        match mapped {
+                            Some(mapped) => builder.copy(&new_source[mapped..=mapped], &[target]),
+                            None => builder.add(&[target]),
+                        }
+                    }
+                    (DeltaInstruction::Add(add), _) => builder.add(&add.content()[index..=index]),
+                    (DeltaInstruction::TargetCopy(copy), _) => {
+                        builder.target_copy(copy.distance() as usize, 1)
+                    }
+                    _ => (),
+                }
+                if let DeltaInstruction::Remove(_) | DeltaInstruction::Copy(_) = instruction {
+                    old_offset += 1;
+                }
+            }
+        }
+        builder.remove(new_source.len() - next_offset);
+        Some(builder.build())
+    }
+
+    pub fn instructions(&self) -> &[DeltaInstruction] {
+        &self.instructions
+    }
+
+    pub(crate) fn instructions_mut(&mut self) -> &mut [DeltaInstruction] {
+        &mut self.instructions
+    }
+
     fn create_instructions(
         lcs: &mut Peekable<Iter<'_, u8>>,
         source: &mut Peekable<Iter<'_, u8>>,
@@ -70,33 +542,246 @@ impl Patch {
     }
 
     fn construct_target(&self, source: &mut Iter<'_, u8>) -> Option<Vec<u8>> {
-        if source.len() != self.source_lenth() {
+        if source.len() != self.source_length() {
             return None;
         }
+        let start = Instant::now();
         let mut target: Vec<u8> = Vec::with_capacity(self.target_length());
         for instruction in self.instructions.iter() {
             instruction.apply(source, &mut target)
         }
+        telemetry::record_apply(target.len(), start.elapsed());
         Some(target)
     }
 
-    fn target_length(&self) -> usize {
+    pub fn apply_cow<'a>(&self, source: &'a [u8]) -> Option<Cow<'a, [u8]>> {
+        if source.len() != self.source_length() {
+            return None;
+        }
+        let unchanged = |instruction: &&DeltaInstruction| match instruction {
+            DeltaInstruction::Copy(copy) => copy.non_default_item_count() == Some(0),
+            instruction => instruction.is_empty(),
+        };
+        let prefix = self.instructions.iter().take_while(unchanged).count();
+        let prefix_length: usize = self.instructions[..prefix]
+            .iter()
+            .filter(|instruction| matches!(instruction, DeltaInstruction::Copy(_)))
+            .map(|instruction| instruction.len() as usize)
+            .sum();
+        let rest = &self.instructions[prefix..];
+        if rest
+            .iter()
+            .all(|instruction| matches!(instruction, DeltaInstruction::Remove(_)))
+        {
+            return Some(Cow::Borrowed(&source[..prefix_length]));
+        }
+        let start = Instant::now();
+        let mut target: Vec<u8> = Vec::with_capacity(self.target_length());
+        target.extend_from_slice(&source[..prefix_length]);
+        let mut source = source[prefix_length..].iter();
+        for instruction in rest {
+            instruction.apply(&mut source, &mut target)
+        }
+        telemetry::record_apply(target.len(), start.elapsed());
+        Some(Cow::Owned(target))
+    }
+
+    pub fn apply_with<W: Write>(
+        &self,
+        source: &[u8],
+        target: &mut W,
+        options: &mut ApplyOptions,
+    ) -> io::Result<()> {
+        if options.enforce_versions.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                VersionError::Unbound,
+            ));
+        }
+        let mut source_iter = source.iter();
+        if source_iter.len() != self.source_length() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Source length doesn't match the patch source length",
+            ));
+        }
+        let start = Instant::now();
+        let mut throttle = Throttle::new(options.max_bytes_per_sec);
+        let mut history = TargetHistory::new(self);
+        let mut changes = ChangeTracker::default();
+        let mut target_offset = 0usize;
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            let buffer = history.apply(instruction, &mut source_iter);
+            target.write_all(buffer)?;
+            throttle.consume(buffer.len());
+            match instruction {
+                DeltaInstruction::Remove(_) => (),
+                DeltaInstruction::Copy(copy) => {
+                    for (position, delta) in copy.content().iter().enumerate() {
+                        if *delta != 0 {
+                            let position = target_offset + position;
+                            changes.changed(options, position..position + 1);
+                        }
+                    }
+                }
+                _ => changes.changed(options, target_offset..target_offset + buffer.len()),
+            }
+            target_offset += buffer.len();
+            options.yield_point(YieldPoint {
+                instruction: index + 1,
+                instruction_count: self.instructions.len(),
+                target_offset,
+            });
+        }
+        target.flush()?;
+        changes.finish(options);
+        telemetry::record_apply(target_offset, start.elapsed());
+        Ok(())
+    }
+
+    /// Checks whether `self` and `other` turn `source` into the same target. Both patches are
+    /// applied side by side and compared as they go, so only their target copy histories and
+    /// the few bytes one is ahead of the other are held in memory.
+    pub fn equivalent_to(&self, other: &Patch, source: &[u8]) -> bool {
+        if self.target_length() != other.target_length()
+            || self.source_length() != source.len()
+            || other.source_length() != source.len()
+        {
+            return false;
+        }
+        let mut sides = [self, other].map(|patch| {
+            (
+                patch.instructions.iter(),
+                source.iter(),
+                TargetHistory::new(patch),
+            )
+        });
+        // Output of `sides[ahead]` the other side hasn't produced yet.
+        let mut pending: VecDeque<u8> = VecDeque::new();
+        let mut ahead = 0;
+        loop {
+            let behind = if pending.is_empty() { 0 } else { 1 - ahead };
+            let (instructions, source, history) = &mut sides[behind];
+            let Some(instruction) = instructions.next() else {
+                return pending.is_empty();
+            };
+            for &byte in history.apply(instruction, source) {
+                if pending.is_empty() || ahead == behind {
+                    ahead = behind;
+                    pending.push_back(byte);
+                } else if pending.pop_front() != Some(byte) {
+                    return false;
+                }
+            }
+        }
+    }
+
+    pub fn apply_vectored<W: Write>(&self, source: &[u8], target: &mut W) -> io::Result<()> {
+        if self.has_target_copies() {
+            return self.apply_with(source, target, &mut ApplyOptions::default());
+        }
+        if source.len() != self.source_length() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Source length doesn't match the patch source length",
+            ));
+        }
+        let start = Instant::now();
+        let mut pieces: Vec<OutputPiece<'_>> = Vec::with_capacity(MAX_IO_SLICES);
+        let mut computed: Vec<u8> = Vec::new();
+        let mut source_offset = 0usize;
+        let mut target_offset = 0usize;
+        for instruction in self.instructions.iter() {
+            let length = instruction.len() as usize;
+            match instruction {
+                DeltaInstruction::Remove(_) => source_offset += length,
+                DeltaInstruction::Add(add) => pieces.push(OutputPiece::Added(add.content())),
+                DeltaInstruction::Copy(copy) if copy.non_default_item_count() == Some(0) => {
+                    let range = source_offset..source_offset + length;
+                    match pieces.last_mut() {
+                        Some(OutputPiece::Source(last)) if last.end == range.start => {
+                            last.end = range.end
+                        }
+                        _ => pieces.push(OutputPiece::Source(range)),
+                    }
+                    source_offset += length;
+                }
+                _ => {
+                    let computed_start = computed.len();
+                    let mut source_iter = source[source_offset..].iter();
+                    instruction.apply(&mut source_iter, &mut computed);
+                    pieces.push(OutputPiece::Computed(computed_start..computed.len()));
+                    source_offset += length;
+                }
+            }
+            if matches!(
+                instruction,
+                DeltaInstruction::Add(_) | DeltaInstruction::Copy(_)
+            ) {
+                target_offset += length;
+            }
+            if pieces.len() == MAX_IO_SLICES {
+                write_pieces(target, source, &computed, &pieces)?;
+                pieces.clear();
+                computed.clear();
+            }
+        }
+        write_pieces(target, source, &computed, &pieces)?;
+        target.flush()?;
+        telemetry::record_apply(target_offset, start.elapsed());
+        Ok(())
+    }
+
+    pub fn apply_aligned<F>(
+        &self,
+        source: &[u8],
+        block_size: usize,
+        mut write_block: F,
+    ) -> Option<()>
+    where
+        F: FnMut(usize, &[u8]),
+    {
+        assert!(block_size > 0, "Block size must be greater than zero");
+        let mut source_iter = source.iter();
+        if source_iter.len() != self.source_length() {
+            return None;
+        }
+        let mut buffer: Vec<u8> = Vec::with_capacity(block_size + u8::MAX as usize);
+        let mut history = TargetHistory::new(self);
+        let mut block = 0usize;
+        for instruction in self.instructions.iter() {
+            buffer.extend(history.apply(instruction, &mut source_iter));
+            while buffer.len() >= block_size {
+                write_block(block, &buffer[..block_size]);
+                buffer.drain(..block_size);
+                block += 1;
+            }
+        }
+        if !buffer.is_empty() {
+            write_block(block, &buffer);
+        }
+        Some(())
+    }
+
+    pub fn plan_flash(&self, block_size: usize) -> FlashPlan {
+        FlashPlan::new(self, block_size)
+    }
+
+    pub fn target_length(&self) -> usize {
         self.instructions
             .iter()
             .fold(0usize, |mut acc, instruction| {
                 match instruction {
                     DeltaInstruction::Remove(_) => (),
                     DeltaInstruction::Add(_) => acc += instruction.len() as usize,
-                    DeltaInstruction::Copy(_) => {
-                        acc += instruction.len() as usize
-                            - instruction.non_default_item_count().unwrap() as usize
-                    }
+                    DeltaInstruction::Copy(_) => acc += instruction.len() as usize,
+                    DeltaInstruction::TargetCopy(_) => acc += instruction.len() as usize,
                 };
                 acc
             })
     }
 
-    fn source_lenth(&self) -> usize {
+    pub fn source_length(&self) -> usize {
         self.instructions
             .iter()
             .fold(0usize, |mut acc, instruction| {
@@ -104,15 +789,59 @@ impl Patch {
                     DeltaInstruction::Remove(_) => acc += instruction.len() as usize,
                     DeltaInstruction::Add(_) => (),
                     DeltaInstruction::Copy(_) => acc += instruction.len() as usize,
+                    DeltaInstruction::TargetCopy(_) => (),
                 };
                 acc
             })
     }
 
-    fn byte_length(&self) -> usize {
+    pub fn has_target_copies(&self) -> bool {
+        self.instructions
+            .iter()
+            .any(|instruction| matches!(instruction, DeltaInstruction::TargetCopy(_)))
+    }
+
+    pub fn max_target_distance(&self) -> usize {
+        self.instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                DeltaInstruction::TargetCopy(target_copy) => Some(target_copy.distance() as usize),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub(crate) fn validate_target_copies(instructions: &[DeltaInstruction]) -> Result<()> {
+        let mut target_offset = 0usize;
+        for instruction in instructions {
+            match instruction {
+                DeltaInstruction::Remove(_) => continue,
+                DeltaInstruction::TargetCopy(target_copy)
+                    if target_copy.distance() as usize > target_offset =>
+                {
+                    return Err(InstructionError::InvalidContent)
+                }
+                _ => target_offset += instruction.len() as usize,
+            }
+        }
+        Ok(())
+    }
+
+    /// Bytes allocated by the patch, not counting the `Patch` itself.
+    pub fn heap_size(&self) -> usize {
+        self.instructions.capacity() * mem::size_of::<DeltaInstruction>()
+            + self
+                .instructions
+                .iter()
+                .map(DeltaInstruction::heap_size)
+                .sum::<usize>()
+    }
+
+    pub(crate) fn byte_length(&self) -> usize {
         self.instructions
             .iter()
-            .map(|instruction| instruction.byte_length())
+            .map(|instruction| instruction.encoded_len())
             .sum::<usize>()
     }
 
@@ -125,11 +854,60 @@ impl Patch {
     }
 
     pub fn try_from_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::try_from_bytes_with(bytes, DecodePolicy::Strict).map(|(patch, _)| patch)
+    }
+
+    pub fn try_from_bytes_with(
+        bytes: &[u8],
+        policy: DecodePolicy,
+    ) -> Result<(Self, Vec<SkippedInstruction>)> {
+        let mut bytes_iter = bytes.iter().peekable();
+        let mut instructions: Vec<DeltaInstruction> = Vec::new();
+        let mut skipped: Vec<SkippedInstruction> = Vec::new();
+        while let Some(&&sign) = bytes_iter.peek() {
+            if policy == DecodePolicy::Strict || instructions::is_known_sign(sign) {
+                instructions.push(DeltaInstruction::try_from_bytes(&mut bytes_iter)?);
+                continue;
+            }
+            bytes_iter.next();
+            let length = *bytes_iter.next().ok_or(InstructionError::MissingLength)? as usize;
+            let content: Vec<u8> = bytes_iter.by_ref().take(length).copied().collect();
+            if content.len() != length {
+                return Err(InstructionError::MissingContent);
+            }
+            skipped.push(SkippedInstruction {
+                instruction: instructions.len() + skipped.len(),
+                sign,
+                content,
+            });
+        }
+        Self::validate_target_copies(&instructions)?;
+        Ok((Self { instructions }, skipped))
+    }
+
+    pub fn to_cbor(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(self.byte_length() + 9);
+        cbor::write_head(&mut bytes, MAJOR_ARRAY, self.instructions.len() as u64);
+        for instruction in self.instructions.iter() {
+            bytes.extend(instruction.to_cbor());
+        }
+        bytes
+    }
+
+    pub fn try_from_cbor(bytes: &[u8]) -> Result<Self> {
         let mut bytes_iter = bytes.iter().peekable();
+        let count = match cbor::read_head(&mut bytes_iter)? {
+            (MAJOR_ARRAY, count) => count,
+            _ => return Err(InstructionError::InvalidContent),
+        };
         let mut instructions: Vec<DeltaInstruction> = Vec::new();
-        while bytes_iter.peek().is_some() {
-            instructions.push(DeltaInstruction::try_from_bytes(&mut bytes_iter)?);
+        for _ in 0..count {
+            instructions.push(DeltaInstruction::try_from_cbor(&mut bytes_iter)?);
+        }
+        if bytes_iter.peek().is_some() {
+            return Err(InstructionError::InvalidContent);
         }
+        Self::validate_target_copies(&instructions)?;
         Ok(Self { instructions })
     }
 }
@@ -188,14 +966,15 @@ mod remove_instruction_tests {
         );
         assert_eq!(Patch::new(b"AAAAAAAA", b"").target_length(), 0);
         assert_eq!(Patch::new(b"", b"AAA").target_length(), 3);
+        assert_eq!(Patch::new(b"ABC", b"AYZ").target_length(), 3);
     }
 
     #[test]
     fn source_length() {
-        assert_eq!(Patch::new(b"AAA", b"AAA").source_lenth(), 3);
-        assert_eq!(Patch::new(b"", b"AAA").source_lenth(), 0);
-        assert_eq!(Patch::new(b"AAA", b"").source_lenth(), 3);
-        assert_eq!(Patch::new(b"AAA", b"BAABBCCCAAA").source_lenth(), 3);
+        assert_eq!(Patch::new(b"AAA", b"AAA").source_length(), 3);
+        assert_eq!(Patch::new(b"", b"AAA").source_length(), 0);
+        assert_eq!(Patch::new(b"AAA", b"").source_length(), 3);
+        assert_eq!(Patch::new(b"AAA", b"BAABBCCCAAA").source_length(), 3);
     }
 
     #[test]
@@ -227,10 +1006,55 @@ mod remove_instruction_tests {
             b"Text files are easy to compare, unlike binary files.".to_vec(),
         ];
         for (source, target) in source_phrases.iter().zip(target_phrases.iter()) {
-            assert_eq!(&Patch::new(&source, &target).apply(source).unwrap(), target);
+            assert_eq!(&Patch::new(source, target).apply(source).unwrap(), target);
         }
     }
 
+    #[test]
+    fn heap_size() {
+        assert_eq!(Patch::default().heap_size(), 0);
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+        let content: usize = patch
+            .instructions()
+            .iter()
+            .filter_map(DeltaInstruction::content)
+            .map(<[u8]>::len)
+            .sum();
+        let minimum = mem::size_of_val(patch.instructions()) + content;
+        assert!(patch.heap_size() >= minimum);
+        assert!(patch.heap_size() < minimum * 2);
+    }
+
+    #[test]
+    fn apply_cow() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+        assert_eq!(
+            patch.apply_cow(&source),
+            Some(Cow::Owned::<[u8]>(target.clone()))
+        );
+        assert_eq!(patch.apply_cow(&source[1..]), None);
+
+        assert!(matches!(
+            Patch::new(&source, &source).apply_cow(&source),
+            Some(Cow::Borrowed(borrowed)) if borrowed == source.as_slice()
+        ));
+        assert!(matches!(
+            Patch::new(&source, &source[..100]).apply_cow(&source),
+            Some(Cow::Borrowed(borrowed)) if borrowed == &source[..100]
+        ));
+
+        let mut appended = source.clone();
+        appended.extend(b"appended");
+        assert!(matches!(
+            Patch::new(&source, &appended).apply_cow(&source),
+            Some(Cow::Owned(owned)) if owned == appended
+        ));
+    }
+
     #[test]
     fn try_from_bytes() {
         let source = fs::read("files/source.txt").unwrap();
@@ -240,4 +1064,524 @@ mod remove_instruction_tests {
         let constructed_patch = Patch::try_from_bytes(&patch_bytes).unwrap();
         assert_eq!(patch, constructed_patch);
     }
+
+    #[test]
+    fn cbor() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+        let constructed_patch = Patch::try_from_cbor(&patch.to_cbor()).unwrap();
+        assert_eq!(patch, constructed_patch);
+        assert_eq!(
+            Patch::try_from_bytes(&constructed_patch.to_bytes()).unwrap(),
+            patch
+        );
+
+        assert_eq!(
+            Patch::try_from_cbor(&[]),
+            Err(InstructionError::MissingContent)
+        );
+        assert_eq!(
+            Patch::try_from_cbor(&patch.to_bytes()),
+            Err(InstructionError::InvalidContent)
+        );
+        let mut bytes = Patch::default().to_cbor();
+        bytes.push(0);
+        assert_eq!(
+            Patch::try_from_cbor(&bytes),
+            Err(InstructionError::InvalidContent)
+        );
+    }
+
+    #[test]
+    fn apply_vectored() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+        let mut output: Vec<u8> = Vec::new();
+        patch.apply_vectored(&source, &mut output).unwrap();
+        assert_eq!(output, target);
+
+        struct ShortWriter(Vec<u8>);
+        impl Write for ShortWriter {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                let length = buf.len().min(7);
+                self.0.extend(&buf[..length]);
+                Ok(length)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        let mut short = ShortWriter(Vec::new());
+        patch.apply_vectored(&source, &mut short).unwrap();
+        assert_eq!(short.0, target);
+
+        let (source, target) = repetitive();
+        output.clear();
+        Patch::new_with_target_copies(&source, &target)
+            .apply_vectored(&source, &mut output)
+            .unwrap();
+        assert_eq!(output, target);
+
+        assert_eq!(
+            patch.apply_vectored(b"", &mut output).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn apply_aligned() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+        let mut blocks: Vec<(usize, Vec<u8>)> = Vec::new();
+        patch
+            .apply_aligned(&source, 64, |block, bytes| {
+                blocks.push((block, bytes.to_vec()))
+            })
+            .unwrap();
+        assert_eq!(blocks.len(), target.len().div_ceil(64));
+        for (index, (block, bytes)) in blocks.iter().enumerate() {
+            assert_eq!(*block, index);
+            assert_eq!(
+                bytes,
+                &target[index * 64..target.len().min((index + 1) * 64)]
+            );
+        }
+        assert_eq!(patch.apply_aligned(b"", 64, |_, _| ()), None);
+    }
+
+    #[test]
+    fn apply_with() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+
+        let mut output: Vec<u8> = Vec::new();
+        patch
+            .apply_with(&source, &mut output, &mut ApplyOptions::default())
+            .unwrap();
+        assert_eq!(output, target);
+
+        let mut options = ApplyOptions {
+            max_bytes_per_sec: Some(target.len() as u64 * 10),
+            ..Default::default()
+        };
+        output.clear();
+        patch
+            .apply_with(&source, &mut output, &mut options)
+            .unwrap();
+        assert_eq!(output, target);
+
+        assert_eq!(
+            patch
+                .apply_with(b"", &mut output, &mut ApplyOptions::default())
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn apply_with_changed() {
+        let source: Vec<u8> = (0..=255).cycle().take(1024).collect();
+        let mut target = source.clone();
+        target[10..20].fill(0xaa);
+        target[500] ^= 1;
+        target.extend(b"appended");
+        let patch = Patch::new_with_hints(&source, &target, &[10..20, 500..501]);
+        let mut ranges: Vec<std::ops::Range<usize>> = Vec::new();
+        let mut options = ApplyOptions {
+            on_changed: Some(Box::new(|range| ranges.push(range))),
+            ..Default::default()
+        };
+        let mut output: Vec<u8> = Vec::new();
+        patch
+            .apply_with(&source, &mut output, &mut options)
+            .unwrap();
+        drop(options);
+        assert_eq!(output, target);
+        assert_eq!(ranges, vec![10..20, 500..501, 1024..1032]);
+    }
+
+    #[test]
+    fn equivalent_to() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+        let block_hash = Patch::encode_with(&source, &target, encode::EncodeAlgorithm::BlockHash);
+        assert_ne!(patch, block_hash);
+        assert!(patch.equivalent_to(&block_hash, &source));
+        assert!(!patch.equivalent_to(&Patch::new(&source, &source), &source));
+        assert!(!patch.equivalent_to(&block_hash, &source[1..]));
+
+        let mut altered = target.clone();
+        altered[0] ^= 1;
+        assert!(!patch.equivalent_to(&Patch::new(&source, &altered), &source));
+        let mut altered = target.clone();
+        *altered.last_mut().unwrap() ^= 1;
+        assert!(!Patch::new(&source, &altered).equivalent_to(&block_hash, &source));
+
+        let mut target = b"0123456789".repeat(30);
+        target.extend(vec![b'='; 100]);
+        let target_copies = Patch::new_with_target_copies(&source, &target);
+        assert!(target_copies.has_target_copies());
+        assert!(target_copies.equivalent_to(&Patch::new(&source, &target), &source));
+        assert!(Patch::new(&source, &target).equivalent_to(&target_copies, &source));
+        *target.last_mut().unwrap() = b'-';
+        assert!(!target_copies.equivalent_to(&Patch::new(&source, &target), &source));
+    }
+
+    #[test]
+    fn apply_with_yield() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+
+        let mut points: Vec<YieldPoint> = Vec::new();
+        let mut options = ApplyOptions {
+            yield_every: Some(1),
+            on_yield: Some(Box::new(|point| points.push(point))),
+            ..Default::default()
+        };
+        patch
+            .apply_with(&source, &mut Vec::new(), &mut options)
+            .unwrap();
+        drop(options);
+        assert_eq!(points.len(), patch.instructions().len());
+        assert_eq!(points.last().unwrap().target_offset, target.len());
+    }
+
+    #[test]
+    fn from_edits() {
+        let source = b"The quick brown fox jumps over the lazy dog.";
+        let edits = vec![
+            Edit::Replace {
+                offset: 4,
+                length: 5,
+                content: b"slow".to_vec(),
+            },
+            Edit::Delete {
+                offset: 9,
+                length: 6,
+            },
+            Edit::Insert {
+                offset: 37,
+                content: vec![b'!'; 300],
+            },
+        ];
+        let patch = Patch::from_edits(source.len(), &edits).unwrap();
+        let mut expected = b"The slow fox jumps over the lazy dog.".to_vec();
+        expected.extend(vec![b'!'; 300]);
+        assert_eq!(patch.apply(source).unwrap(), expected);
+        assert!(patch
+            .instructions()
+            .iter()
+            .all(|instruction| !instruction.is_empty()));
+
+        assert_eq!(
+            Patch::from_edits(3, &[]).unwrap().apply(b"ABC").unwrap(),
+            b"ABC"
+        );
+        assert_eq!(
+            Patch::from_edits(
+                3,
+                &[Edit::Delete {
+                    offset: 0,
+                    length: 3
+                }]
+            )
+            .unwrap()
+            .apply(b"ABC")
+            .unwrap(),
+            b""
+        );
+        assert!(Patch::from_edits(
+            3,
+            &[Edit::Delete {
+                offset: 0,
+                length: 4
+            }]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn to_edits() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+        let edits = patch.to_edits(&source).unwrap();
+
+        let mut document = source.clone();
+        for edit in edits.iter() {
+            match edit {
+                Edit::Insert { offset, content } => {
+                    document.splice(*offset..*offset, content.iter().copied());
+                }
+                Edit::Delete { offset, length } => {
+                    document.drain(*offset..offset + length);
+                }
+                Edit::Replace {
+                    offset,
+                    length,
+                    content,
+                } => {
+                    document.splice(*offset..offset + length, content.iter().copied());
+                }
+            }
+        }
+        assert_eq!(document, target);
+
+        let rebuilt = Patch::from_edits(source.len(), &edits).unwrap();
+        assert_eq!(rebuilt.apply(&source).unwrap(), target);
+
+        assert_eq!(Patch::new(b"AAA", b"AAA").to_edits(b"AAA").unwrap(), vec![]);
+        assert!(patch.to_edits(b"").is_none());
+    }
+
+    #[test]
+    fn rebase_target_edit() {
+        let source = fs::read("files/source.txt").unwrap();
+        let mut document = fs::read("files/target.txt").unwrap();
+        let mut patch = Patch::new(&source, &document);
+        let edits = [
+            Edit::Insert {
+                offset: 0,
+                content: b"autosave ".to_vec(),
+            },
+            Edit::Replace {
+                offset: 500,
+                length: 20,
+                content: b"typed".to_vec(),
+            },
+            Edit::Delete {
+                offset: 100,
+                length: 300,
+            },
+            Edit::Insert {
+                offset: document.len() - 300 + 9 - 15,
+                content: vec![b'!'; 600],
+            },
+        ];
+        for edit in edits.iter() {
+            patch.rebase_target_edit(edit).unwrap();
+            let (offset, length) = edit.range();
+            document.splice(offset..offset + length, edit.content().iter().copied());
+            assert_eq!(patch.apply(&source).unwrap(), document);
+        }
+        let before = patch.clone();
+        assert_eq!(
+            patch.rebase_target_edit(&Edit::Delete {
+                offset: document.len(),
+                length: 1
+            }),
+            Err(EditError::OutOfBounds {
+                edit: 0,
+                offset: document.len(),
+                length: 1
+            })
+        );
+        assert_eq!(patch, before);
+
+        let (source, mut target) = repetitive();
+        let mut patch = Patch::new_with_target_copies(&source, &target);
+        let edit = Edit::Replace {
+            offset: 417,
+            length: 1,
+            content: b"YY".to_vec(),
+        };
+        patch.rebase_target_edit(&edit).unwrap();
+        target.splice(417..418, *b"YY");
+        assert_eq!(patch.apply(&source).unwrap(), target);
+        assert!(patch.has_target_copies());
+        let before = patch.clone();
+        assert!(matches!(
+            patch.rebase_target_edit(&Edit::Delete {
+                offset: 20,
+                length: 2
+            }),
+            Err(EditError::TargetCopyConflict(_))
+        ));
+        assert_eq!(patch, before);
+    }
+
+    #[test]
+    fn rebase_source() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let stamp = |stamp: &[u8]| {
+            let mut stamped = source.clone();
+            stamped.splice(40..48, stamp.iter().copied());
+            stamped
+        };
+        let old_source = stamp(b"signed-1");
+        for patch in [
+            Patch::new(&old_source, &target),
+            Patch::new_with_target_copies(&old_source, &target),
+        ] {
+            for new_source in [
+                stamp(b"SIGNED-2"),
+                stamp(b"signed-two"),
+                stamp(b"s2"),
+                old_source.clone(),
+                old_source[..100].to_vec(),
+                Vec::new(),
+            ] {
+                let rebased = patch.rebase_source(&new_source, &old_source).unwrap();
+                assert_eq!(rebased.apply(&new_source), Some(target.clone()));
+            }
+            let rebased = patch
+                .rebase_source(&stamp(b"SIGNED-2"), &old_source)
+                .unwrap();
+            assert_eq!(rebased.byte_length(), patch.byte_length());
+            assert_eq!(patch.rebase_source(&old_source, &source[1..]), None);
+        }
+    }
+
+    fn repetitive() -> (Vec<u8>, Vec<u8>) {
+        let source = b"header: unchanged".to_vec();
+        let mut target = b"header: changed ".to_vec();
+        target.extend(b"abcdefghij".repeat(40));
+        target.extend(b" Z ");
+        target.extend(vec![b'-'; 300]);
+        target.extend(b"header: changed");
+        (source, target)
+    }
+
+    #[test]
+    fn new_with_target_copies() {
+        let (source, target) = repetitive();
+        let patch = Patch::new_with_target_copies(&source, &target);
+        assert!(patch.has_target_copies());
+        assert!(!Patch::new(&source, &target).has_target_copies());
+        assert!(patch.to_bytes().len() < Patch::new(&source, &target).to_bytes().len() / 4);
+        assert_eq!(patch.target_length(), target.len());
+        assert_eq!(patch.source_length(), source.len());
+        assert_eq!(patch.apply(&source).unwrap(), target);
+        assert_eq!(Patch::try_from_bytes(&patch.to_bytes()).unwrap(), patch);
+        assert_eq!(Patch::try_from_cbor(&patch.to_cbor()).unwrap(), patch);
+
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new_with_target_copies(&source, &target);
+        assert_eq!(patch.apply(&source).unwrap(), target);
+    }
+
+    #[test]
+    fn target_copies_streaming() {
+        let (source, target) = repetitive();
+        let patch = Patch::new_with_target_copies(&source, &target);
+
+        let mut written: Vec<u8> = Vec::new();
+        patch
+            .apply_with(&source, &mut written, &mut ApplyOptions::default())
+            .unwrap();
+        assert_eq!(written, target);
+
+        let mut blocks: Vec<u8> = Vec::new();
+        patch
+            .apply_aligned(&source, 64, |_, block| blocks.extend(block))
+            .unwrap();
+        assert_eq!(blocks, target);
+
+        let edits = patch.to_edits(&source).unwrap();
+        assert_eq!(
+            Patch::from_edits(source.len(), &edits)
+                .unwrap()
+                .apply(&source)
+                .unwrap(),
+            target
+        );
+    }
+
+    #[test]
+    fn target_copies_validation() {
+        assert_eq!(
+            Patch::try_from_bytes(&TargetCopyInstruction::new(1, 1).to_bytes()),
+            Err(InstructionError::InvalidContent)
+        );
+        let mut bytes = AddInstruction::new(vec![1, 2]).to_bytes();
+        bytes.extend(TargetCopyInstruction::new(4, 2).to_bytes());
+        assert_eq!(
+            Patch::try_from_bytes(&bytes).unwrap().apply(b"").unwrap(),
+            vec![1, 2, 1, 2, 1, 2]
+        );
+        bytes.extend(TargetCopyInstruction::new(1, 7).to_bytes());
+        assert_eq!(
+            Patch::try_from_bytes(&bytes),
+            Err(InstructionError::InvalidContent)
+        );
+    }
+
+    #[test]
+    fn max_target_distance() {
+        let (source, target) = repetitive();
+        assert_eq!(Patch::new(&source, &target).max_target_distance(), 0);
+        let patch = Patch::new_with_target_copies(&source, &target);
+        assert_eq!(patch.max_target_distance(), target.len() - 15);
+
+        let mut target = b"0123456789".repeat(10);
+        target.extend((0..=u8::MAX).cycle().take(4000).collect::<Vec<u8>>());
+        let patch = Patch::new_with_target_copies(b"", &target);
+        let window = patch.max_target_distance();
+        assert_eq!(window, u8::MAX as usize + 1);
+        let mut history = TargetHistory::new(&patch);
+        let mut written: Vec<u8> = Vec::new();
+        for instruction in patch.instructions() {
+            written.extend(history.apply(instruction, &mut [].iter()));
+            assert!(history.bytes.len() <= window * 2 + u8::MAX as usize);
+        }
+        assert_eq!(written, target);
+
+        let mut builder = PatchBuilder::default();
+        builder.add(b"x");
+        builder.target_copy(u32::MAX as usize, 1);
+        let history = TargetHistory::new(&builder.build());
+        assert!(history.bytes.capacity() <= MAX_HISTORY_RESERVATION);
+    }
+
+    #[test]
+    fn try_from_bytes_with() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+        let mut bytes = vec![b'?', 3, 1, 2, 3];
+        bytes.extend(patch.to_bytes());
+        bytes.extend([b'!', 0]);
+
+        assert_eq!(
+            Patch::try_from_bytes(&bytes),
+            Err(InstructionError::InvalidSign)
+        );
+        let (decoded, skipped) =
+            Patch::try_from_bytes_with(&bytes, DecodePolicy::SkipUnknown).unwrap();
+        assert_eq!(decoded, patch);
+        assert_eq!(
+            skipped,
+            vec![
+                SkippedInstruction {
+                    instruction: 0,
+                    sign: b'?',
+                    content: vec![1, 2, 3]
+                },
+                SkippedInstruction {
+                    instruction: patch.instructions().len() + 1,
+                    sign: b'!',
+                    content: vec![]
+                },
+            ]
+        );
+
+        assert_eq!(
+            Patch::try_from_bytes_with(&[b'?', 3, 1], DecodePolicy::SkipUnknown),
+            Err(InstructionError::MissingContent)
+        );
+        assert_eq!(
+            Patch::try_from_bytes_with(b"?", DecodePolicy::SkipUnknown),
+            Err(InstructionError::MissingLength)
+        );
+    }
 }
@@ -0,0 +1,243 @@
+use std::error::Error;
+
+use crate::{
+    instructions::{
+        delta_instruction::DeltaInstruction, InstructionError, InstructionInfo, Result,
+        ADD_INSTRUCTION_SIGN, COPY_INSTRUCTION_SIGN, REMOVE_INSTRUCTION_SIGN,
+        TARGET_COPY_INSTRUCTION_SIGN,
+    },
+    patch::Patch,
+};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ProfileError {
+    DuplicateSign(u8),
+    ZeroLength,
+}
+
+impl std::fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProfileError::DuplicateSign(sign) => {
+                write!(
+                    f,
+                    "Sign {:?} is used by more than one instruction",
+                    *sign as char
+                )
+            }
+            ProfileError::ZeroLength => write!(f, "Maximum instruction length can't be zero"),
+        }
+    }
+}
+
+impl Error for ProfileError {}
+
+/// Sign bytes and limits for embedding patches in formats that reserve the standard signs.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FormatProfile {
+    id: u8,
+    signs: [u8; 4],
+    max_instruction_length: u8,
+}
+
+impl FormatProfile {
+    pub const STANDARD: FormatProfile = FormatProfile {
+        id: 0,
+        signs: [
+            REMOVE_INSTRUCTION_SIGN,
+            ADD_INSTRUCTION_SIGN,
+            COPY_INSTRUCTION_SIGN,
+            TARGET_COPY_INSTRUCTION_SIGN,
+        ],
+        max_instruction_length: u8::MAX,
+    };
+
+    /// `signs` are the remove, add, copy and target copy signs, in that order.
+    pub fn new(
+        id: u8,
+        signs: [u8; 4],
+        max_instruction_length: u8,
+    ) -> std::result::Result<Self, ProfileError> {
+        if let Some(index) = (1..signs.len()).find(|index| signs[..*index].contains(&signs[*index]))
+        {
+            return Err(ProfileError::DuplicateSign(signs[index]));
+        }
+        if max_instruction_length == 0 {
+            return Err(ProfileError::ZeroLength);
+        }
+        Ok(Self {
+            id,
+            signs,
+            max_instruction_length,
+        })
+    }
+
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    pub fn signs(&self) -> [u8; 4] {
+        self.signs
+    }
+
+    pub fn max_instruction_length(&self) -> u8 {
+        self.max_instruction_length
+    }
+
+    /// Reads the profile id from the header of a profiled patch.
+    pub fn read_id(bytes: &[u8]) -> Option<u8> {
+        bytes.first().copied()
+    }
+
+    fn kind(&self, sign: u8) -> Option<usize> {
+        self.signs.iter().position(|item| *item == sign)
+    }
+}
+
+impl Default for FormatProfile {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
+
+impl Patch {
+    pub fn to_bytes_with_profile(&self, profile: &FormatProfile) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(1 + self.byte_length());
+        bytes.push(profile.id);
+        let max = profile.max_instruction_length as usize;
+        for instruction in self.instructions() {
+            let length = instruction.len() as usize;
+            match instruction {
+                DeltaInstruction::Remove(_) => {
+                    for start in (0..length).step_by(max) {
+                        bytes.push(profile.signs[0]);
+                        bytes.push((length - start).min(max) as u8);
+                    }
+                }
+                DeltaInstruction::Add(add) => {
+                    for chunk in add.content().chunks(max) {
+                        bytes.push(profile.signs[1]);
+                        bytes.push(chunk.len() as u8);
+                        bytes.extend_from_slice(chunk);
+                    }
+                }
+                DeltaInstruction::Copy(copy) => {
+                    for chunk in copy.content().chunks(max) {
+                        bytes.push(profile.signs[2]);
+                        bytes.push(chunk.len() as u8);
+                        bytes.extend_from_slice(chunk);
+                    }
+                }
+                DeltaInstruction::TargetCopy(target_copy) => {
+                    for start in (0..length).step_by(max) {
+                        bytes.push(profile.signs[3]);
+                        bytes.push((length - start).min(max) as u8);
+                        bytes.extend(target_copy.distance().to_be_bytes());
+                    }
+                }
+            }
+        }
+        bytes
+    }
+
+    pub fn try_from_bytes_with_profile(bytes: &[u8], profile: &FormatProfile) -> Result<Self> {
+        match FormatProfile::read_id(bytes) {
+            Some(id) if id == profile.id => (),
+            Some(_) => return Err(InstructionError::InvalidContent),
+            None => return Err(InstructionError::MissignSign),
+        }
+        let standard = FormatProfile::STANDARD.signs;
+        let mut translated: Vec<u8> = Vec::with_capacity(bytes.len());
+        let mut offset = 1usize;
+        while offset < bytes.len() {
+            let kind = profile
+                .kind(bytes[offset])
+                .ok_or(InstructionError::InvalidSign)?;
+            let length = *bytes
+                .get(offset + 1)
+                .ok_or(InstructionError::MissingLength)?;
+            if length > profile.max_instruction_length {
+                return Err(InstructionError::InvalidLength);
+            }
+            let content_length = match kind {
+                0 => 0,
+                3 => 4,
+                _ => length as usize,
+            };
+            let content = bytes
+                .get(offset + 2..offset + 2 + content_length)
+                .ok_or(InstructionError::MissingContent)?;
+            translated.extend([standard[kind], length]);
+            translated.extend_from_slice(content);
+            offset += 2 + content_length;
+        }
+        Patch::try_from_bytes(&translated)
+    }
+}
+
+#[cfg(test)]
+mod profile_tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn new() {
+        assert_eq!(
+            FormatProfile::new(1, *b"abca", 16),
+            Err(ProfileError::DuplicateSign(b'a'))
+        );
+        assert_eq!(
+            FormatProfile::new(1, *b"abcd", 0),
+            Err(ProfileError::ZeroLength)
+        );
+        assert_eq!(
+            FormatProfile::new(0, *b"-+|<", 255),
+            Ok(FormatProfile::STANDARD)
+        );
+    }
+
+    #[test]
+    fn bytes_with_profile() {
+        let source = fs::read("files/source.txt").unwrap();
+        let mut target = fs::read("files/target.txt").unwrap();
+        target.extend(b"0123456789".repeat(40));
+        let patch = Patch::new_with_target_copies(&source, &target);
+
+        let standard = patch.to_bytes_with_profile(&FormatProfile::STANDARD);
+        assert_eq!(standard[0], 0);
+        assert_eq!(standard[1..], patch.to_bytes());
+        assert_eq!(
+            Patch::try_from_bytes_with_profile(&standard, &FormatProfile::default()),
+            Ok(patch.clone())
+        );
+
+        let profile = FormatProfile::new(7, *b"RACT", 100).unwrap();
+        let bytes = patch.to_bytes_with_profile(&profile);
+        assert_eq!(FormatProfile::read_id(&bytes), Some(7));
+        let decoded = Patch::try_from_bytes_with_profile(&bytes, &profile).unwrap();
+        assert_eq!(decoded.apply(&source), Some(target));
+        assert!(decoded
+            .instructions()
+            .iter()
+            .all(|instruction| instruction.len() <= 100));
+
+        assert_eq!(
+            Patch::try_from_bytes_with_profile(&bytes, &FormatProfile::STANDARD),
+            Err(InstructionError::InvalidContent)
+        );
+        let strict = FormatProfile::new(7, *b"RACT", 50).unwrap();
+        assert_eq!(
+            Patch::try_from_bytes_with_profile(&bytes, &strict),
+            Err(InstructionError::InvalidLength)
+        );
+        assert_eq!(
+            Patch::try_from_bytes_with_profile(&[7, b'+', 0], &profile),
+            Err(InstructionError::InvalidSign)
+        );
+        assert_eq!(
+            Patch::try_from_bytes_with_profile(&[7, b'A', 2, 1], &profile),
+            Err(InstructionError::MissingContent)
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use std::slice::Iter;
+
+use crate::{
+    instructions::{
+        delta_instruction::DeltaInstruction, InstructionBytes, InstructionContent,
+        InstructionError, InstructionInfo,
+    },
+    ApplyError,
+};
+
+#[derive(Debug)]
+pub struct ProgressiveApplier<'a> {
+    source: Iter<'a, u8>,
+    pending: Vec<u8>,
+    target: Vec<u8>,
+}
+
+impl<'a> ProgressiveApplier<'a> {
+    pub fn new(source: &'a [u8]) -> Self {
+        Self {
+            source: source.iter(),
+            pending: Vec::new(),
+            target: Vec::new(),
+        }
+    }
+
+    pub fn target(&self) -> &[u8] {
+        &self.target
+    }
+
+    /// Applies every complete instruction in the bytes fed so far. On an error the instructions
+    /// before the failing one stay applied and the failing one stays pending, so feeding more
+    /// bytes fails again instead of applying anything twice.
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<&[u8], ApplyError> {
+        self.pending.extend_from_slice(bytes);
+        let start = self.target.len();
+        let mut consumed = 0usize;
+        let mut result = Ok(());
+        while consumed < self.pending.len() {
+            let mut bytes_iter = self.pending[consumed..].iter().peekable();
+            let instruction = match DeltaInstruction::try_from_bytes(&mut bytes_iter) {
+                Ok(instruction) => instruction,
+                Err(InstructionError::MissingLength | InstructionError::MissingContent) => break,
+                Err(error) => {
+                    result = Err(error.into());
+                    break;
+                }
+            };
+            let end = self.pending.len() - bytes_iter.len();
+            if let Err(error) = self.apply(&instruction) {
+                result = Err(error);
+                break;
+            }
+            consumed = end;
+        }
+        self.pending.drain(..consumed);
+        result.map(|()| &self.target[start..])
+    }
+
+    fn apply(&mut self, instruction: &DeltaInstruction) -> Result<(), ApplyError> {
+        match instruction {
+            DeltaInstruction::Remove(_) | DeltaInstruction::Copy(_)
+                if instruction.len() as usize > self.source.len() =>
+            {
+                return Err(ApplyError::SourceMismatch)
+            }
+            DeltaInstruction::TargetCopy(target_copy)
+                if target_copy.distance() as usize > self.target.len() =>
+            {
+                return Err(InstructionError::InvalidContent.into())
+            }
+            _ => (),
+        }
+        instruction.apply(&mut self.source, &mut self.target);
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<Vec<u8>, ApplyError> {
+        if !self.pending.is_empty() {
+            return Err(InstructionError::MissingContent.into());
+        }
+        if self.source.len() != 0 {
+            return Err(ApplyError::SourceMismatch);
+        }
+        Ok(self.target)
+    }
+}
+
+#[cfg(test)]
+mod progressive_tests {
+    use std::fs;
+
+    use super::*;
+    use crate::patch::Patch;
+
+    #[test]
+    fn feed() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let bytes = Patch::new(&source, &target).to_bytes();
+        for chunk_size in [1, 2, 7, 300, bytes.len()] {
+            let mut applier = ProgressiveApplier::new(&source);
+            let mut output: Vec<u8> = Vec::new();
+            for chunk in bytes.chunks(chunk_size) {
+                output.extend_from_slice(applier.feed(chunk).unwrap());
+                assert_eq!(applier.target(), &output[..]);
+                assert!(target.starts_with(&output));
+            }
+            assert_eq!(applier.finish().unwrap(), target);
+        }
+
+        let repeated = b"0123456789".repeat(10);
+        let bytes = Patch::new_with_target_copies(b"", &repeated).to_bytes();
+        let mut applier = ProgressiveApplier::new(b"");
+        for chunk in bytes.chunks(3) {
+            applier.feed(chunk).unwrap();
+        }
+        assert_eq!(applier.finish().unwrap(), repeated);
+    }
+
+    #[test]
+    fn feed_err() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let bytes = Patch::new(&source, &target).to_bytes();
+
+        let mut applier = ProgressiveApplier::new(&source);
+        applier.feed(&bytes[..bytes.len() - 1]).unwrap();
+        assert_eq!(
+            applier.finish(),
+            Err(ApplyError::InvalidPatch(InstructionError::MissingContent))
+        );
+
+        let mut applier = ProgressiveApplier::new(&source[..10]);
+        assert_eq!(applier.feed(&bytes), Err(ApplyError::SourceMismatch));
+
+        let mut applier = ProgressiveApplier::new(&source);
+        applier.feed(&bytes).unwrap();
+        assert_eq!(
+            applier.feed(b"?"),
+            Err(ApplyError::InvalidPatch(InstructionError::InvalidSign))
+        );
+
+        let mut applier = ProgressiveApplier::new(b"");
+        assert_eq!(
+            applier.feed(&[b'<', 1, 0, 0, 0, 1]),
+            Err(ApplyError::InvalidPatch(InstructionError::InvalidContent))
+        );
+
+        let mut applier = ProgressiveApplier::new(b"");
+        let bad_segment = [b'+', 3, b'a', b'b', b'c', b'<', 1, 0, 0, 0, 9];
+        assert_eq!(
+            applier.feed(&bad_segment),
+            Err(ApplyError::InvalidPatch(InstructionError::InvalidContent))
+        );
+        assert_eq!(applier.target(), b"abc");
+        assert_eq!(
+            applier.feed(b"+\x01d"),
+            Err(ApplyError::InvalidPatch(InstructionError::InvalidContent))
+        );
+        assert_eq!(applier.target(), b"abc");
+        assert!(applier.finish().is_err());
+    }
+}
@@ -0,0 +1,329 @@
+use crate::{
+    instructions::{
+        delta_instruction::DeltaInstruction, InstructionError, InstructionInfo, Result,
+        ADD_INSTRUCTION_SIGN, COPY_INSTRUCTION_SIGN, REMOVE_INSTRUCTION_SIGN,
+        TARGET_COPY_INSTRUCTION_SIGN,
+    },
+    patch::Patch,
+};
+
+pub const ENTROPY_HEADER_SIGN: u8 = b'E';
+const TOP: u32 = 1 << 24;
+const MAX_TOTAL: u32 = 1 << 16;
+const INCREMENT: u32 = 24;
+/// Bytes the decoder may read past the end before the body counts as truncated. The encoder
+/// flushes enough bytes that a valid body never needs more than this.
+const MAX_OVERRUN: usize = 4;
+const SIGNS: [u8; 4] = [
+    REMOVE_INSTRUCTION_SIGN,
+    ADD_INSTRUCTION_SIGN,
+    COPY_INSTRUCTION_SIGN,
+    TARGET_COPY_INSTRUCTION_SIGN,
+];
+const END: usize = SIGNS.len();
+
+/// Adaptive frequencies for one context.
+#[derive(Debug, Clone)]
+struct Model {
+    frequencies: Vec<u32>,
+    total: u32,
+}
+
+impl Model {
+    fn new(symbols: usize) -> Self {
+        Self {
+            frequencies: vec![1; symbols],
+            total: symbols as u32,
+        }
+    }
+
+    fn cumulative(&self, symbol: usize) -> u32 {
+        self.frequencies[..symbol].iter().sum()
+    }
+
+    fn update(&mut self, symbol: usize) {
+        self.frequencies[symbol] += INCREMENT;
+        self.total += INCREMENT;
+        if self.total > MAX_TOTAL {
+            self.frequencies
+                .iter_mut()
+                .for_each(|frequency| *frequency = frequency.div_ceil(2));
+            self.total = self.frequencies.iter().sum();
+        }
+    }
+}
+
+/// One model per kind, length and byte context.
+struct Models {
+    kinds: Vec<Model>,
+    lengths: Vec<Model>,
+    literals: Vec<Model>,
+    deltas: Vec<Model>,
+    distances: Vec<Model>,
+}
+
+impl Models {
+    fn new() -> Self {
+        Self {
+            kinds: vec![Model::new(END + 1); END + 1],
+            lengths: vec![Model::new(256); SIGNS.len()],
+            literals: vec![Model::new(256); 256],
+            deltas: vec![Model::new(256); 256],
+            distances: vec![Model::new(256); 4],
+        }
+    }
+}
+
+struct Encoder {
+    low: u64,
+    range: u32,
+    cache: u8,
+    cache_size: u64,
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    fn new(bytes: Vec<u8>) -> Self {
+        Self {
+            low: 0,
+            range: u32::MAX,
+            cache: 0,
+            cache_size: 1,
+            bytes,
+        }
+    }
+
+    fn shift_low(&mut self) {
+        if self.low < 0xff00_0000 || self.low > u32::MAX as u64 {
+            let carry = (self.low >> 32) as u8;
+            let mut byte = self.cache;
+            while self.cache_size > 0 {
+                self.bytes.push(byte.wrapping_add(carry));
+                byte = 0xff;
+                self.cache_size -= 1;
+            }
+            self.cache = (self.low >> 24) as u8;
+        }
+        self.cache_size += 1;
+        self.low = (self.low & 0x00ff_ffff) << 8;
+    }
+
+    fn encode(&mut self, model: &mut Model, symbol: usize) {
+        self.range /= model.total;
+        self.low += (model.cumulative(symbol) * self.range) as u64;
+        self.range *= model.frequencies[symbol];
+        while self.range < TOP {
+            self.range <<= 8;
+            self.shift_low();
+        }
+        model.update(symbol);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        for _ in 0..5 {
+            self.shift_low();
+        }
+        self.bytes
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    code: u32,
+    range: u32,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(bytes: &'a [u8]) -> Result<Self> {
+        let mut decoder = Self {
+            bytes,
+            offset: 0,
+            code: 0,
+            range: u32::MAX,
+        };
+        for _ in 0..5 {
+            decoder.code = (decoder.code << 8) | decoder.next()? as u32;
+        }
+        Ok(decoder)
+    }
+
+    fn next(&mut self) -> Result<u8> {
+        let byte = self.bytes.get(self.offset).copied().unwrap_or(0);
+        self.offset += 1;
+        match self.offset > self.bytes.len() + MAX_OVERRUN {
+            true => Err(InstructionError::MissingContent),
+            false => Ok(byte),
+        }
+    }
+
+    fn decode(&mut self, model: &mut Model) -> Result<usize> {
+        self.range /= model.total;
+        let value = (self.code / self.range).min(model.total - 1);
+        let mut cumulative = 0u32;
+        let mut symbol = 0usize;
+        while cumulative + model.frequencies[symbol] <= value {
+            cumulative += model.frequencies[symbol];
+            symbol += 1;
+        }
+        self.code -= cumulative * self.range;
+        self.range *= model.frequencies[symbol];
+        while self.range < TOP {
+            self.code = (self.code << 8) | self.next()? as u32;
+            self.range <<= 8;
+        }
+        model.update(symbol);
+        Ok(symbol)
+    }
+}
+
+impl Patch {
+    /// Encodes the patch with an adaptive range coder. Instruction kinds are modelled on the
+    /// previous kind, lengths on the kind, and add and copy bytes on the previous byte of the
+    /// same kind.
+    pub fn to_entropy_coded(&self) -> Vec<u8> {
+        let mut models = Models::new();
+        let mut encoder = Encoder::new(vec![ENTROPY_HEADER_SIGN]);
+        let (mut kind, mut literal, mut delta) = (END, 0usize, 0usize);
+        for instruction in self.instructions() {
+            let next = match instruction {
+                DeltaInstruction::Remove(_) => 0,
+                DeltaInstruction::Add(_) => 1,
+                DeltaInstruction::Copy(_) => 2,
+                DeltaInstruction::TargetCopy(_) => 3,
+            };
+            encoder.encode(&mut models.kinds[kind], next);
+            kind = next;
+            encoder.encode(&mut models.lengths[kind], instruction.len() as usize);
+            match instruction {
+                DeltaInstruction::Add(add) => {
+                    for byte in add.content() {
+                        encoder.encode(&mut models.literals[literal], *byte as usize);
+                        literal = *byte as usize;
+                    }
+                }
+                DeltaInstruction::Copy(copy) => {
+                    for byte in copy.content() {
+                        encoder.encode(&mut models.deltas[delta], *byte as usize);
+                        delta = *byte as usize;
+                    }
+                }
+                DeltaInstruction::TargetCopy(target_copy) => {
+                    for (model, byte) in models
+                        .distances
+                        .iter_mut()
+                        .zip(target_copy.distance().to_be_bytes())
+                    {
+                        encoder.encode(model, byte as usize);
+                    }
+                }
+                DeltaInstruction::Remove(_) => (),
+            }
+        }
+        encoder.encode(&mut models.kinds[kind], END);
+        encoder.finish()
+    }
+
+    pub fn try_from_entropy_coded(bytes: &[u8]) -> Result<Self> {
+        match bytes.first() {
+            Some(&ENTROPY_HEADER_SIGN) => (),
+            Some(_) => return Err(InstructionError::InvalidSign),
+            None => return Err(InstructionError::MissignSign),
+        }
+        let mut models = Models::new();
+        let mut decoder = Decoder::new(&bytes[1..])?;
+        let mut translated: Vec<u8> = Vec::with_capacity(bytes.len() * 2);
+        let (mut kind, mut literal, mut delta) = (END, 0usize, 0usize);
+        loop {
+            kind = decoder.decode(&mut models.kinds[kind])?;
+            if kind == END {
+                break;
+            }
+            let length = decoder.decode(&mut models.lengths[kind])?;
+            translated.extend([SIGNS[kind], length as u8]);
+            match SIGNS[kind] {
+                ADD_INSTRUCTION_SIGN => {
+                    for _ in 0..length {
+                        literal = decoder.decode(&mut models.literals[literal])?;
+                        translated.push(literal as u8);
+                    }
+                }
+                COPY_INSTRUCTION_SIGN => {
+                    for _ in 0..length {
+                        delta = decoder.decode(&mut models.deltas[delta])?;
+                        translated.push(delta as u8);
+                    }
+                }
+                TARGET_COPY_INSTRUCTION_SIGN => {
+                    for model in models.distances.iter_mut() {
+                        translated.push(decoder.decode(model)? as u8);
+                    }
+                }
+                _ => (),
+            }
+        }
+        Patch::try_from_bytes(&translated)
+    }
+}
+
+#[cfg(test)]
+mod range_coder_tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn entropy_coded() {
+        let source = fs::read("files/source.txt").unwrap();
+        let mut target = fs::read("files/target.txt").unwrap();
+        target.extend(b"0123456789".repeat(40));
+        for patch in [
+            Patch::new(&source, &target),
+            Patch::new_with_target_copies(&source, &target),
+            Patch::new(&source, b""),
+            Patch::default(),
+        ] {
+            let bytes = patch.to_entropy_coded();
+            assert_eq!(bytes[0], ENTROPY_HEADER_SIGN);
+            assert_eq!(Patch::try_from_entropy_coded(&bytes), Ok(patch.clone()));
+        }
+
+        let patch = Patch::new(&source, &target);
+        let coded = patch.to_entropy_coded().len();
+        let raw = patch.to_bytes().len();
+        assert!(
+            coded * 4 < raw * 3,
+            "{} coded bytes for {} raw bytes",
+            coded,
+            raw
+        );
+
+        let wide: Vec<u8> = (0..=255).cycle().take(100_000).collect();
+        let patch = Patch::new(b"", &wide);
+        assert_eq!(
+            Patch::try_from_entropy_coded(&patch.to_entropy_coded()),
+            Ok(patch)
+        );
+    }
+
+    #[test]
+    fn entropy_coded_err() {
+        assert_eq!(
+            Patch::try_from_entropy_coded(b""),
+            Err(InstructionError::MissignSign)
+        );
+        assert_eq!(
+            Patch::try_from_entropy_coded(b"+\x01a"),
+            Err(InstructionError::InvalidSign)
+        );
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let bytes = Patch::new(&source, &target).to_entropy_coded();
+        assert!(Patch::try_from_entropy_coded(&bytes[..bytes.len() / 2]).is_err());
+        for index in (1..bytes.len()).step_by(37) {
+            let mut corrupted = bytes.clone();
+            corrupted[index] ^= 0x55;
+            let _ = Patch::try_from_entropy_coded(&corrupted);
+        }
+    }
+}
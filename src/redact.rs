@@ -0,0 +1,207 @@
+use std::ops::Range;
+
+use crate::{
+    instructions::{
+        delta_instruction::DeltaInstruction, InstructionError, InstructionInfo, Result,
+    },
+    patch::{Patch, PatchBuilder},
+};
+
+/// Marks [`RedactedPatch::to_bytes`], followed by the redacted ranges and the patch.
+pub const REDACTED_HEADER_SIGN: u8 = b'R';
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct RedactedPatch {
+    patch: Patch,
+    ranges: Vec<Range<usize>>,
+}
+
+impl RedactedPatch {
+    pub fn instructions(&self) -> &[DeltaInstruction] {
+        self.patch.instructions()
+    }
+
+    pub fn redacted_ranges(&self) -> &[Range<usize>] {
+        &self.ranges
+    }
+
+    pub fn source_length(&self) -> usize {
+        self.patch.source_length()
+    }
+
+    pub fn target_length(&self) -> usize {
+        self.patch.target_length()
+    }
+
+    /// The redacted patch. It applies like any other, writing zeros over the redacted ranges.
+    /// Target copies reading from a redacted range copy those zeros.
+    pub fn patch(&self) -> &Patch {
+        &self.patch
+    }
+
+    /// [`REDACTED_HEADER_SIGN`], the range count and each range as big endian u64 start and
+    /// end, then the patch bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![REDACTED_HEADER_SIGN];
+        bytes.extend((self.ranges.len() as u64).to_be_bytes());
+        for range in self.ranges.iter() {
+            bytes.extend((range.start as u64).to_be_bytes());
+            bytes.extend((range.end as u64).to_be_bytes());
+        }
+        bytes.extend(self.patch.to_bytes());
+        bytes
+    }
+
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self> {
+        match bytes.first() {
+            Some(&REDACTED_HEADER_SIGN) => (),
+            Some(_) => return Err(InstructionError::InvalidSign),
+            None => return Err(InstructionError::MissignSign),
+        }
+        let mut values = bytes[1..].chunks(8).map(|chunk| {
+            <[u8; 8]>::try_from(chunk)
+                .map_err(|_| InstructionError::MissingLength)
+                .and_then(|value| {
+                    usize::try_from(u64::from_be_bytes(value))
+                        .map_err(|_| InstructionError::InvalidLength)
+                })
+        });
+        let count = values.next().ok_or(InstructionError::MissingLength)??;
+        if count > (bytes.len() - 9) / 16 {
+            return Err(InstructionError::MissingLength);
+        }
+        let mut ranges: Vec<Range<usize>> = Vec::with_capacity(count);
+        for _ in 0..count {
+            let start = values.next().ok_or(InstructionError::MissingLength)??;
+            let end = values.next().ok_or(InstructionError::MissingLength)??;
+            if start > end {
+                return Err(InstructionError::InvalidContent);
+            }
+            ranges.push(start..end);
+        }
+        Ok(Self {
+            patch: Patch::try_from_bytes(&bytes[9 + count * 16..])?,
+            ranges,
+        })
+    }
+}
+
+/// Splits `offset..offset + length` into runs that are either all redacted or all kept.
+fn runs(offset: usize, length: usize, ranges: &[Range<usize>]) -> Vec<(Range<usize>, bool)> {
+    let mut runs: Vec<(Range<usize>, bool)> = Vec::new();
+    for position in offset..offset + length {
+        let redacted = ranges.iter().any(|range| range.contains(&position));
+        match runs.last_mut() {
+            Some((run, last)) if *last == redacted => run.end = position + 1,
+            _ => runs.push((position..position + 1, redacted)),
+        }
+    }
+    runs
+}
+
+impl Patch {
+    /// Rewrites every part of the patch that writes into `ranges` of the target as added zeros.
+    /// Copies into a redacted range become a remove and an add, so neither new content nor
+    /// source bytes end up there.
+    pub fn redact(&self, ranges: &[Range<usize>]) -> RedactedPatch {
+        let mut builder = PatchBuilder::default();
+        let mut target_offset = 0usize;
+        for instruction in self.instructions() {
+            let length = instruction.len() as usize;
+            if let DeltaInstruction::Remove(_) = instruction {
+                builder.remove(length);
+                continue;
+            }
+            for (run, redacted) in runs(target_offset, length, ranges) {
+                let content = run.start - target_offset..run.end - target_offset;
+                match instruction {
+                    _ if redacted => {
+                        if let DeltaInstruction::Copy(_) = instruction {
+                            builder.remove(run.len());
+                        }
+                        builder.add(&vec![0; run.len()]);
+                    }
+                    DeltaInstruction::Add(add) => builder.add(&add.content()[content]),
+                    DeltaInstruction::Copy(copy) => builder.copy_deltas(&copy.content()[content]),
+                    DeltaInstruction::TargetCopy(target_copy) => {
+                        builder.target_copy(target_copy.distance() as usize, run.len())
+                    }
+                    DeltaInstruction::Remove(_) => (),
+                }
+            }
+            target_offset += length;
+        }
+        RedactedPatch {
+            patch: builder.build(),
+            ranges: ranges.to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod redact_tests {
+    use super::*;
+
+    #[test]
+    fn redact() {
+        let source = b"user=admin password=hunter2 ok";
+        let target = b"user=admin password=correcthorse ok!";
+        let patch = Patch::new(source, target);
+        let redacted = patch.redact(&[20..26, 26..32]);
+
+        assert_eq!(redacted.redacted_ranges(), &[20..26, 26..32]);
+        assert_eq!(redacted.target_length(), patch.target_length());
+        assert_eq!(redacted.source_length(), patch.source_length());
+        let restored = RedactedPatch::try_from_bytes(&redacted.to_bytes()).unwrap();
+        assert_eq!(restored, redacted);
+        let applied = restored.patch().apply(source).unwrap();
+        assert_eq!(&applied[..20], &target[..20]);
+        assert_eq!(&applied[32..], &target[32..]);
+        assert_eq!(applied[20..32], [0; 12]);
+
+        let source = b"secret key 42, public data";
+        let target = b"secret key 42, public data secret key 42";
+        let patch = Patch::new_with_target_copies(source, target);
+        assert!(patch.has_target_copies());
+        let applied = patch.redact(&[0..6, 6..13]).patch().apply(source).unwrap();
+        assert_eq!(applied[..13], [0; 13]);
+        assert_eq!(applied[13..27], target[13..27]);
+        assert_eq!(applied.len(), target.len());
+        let applied = patch
+            .redact(&[27..34, 34..40])
+            .patch()
+            .apply(source)
+            .unwrap();
+        assert_eq!(applied[..27], target[..27]);
+        assert_eq!(applied[27..], [0; 13]);
+
+        assert_eq!(
+            Patch::try_from_bytes(&redacted.to_bytes()),
+            Err(InstructionError::InvalidSign)
+        );
+        let bytes = redacted.to_bytes();
+        assert_eq!(
+            RedactedPatch::try_from_bytes(&bytes[..20]),
+            Err(InstructionError::MissingLength)
+        );
+        assert_eq!(
+            RedactedPatch::try_from_bytes(&patch.to_bytes()),
+            Err(InstructionError::InvalidSign)
+        );
+        let mut reversed = bytes.clone();
+        reversed[9..17].copy_from_slice(&40u64.to_be_bytes());
+        assert_eq!(
+            RedactedPatch::try_from_bytes(&reversed),
+            Err(InstructionError::InvalidContent)
+        );
+    }
+
+    #[test]
+    fn redact_nothing() {
+        let patch = Patch::new(b"Hello world", b"Hello brave new world");
+        let redacted = patch.redact(&[]);
+        assert_eq!(redacted.instructions(), patch.instructions());
+        let redacted = patch.redact(&[100..200, 300..400]);
+        assert_eq!(redacted.instructions(), patch.instructions());
+    }
+}
@@ -0,0 +1,197 @@
+use crate::{
+    instructions::Result,
+    patch::{DecodePolicy, Patch, SkippedInstruction},
+};
+
+/// Limits used by [`Patch::risk_report_with`].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct RiskThresholds {
+    /// Largest target length produced per encoded patch byte. Only target copies expand past
+    /// roughly one byte per byte, so a high ratio points at a decompression bomb.
+    pub max_expansion_ratio: f64,
+    /// Largest factor by which the target may grow or shrink relative to the source. Patches
+    /// creating or deleting a whole file aren't checked.
+    pub max_size_ratio: f64,
+    pub max_instructions: usize,
+    pub max_target_length: usize,
+}
+
+impl Default for RiskThresholds {
+    fn default() -> Self {
+        Self {
+            max_expansion_ratio: 32.0,
+            max_size_ratio: 1000.0,
+            max_instructions: 1 << 24,
+            max_target_length: usize::try_from(1u64 << 32).unwrap_or(usize::MAX),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum RiskFlag {
+    ExpansionRatio {
+        patch_length: usize,
+        target_length: usize,
+    },
+    SizeRatio {
+        source_length: usize,
+        target_length: usize,
+    },
+    InstructionCount(usize),
+    TargetLength(usize),
+    UnknownSections(Vec<u8>),
+}
+
+impl std::fmt::Display for RiskFlag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RiskFlag::ExpansionRatio {
+                patch_length,
+                target_length,
+            } => write!(
+                f,
+                "A {} byte patch expands into {} bytes",
+                patch_length, target_length
+            ),
+            RiskFlag::SizeRatio {
+                source_length,
+                target_length,
+            } => write!(
+                f,
+                "A {} byte source turns into a {} byte target",
+                source_length, target_length
+            ),
+            RiskFlag::InstructionCount(count) => write!(f, "Patch has {} instructions", count),
+            RiskFlag::TargetLength(length) => write!(f, "Patch produces {} bytes", length),
+            RiskFlag::UnknownSections(signs) => {
+                write!(f, "Patch contains unknown sections with signs {:?}", signs)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct RiskReport {
+    pub flags: Vec<RiskFlag>,
+}
+
+impl RiskReport {
+    pub fn is_suspicious(&self) -> bool {
+        !self.flags.is_empty()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::from_bytes_with(bytes, &RiskThresholds::default())
+    }
+
+    pub fn from_bytes_with(bytes: &[u8], thresholds: &RiskThresholds) -> Result<Self> {
+        let (patch, skipped) = Patch::try_from_bytes_with(bytes, DecodePolicy::SkipUnknown)?;
+        Ok(patch.risk_report_with(thresholds, &skipped))
+    }
+}
+
+impl Patch {
+    pub fn risk_report(&self) -> RiskReport {
+        self.risk_report_with(&RiskThresholds::default(), &[])
+    }
+
+    pub fn risk_report_with(
+        &self,
+        thresholds: &RiskThresholds,
+        skipped: &[SkippedInstruction],
+    ) -> RiskReport {
+        let mut flags: Vec<RiskFlag> = Vec::new();
+        let patch_length = self.byte_length()
+            + skipped
+                .iter()
+                .map(|skipped| skipped.content.len() + 2)
+                .sum::<usize>();
+        let source_length = self.source_length();
+        let target_length = self.target_length();
+        if target_length as f64 > patch_length.max(1) as f64 * thresholds.max_expansion_ratio {
+            flags.push(RiskFlag::ExpansionRatio {
+                patch_length,
+                target_length,
+            });
+        }
+        let (smaller, larger) = match source_length < target_length {
+            true => (source_length, target_length),
+            false => (target_length, source_length),
+        };
+        if smaller > 0 && larger as f64 > smaller as f64 * thresholds.max_size_ratio {
+            flags.push(RiskFlag::SizeRatio {
+                source_length,
+                target_length,
+            });
+        }
+        if self.instructions().len() > thresholds.max_instructions {
+            flags.push(RiskFlag::InstructionCount(self.instructions().len()));
+        }
+        if target_length > thresholds.max_target_length {
+            flags.push(RiskFlag::TargetLength(target_length));
+        }
+        if !skipped.is_empty() {
+            flags.push(RiskFlag::UnknownSections(
+                skipped.iter().map(|skipped| skipped.sign).collect(),
+            ));
+        }
+        RiskReport { flags }
+    }
+}
+
+#[cfg(test)]
+mod risk_tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn risk_report() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+        assert_eq!(patch.risk_report(), RiskReport::default());
+        assert!(!patch.risk_report().is_suspicious());
+
+        let bomb = Patch::new_with_target_copies(b"", &[0u8; 1 << 20]);
+        let report = bomb.risk_report();
+        assert!(report.is_suspicious());
+        assert!(matches!(
+            report.flags[..],
+            [RiskFlag::ExpansionRatio { target_length, .. }] if target_length == 1 << 20
+        ));
+
+        let thresholds = RiskThresholds {
+            max_size_ratio: 1.05,
+            max_instructions: 2,
+            max_target_length: 100,
+            ..Default::default()
+        };
+        assert_eq!(
+            patch.risk_report_with(&thresholds, &[]).flags,
+            vec![
+                RiskFlag::SizeRatio {
+                    source_length: source.len(),
+                    target_length: target.len()
+                },
+                RiskFlag::InstructionCount(patch.instructions().len()),
+                RiskFlag::TargetLength(target.len()),
+            ]
+        );
+    }
+
+    #[test]
+    fn from_bytes() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let mut bytes = Patch::new(&source, &target).to_bytes();
+        assert_eq!(RiskReport::from_bytes(&bytes), Ok(RiskReport::default()));
+        bytes.extend([b'?', 2, 1, 2, b'!', 0]);
+        assert_eq!(
+            RiskReport::from_bytes(&bytes).unwrap().flags,
+            vec![RiskFlag::UnknownSections(vec![b'?', b'!'])]
+        );
+        bytes.push(b'?');
+        assert!(RiskReport::from_bytes(&bytes).is_err());
+    }
+}
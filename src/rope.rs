@@ -0,0 +1,253 @@
+use std::{error::Error, ops::Range};
+
+use ropey::Rope;
+
+use crate::{edit::Edit, patch::Patch};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum RopeEdit {
+    Insert { char_idx: usize, text: String },
+    Remove { start: usize, end: usize },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum RopeError {
+    SourceLengthMismatch,
+    InvalidUtf8,
+    OutOfBounds { edit: usize },
+}
+
+impl std::fmt::Display for RopeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RopeError::SourceLengthMismatch => {
+                write!(f, "Rope length doesn't match the patch source length")
+            }
+            RopeError::InvalidUtf8 => write!(f, "Patched content isn't valid UTF-8"),
+            RopeError::OutOfBounds { edit } => {
+                write!(f, "Rope edit {} is outside the document", edit)
+            }
+        }
+    }
+}
+
+impl Error for RopeError {}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Region {
+    source: Range<usize>,
+    target: Range<usize>,
+}
+
+fn is_boundary(bytes: &[u8], index: usize) -> bool {
+    index == 0 || index >= bytes.len() || (bytes[index] as i8) >= -0x40
+}
+
+fn regions(patch: &Patch, source: &[u8]) -> Option<Vec<Region>> {
+    let mut regions: Vec<Region> = Vec::new();
+    let mut shift = 0isize;
+    for edit in patch.to_edits(source)? {
+        let (offset, length, inserted) = match edit {
+            Edit::Insert { offset, content } => (offset, 0, content.len()),
+            Edit::Delete { offset, length } => (offset, length, 0),
+            Edit::Replace {
+                offset,
+                length,
+                content,
+            } => (offset, length, content.len()),
+        };
+        let source_start = (offset as isize - shift) as usize;
+        regions.push(Region {
+            source: source_start..source_start + length,
+            target: offset..offset + inserted,
+        });
+        shift += inserted as isize - length as isize;
+    }
+    Some(regions)
+}
+
+fn align(regions: Vec<Region>, source: &[u8], target: &[u8]) -> Vec<Region> {
+    let mut aligned: Vec<Region> = Vec::with_capacity(regions.len());
+    for mut region in regions {
+        while !is_boundary(source, region.source.start) || !is_boundary(target, region.target.start)
+        {
+            region.source.start -= 1;
+            region.target.start -= 1;
+        }
+        while !is_boundary(source, region.source.end) || !is_boundary(target, region.target.end) {
+            region.source.end += 1;
+            region.target.end += 1;
+        }
+        while let Some(previous) = aligned.last() {
+            if previous.source.end < region.source.start {
+                break;
+            }
+            let previous = aligned.pop().unwrap();
+            region.source.start = previous.source.start;
+            region.target.start = previous.target.start;
+        }
+        aligned.push(region);
+    }
+    aligned
+}
+
+pub fn to_rope_edits(patch: &Patch, rope: &Rope) -> Result<Vec<RopeEdit>, RopeError> {
+    let source: Vec<u8> = rope.bytes().collect();
+    let target = patch
+        .apply(&source)
+        .ok_or(RopeError::SourceLengthMismatch)?;
+    std::str::from_utf8(&target).map_err(|_| RopeError::InvalidUtf8)?;
+    let regions = regions(patch, &source).ok_or(RopeError::SourceLengthMismatch)?;
+
+    let mut edits: Vec<RopeEdit> = Vec::new();
+    let mut char_idx = 0usize;
+    let mut target_offset = 0usize;
+    for region in align(regions, &source, &target) {
+        char_idx += std::str::from_utf8(&target[target_offset..region.target.start])
+            .unwrap()
+            .chars()
+            .count();
+        let removed = rope
+            .byte_slice(region.source.start..region.source.end)
+            .len_chars();
+        if removed > 0 {
+            edits.push(RopeEdit::Remove {
+                start: char_idx,
+                end: char_idx + removed,
+            });
+        }
+        let text = std::str::from_utf8(&target[region.target.clone()]).unwrap();
+        if !text.is_empty() {
+            edits.push(RopeEdit::Insert {
+                char_idx,
+                text: text.to_string(),
+            });
+        }
+        char_idx += text.chars().count();
+        target_offset = region.target.end;
+    }
+    Ok(edits)
+}
+
+pub fn from_rope_edits(rope: &Rope, edits: &[RopeEdit]) -> Result<Patch, RopeError> {
+    let mut working = rope.clone();
+    let mut byte_edits: Vec<Edit> = Vec::with_capacity(edits.len());
+    for (index, edit) in edits.iter().enumerate() {
+        match edit {
+            RopeEdit::Insert { char_idx, text } => {
+                if *char_idx > working.len_chars() {
+                    return Err(RopeError::OutOfBounds { edit: index });
+                }
+                byte_edits.push(Edit::Insert {
+                    offset: working.char_to_byte(*char_idx),
+                    content: text.as_bytes().to_vec(),
+                });
+                working.insert(*char_idx, text);
+            }
+            RopeEdit::Remove { start, end } => {
+                if start > end || *end > working.len_chars() {
+                    return Err(RopeError::OutOfBounds { edit: index });
+                }
+                let offset = working.char_to_byte(*start);
+                byte_edits.push(Edit::Delete {
+                    offset,
+                    length: working.char_to_byte(*end) - offset,
+                });
+                working.remove(*start..*end);
+            }
+        }
+    }
+    Patch::from_edits(rope.len_bytes(), &byte_edits)
+        .map_err(|_| RopeError::OutOfBounds { edit: edits.len() })
+}
+
+pub fn apply_to_rope(patch: &Patch, rope: &mut Rope) -> Result<(), RopeError> {
+    for edit in to_rope_edits(patch, rope)? {
+        match edit {
+            RopeEdit::Insert { char_idx, text } => rope.insert(char_idx, &text),
+            RopeEdit::Remove { start, end } => rope.remove(start..end),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod rope_tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn apply_to_rope() {
+        let source = fs::read_to_string("files/source.txt").unwrap();
+        let target = fs::read_to_string("files/target.txt").unwrap();
+        let patch = Patch::new(source.as_bytes(), target.as_bytes());
+        let mut rope = Rope::from_str(&source);
+        super::apply_to_rope(&patch, &mut rope).unwrap();
+        assert_eq!(rope.to_string(), target);
+    }
+
+    #[test]
+    fn apply_to_rope_multibyte() {
+        let pairs = [
+            ("café au lait", "cafè au lait"),
+            ("naïve €5", "naive ¥5 ✓"),
+            ("日本語", "日本人"),
+            ("", "😀"),
+            ("😀 smile", " smile"),
+        ];
+        for (source, target) in pairs {
+            let patch = Patch::new(source.as_bytes(), target.as_bytes());
+            let mut rope = Rope::from_str(source);
+            super::apply_to_rope(&patch, &mut rope).unwrap();
+            assert_eq!(rope.to_string(), target);
+        }
+    }
+
+    #[test]
+    fn apply_to_rope_err() {
+        let patch = Patch::new(b"AAA", &[0xff]);
+        assert_eq!(
+            super::apply_to_rope(&patch, &mut Rope::from_str("AAA")),
+            Err(RopeError::InvalidUtf8)
+        );
+        assert_eq!(
+            super::apply_to_rope(&patch, &mut Rope::from_str("AA")),
+            Err(RopeError::SourceLengthMismatch)
+        );
+    }
+
+    #[test]
+    fn from_rope_edits() {
+        let rope = Rope::from_str("héllo wörld");
+        let edits = vec![
+            RopeEdit::Remove { start: 0, end: 5 },
+            RopeEdit::Insert {
+                char_idx: 0,
+                text: String::from("grüß"),
+            },
+            RopeEdit::Insert {
+                char_idx: 10,
+                text: String::from("!"),
+            },
+        ];
+        let patch = super::from_rope_edits(&rope, &edits).unwrap();
+        assert_eq!(
+            patch.apply("héllo wörld".as_bytes()).unwrap(),
+            "grüß wörld!".as_bytes()
+        );
+        assert_eq!(
+            super::to_rope_edits(&patch, &rope)
+                .map(|edits| super::from_rope_edits(&rope, &edits).unwrap())
+                .unwrap()
+                .apply("héllo wörld".as_bytes())
+                .unwrap(),
+            "grüß wörld!".as_bytes()
+        );
+
+        assert_eq!(
+            super::from_rope_edits(&rope, &[RopeEdit::Remove { start: 3, end: 20 }]),
+            Err(RopeError::OutOfBounds { edit: 0 })
+        );
+    }
+}
@@ -0,0 +1,301 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, Read, Write},
+    os::fd::FromRawFd,
+    time::{Duration, Instant},
+};
+
+use crate::{instructions::InstructionError, ApplyError};
+
+const STATUS_OK: u8 = 0;
+const STATUS_ERR: u8 = 1;
+const FRAME_HEADER_LENGTH: usize = 9;
+const INSTRUCTION_ERRORS: [InstructionError; 7] = [
+    InstructionError::ContentOverflow,
+    InstructionError::MissignSign,
+    InstructionError::InvalidSign,
+    InstructionError::MissingLength,
+    InstructionError::InvalidLength,
+    InstructionError::MissingContent,
+    InstructionError::InvalidContent,
+];
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SandboxLimits {
+    /// Size of the output buffer, allocated before forking. The child allocates nothing.
+    pub max_target_length: usize,
+    pub timeout: Duration,
+}
+
+impl Default for SandboxLimits {
+    fn default() -> Self {
+        Self {
+            max_target_length: 64 * 1024 * 1024,
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SandboxError {
+    Io(io::Error),
+    Apply(ApplyError),
+    TimedOut,
+    /// The child died without reporting a result, with its exit code or signal.
+    Crashed(String),
+    /// The child's report was cut short or malformed.
+    InvalidReport,
+}
+
+impl std::fmt::Display for SandboxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SandboxError::Io(err) => write!(f, "Sandbox I/O failed: {}", err),
+            SandboxError::Apply(err) => write!(f, "Sandboxed apply failed: {}", err),
+            SandboxError::TimedOut => write!(f, "Sandboxed apply timed out"),
+            SandboxError::Crashed(status) => write!(f, "Sandboxed apply crashed: {}", status),
+            SandboxError::InvalidReport => write!(f, "Sandbox returned an invalid report"),
+        }
+    }
+}
+
+impl Error for SandboxError {}
+
+impl From<io::Error> for SandboxError {
+    fn from(err: io::Error) -> Self {
+        SandboxError::Io(err)
+    }
+}
+
+fn encode_error(error: &ApplyError) -> (u8, u64) {
+    match error {
+        ApplyError::SourceMismatch => (0, 0),
+        ApplyError::OutputTooSmall(length) => (1, *length as u64),
+        ApplyError::InvalidPatch(error) => (
+            2 + INSTRUCTION_ERRORS
+                .iter()
+                .position(|known| known == error)
+                .unwrap() as u8,
+            0,
+        ),
+    }
+}
+
+fn decode_error(code: u8, value: u64) -> Option<ApplyError> {
+    match code {
+        0 => Some(ApplyError::SourceMismatch),
+        1 => Some(ApplyError::OutputTooSmall(value as usize)),
+        code => INSTRUCTION_ERRORS
+            .get(code as usize - 2)
+            .cloned()
+            .map(ApplyError::InvalidPatch),
+    }
+}
+
+/// Runs in the forked child. Once seccomp strict mode is on the child can only read, write
+/// and exit, so a bug in the patch parser can't reach anything but the output pipe. `out` is
+/// allocated by the parent, since allocating after fork can deadlock a multithreaded parent.
+fn child(pipe: i32, source: &[u8], patch: &[u8], out: &mut [u8]) -> ! {
+    let mut pipe = unsafe { File::from_raw_fd(pipe) };
+    let strict = unsafe {
+        libc::prctl(
+            libc::PR_SET_SECCOMP,
+            libc::SECCOMP_MODE_STRICT as libc::c_ulong,
+        )
+    };
+    let mut header = [0u8; FRAME_HEADER_LENGTH];
+    let body = match (strict, crate::apply_no_alloc(source, patch, &mut *out)) {
+        (0, Ok(length)) => {
+            header[0] = STATUS_OK;
+            header[1..].copy_from_slice(&(length as u64).to_be_bytes());
+            &out[..length]
+        }
+        (0, Err(error)) => {
+            let (code, value) = encode_error(&error);
+            header[0] = STATUS_ERR + code;
+            header[1..].copy_from_slice(&value.to_be_bytes());
+            &out[..0]
+        }
+        _ => unsafe { libc::_exit(2) },
+    };
+    let code = match pipe.write_all(&header).and_then(|_| pipe.write_all(body)) {
+        Ok(_) => 0,
+        Err(_) => 1,
+    };
+    // exit_group, which `_exit` uses, isn't allowed in strict mode.
+    unsafe {
+        libc::syscall(libc::SYS_exit, code);
+    }
+    unreachable!()
+}
+
+fn read_report(pipe: &mut File, deadline: Instant) -> Result<Vec<u8>, SandboxError> {
+    let mut report: Vec<u8> = Vec::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(SandboxError::TimedOut);
+        }
+        let mut poll = libc::pollfd {
+            fd: std::os::fd::AsRawFd::as_raw_fd(pipe),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout = remaining.as_millis().min(i32::MAX as u128) as i32;
+        match unsafe { libc::poll(&mut poll, 1, timeout) } {
+            0 => return Err(SandboxError::TimedOut),
+            -1 => {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err.into());
+                }
+            }
+            _ => match pipe.read(&mut buffer) {
+                Ok(0) => return Ok(report),
+                Ok(read) => report.extend_from_slice(&buffer[..read]),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+                Err(err) => return Err(err.into()),
+            },
+        }
+    }
+}
+
+fn wait(pid: libc::pid_t) -> Result<(), SandboxError> {
+    let mut status = 0;
+    while unsafe { libc::waitpid(pid, &mut status, 0) } < 0 {
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err.into());
+        }
+    }
+    match (libc::WIFEXITED(status), libc::WIFSIGNALED(status)) {
+        (true, _) if libc::WEXITSTATUS(status) == 0 => Ok(()),
+        (true, _) => Err(SandboxError::Crashed(format!(
+            "exit code {}",
+            libc::WEXITSTATUS(status)
+        ))),
+        (_, true) => Err(SandboxError::Crashed(format!(
+            "signal {}",
+            libc::WTERMSIG(status)
+        ))),
+        _ => Err(SandboxError::Crashed(format!("status {}", status))),
+    }
+}
+
+/// Applies an untrusted `patch` in a forked child restricted to seccomp strict mode.
+///
+/// The child decodes and applies the patch with [`crate::apply_no_alloc`] into a buffer of
+/// [`SandboxLimits::max_target_length`] bytes allocated before forking, and reports back over
+/// a pipe. It is killed once [`SandboxLimits::timeout`] passes. Only the reported target
+/// crosses back into this process, after its length and the child's exit status are checked.
+pub fn apply_sandboxed(
+    source: &[u8],
+    patch: &[u8],
+    limits: &SandboxLimits,
+) -> Result<Vec<u8>, SandboxError> {
+    let deadline = Instant::now() + limits.timeout;
+    let mut out = vec![0u8; limits.max_target_length];
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    let pid = unsafe { libc::fork() };
+    if pid == 0 {
+        unsafe { libc::close(fds[0]) };
+        child(fds[1], source, patch, &mut out);
+    }
+    unsafe { libc::close(fds[1]) };
+    let mut pipe = unsafe { File::from_raw_fd(fds[0]) };
+    if pid < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    let report = match read_report(&mut pipe, deadline) {
+        Ok(report) => report,
+        Err(err) => {
+            unsafe { libc::kill(pid, libc::SIGKILL) };
+            let _ = wait(pid);
+            return Err(err);
+        }
+    };
+    wait(pid)?;
+
+    if report.len() < FRAME_HEADER_LENGTH {
+        return Err(SandboxError::InvalidReport);
+    }
+    let value = u64::from_be_bytes(report[1..FRAME_HEADER_LENGTH].try_into().unwrap());
+    let body = &report[FRAME_HEADER_LENGTH..];
+    match report[0] {
+        STATUS_OK if body.len() as u64 == value => Ok(body.to_vec()),
+        STATUS_OK => Err(SandboxError::InvalidReport),
+        status if body.is_empty() => decode_error(status - STATUS_ERR, value)
+            .map(SandboxError::Apply)
+            .map_or(Err(SandboxError::InvalidReport), Err),
+        _ => Err(SandboxError::InvalidReport),
+    }
+}
+
+#[cfg(test)]
+mod sandbox_tests {
+    use std::fs;
+
+    use super::*;
+    use crate::patch::Patch;
+
+    #[test]
+    fn apply_sandboxed() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new_with_target_copies(&source, &target).to_bytes();
+        let limits = SandboxLimits::default();
+        assert_eq!(
+            super::apply_sandboxed(&source, &patch, &limits).unwrap(),
+            target
+        );
+        assert!(matches!(
+            super::apply_sandboxed(&source[1..], &patch, &limits),
+            Err(SandboxError::Apply(ApplyError::SourceMismatch))
+        ));
+        assert!(matches!(
+            super::apply_sandboxed(&source, b"+\x05ab", &limits),
+            Err(SandboxError::Apply(ApplyError::InvalidPatch(
+                InstructionError::MissingContent
+            )))
+        ));
+        let small = SandboxLimits {
+            max_target_length: 10,
+            ..Default::default()
+        };
+        assert!(matches!(
+            super::apply_sandboxed(&source, &patch, &small),
+            Err(SandboxError::Apply(ApplyError::OutputTooSmall(length))) if length == target.len()
+        ));
+    }
+
+    #[test]
+    fn apply_sandboxed_timeout() {
+        let limits = SandboxLimits {
+            max_target_length: 16,
+            timeout: Duration::ZERO,
+        };
+        assert!(matches!(
+            super::apply_sandboxed(b"", b"", &limits),
+            Err(SandboxError::TimedOut)
+        ));
+    }
+
+    #[test]
+    fn error_codes() {
+        for error in INSTRUCTION_ERRORS
+            .iter()
+            .cloned()
+            .map(ApplyError::InvalidPatch)
+            .chain([ApplyError::SourceMismatch, ApplyError::OutputTooSmall(42)])
+        {
+            let (code, value) = encode_error(&error);
+            assert_eq!(decode_error(code, value), Some(error));
+        }
+        assert_eq!(decode_error(2 + INSTRUCTION_ERRORS.len() as u8, 0), None);
+    }
+}
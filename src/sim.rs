@@ -0,0 +1,219 @@
+use std::collections::BTreeSet;
+
+use crate::{
+    flash::{FlashLayout, SectorPatch},
+    patch::Patch,
+    ApplyError,
+};
+
+const ERASED: u8 = 0xff;
+
+#[derive(Debug, PartialEq)]
+enum SimError {
+    SectorTooLarge { sector: usize, length: usize },
+    PatchTooLarge { sector: usize, length: usize },
+    Apply { sector: usize, error: ApplyError },
+    WriteFailed { sector: usize },
+}
+
+struct SimFlash {
+    bytes: Vec<u8>,
+    layout: FlashLayout,
+    failing_writes: BTreeSet<usize>,
+    writes: usize,
+}
+
+impl SimFlash {
+    fn new(contents: &[u8], layout: FlashLayout) -> Self {
+        let mut bytes = contents.to_vec();
+        bytes.resize(layout.len(), ERASED);
+        Self {
+            bytes,
+            layout,
+            failing_writes: BTreeSet::new(),
+            writes: 0,
+        }
+    }
+
+    /// Erases and programs a sector. A failing write leaves the sector half programmed.
+    fn program(&mut self, sector: usize, data: &[u8]) -> Result<(), ()> {
+        let range = self.layout.sector_range(sector).unwrap();
+        let write = self.writes;
+        self.writes += 1;
+        self.bytes[range.clone()].fill(ERASED);
+        let length = match self.failing_writes.contains(&write) {
+            true => data.len() / 2,
+            false => data.len(),
+        };
+        self.bytes[range.start..range.start + length].copy_from_slice(&data[..length]);
+        match length == data.len() {
+            true => Ok(()),
+            false => Err(()),
+        }
+    }
+}
+
+/// A bootloader stand-in that can buffer one `SECTOR` byte sector and receive one `RECEIVE`
+/// byte sub-patch at a time, installing [`Patch::plan_sectors`] output without allocating.
+struct SimDevice<const SECTOR: usize, const RECEIVE: usize> {
+    flash: SimFlash,
+    retries: usize,
+}
+
+impl<const SECTOR: usize, const RECEIVE: usize> SimDevice<SECTOR, RECEIVE> {
+    fn install(&mut self, plan: &[SectorPatch]) -> Result<(), SimError> {
+        let mut received = [0u8; RECEIVE];
+        let mut window = [0u8; SECTOR];
+        for sector_patch in plan {
+            let sector = sector_patch.sector;
+            let capacity = self.flash.layout.sector_range(sector).unwrap().len();
+            let window = window.get_mut(..capacity).ok_or(SimError::SectorTooLarge {
+                sector,
+                length: capacity,
+            })?;
+            // Serialising stands in for the transport; only the device side has to be
+            // allocation free.
+            let bytes = sector_patch.patch.to_bytes();
+            let received = received
+                .get_mut(..bytes.len())
+                .ok_or(SimError::PatchTooLarge {
+                    sector,
+                    length: bytes.len(),
+                })?;
+            received.copy_from_slice(&bytes);
+
+            let source = &self.flash.bytes[sector_patch.source_range.clone()];
+            let length = crate::apply_no_alloc(source, received, window)
+                .map_err(|error| SimError::Apply { sector, error })?;
+            let mut attempts = 0;
+            while self.flash.program(sector, &window[..length]).is_err() {
+                attempts += 1;
+                if attempts > self.retries {
+                    return Err(SimError::WriteFailed { sector });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod sim_tests {
+    use super::*;
+    use crate::encode::EncodeAlgorithm;
+
+    fn firmware() -> (Vec<u8>, Vec<u8>) {
+        let source: Vec<u8> = (0..900u32).map(|item| (item * 13 % 241) as u8).collect();
+        let mut target = source.clone();
+        target.splice(40..40, b"version 2.1.0 build 7".iter().copied());
+        target.drain(500..560);
+        target[300..310].copy_from_slice(b"calibrated");
+        target.extend(b"\x00\x01\x02\x03".repeat(30));
+        (source, target)
+    }
+
+    fn encoded(source: &[u8], target: &[u8]) -> Vec<Patch> {
+        vec![
+            Patch::encode_with(source, target, EncodeAlgorithm::Lcs),
+            Patch::encode_with(source, target, EncodeAlgorithm::BlockHash),
+            Patch::encode_with(source, target, EncodeAlgorithm::Append),
+            Patch::new_with_target_copies(source, target),
+        ]
+    }
+
+    #[test]
+    fn install() {
+        let (source, target) = firmware();
+        let layout = FlashLayout::new(vec![128, 128, 256, 256, 256]);
+        for patch in encoded(&source, &target) {
+            let plan = patch.plan_sectors(&source, &layout).unwrap();
+            let mut device: SimDevice<256, 512> = SimDevice {
+                flash: SimFlash::new(&source, layout.clone()),
+                retries: 0,
+            };
+            assert_eq!(device.install(&plan), Ok(()));
+            assert_eq!(device.flash.bytes[..target.len()], target[..]);
+            assert_eq!(device.flash.writes, plan.len());
+        }
+    }
+
+    #[test]
+    fn install_write_failures() {
+        let (source, target) = firmware();
+        let layout = FlashLayout::uniform(64, 20);
+        let patch = Patch::encode_with(&source, &target, EncodeAlgorithm::BlockHash);
+        let plan = patch.plan_sectors(&source, &layout).unwrap();
+
+        let mut device: SimDevice<64, 256> = SimDevice {
+            flash: SimFlash::new(&source, layout.clone()),
+            retries: 2,
+        };
+        device.flash.failing_writes = BTreeSet::from([0, 3, 4, 9]);
+        assert_eq!(device.install(&plan), Ok(()));
+        assert_eq!(device.flash.bytes[..target.len()], target[..]);
+        assert_eq!(device.flash.writes, plan.len() + 4);
+
+        let mut device: SimDevice<64, 256> = SimDevice {
+            flash: SimFlash::new(&source, layout),
+            retries: 1,
+        };
+        device.flash.failing_writes = BTreeSet::from([2, 3]);
+        assert_eq!(
+            device.install(&plan),
+            Err(SimError::WriteFailed {
+                sector: plan[2].sector
+            })
+        );
+    }
+
+    #[test]
+    fn install_tiny_windows() {
+        let (source, target) = firmware();
+        let patch = Patch::encode_with(&source, &target, EncodeAlgorithm::BlockHash);
+        let layout = FlashLayout::new(vec![64, 512, 512]);
+        let plan = patch.plan_sectors(&source, &layout).unwrap();
+        let mut device: SimDevice<256, 1024> = SimDevice {
+            flash: SimFlash::new(&source, layout),
+            retries: 0,
+        };
+        assert_eq!(
+            device.install(&plan),
+            Err(SimError::SectorTooLarge {
+                sector: plan.iter().find(|sector| sector.sector > 0).unwrap().sector,
+                length: 512
+            })
+        );
+
+        let layout = FlashLayout::uniform(32, 40);
+        let plan = patch.plan_sectors(&source, &layout).unwrap();
+        let mut device: SimDevice<32, 16> = SimDevice {
+            flash: SimFlash::new(&source, layout),
+            retries: 0,
+        };
+        assert!(matches!(
+            device.install(&plan),
+            Err(SimError::PatchTooLarge { length, .. }) if length > 16
+        ));
+    }
+
+    #[test]
+    fn install_corrupted_patch() {
+        let (source, target) = firmware();
+        let layout = FlashLayout::uniform(128, 8);
+        let patch = Patch::encode_with(&source, &target, EncodeAlgorithm::BlockHash);
+        let mut plan = patch.plan_sectors(&source, &layout).unwrap();
+        plan[1].source_range.end -= 1;
+        let mut device: SimDevice<128, 512> = SimDevice {
+            flash: SimFlash::new(&source, layout),
+            retries: 0,
+        };
+        assert_eq!(
+            device.install(&plan),
+            Err(SimError::Apply {
+                sector: plan[1].sector,
+                error: ApplyError::SourceMismatch
+            })
+        );
+        assert_eq!(device.flash.writes, 1);
+    }
+}
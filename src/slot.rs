@@ -0,0 +1,317 @@
+use std::{
+    error::Error,
+    io::{self, Read, Seek, SeekFrom, Write},
+};
+
+use crate::{
+    instructions::{delta_instruction::DeltaInstruction, InstructionContent, InstructionInfo},
+    patch::Patch,
+};
+
+pub const RESUME_TOKEN_LENGTH: usize = 24;
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct ResumeToken {
+    pub instruction: u64,
+    pub source_offset: u64,
+    pub target_offset: u64,
+}
+
+impl ResumeToken {
+    pub fn to_bytes(&self) -> [u8; RESUME_TOKEN_LENGTH] {
+        let mut bytes = [0u8; RESUME_TOKEN_LENGTH];
+        bytes[..8].copy_from_slice(&self.instruction.to_be_bytes());
+        bytes[8..16].copy_from_slice(&self.source_offset.to_be_bytes());
+        bytes[16..].copy_from_slice(&self.target_offset.to_be_bytes());
+        bytes
+    }
+
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, SlotError> {
+        if bytes.len() != RESUME_TOKEN_LENGTH {
+            return Err(SlotError::InvalidResumeToken);
+        }
+        let field = |index: usize| u64::from_be_bytes(bytes[index..index + 8].try_into().unwrap());
+        Ok(Self {
+            instruction: field(0),
+            source_offset: field(8),
+            target_offset: field(16),
+        })
+    }
+
+    fn validate(&self, patch: &Patch) -> Result<(), SlotError> {
+        let instructions = patch.instructions();
+        if self.instruction > instructions.len() as u64 {
+            return Err(SlotError::InvalidResumeToken);
+        }
+        let mut expected = ResumeToken::default();
+        for instruction in instructions.iter().take(self.instruction as usize) {
+            expected.advance(instruction);
+        }
+        if expected != *self {
+            return Err(SlotError::InvalidResumeToken);
+        }
+        Ok(())
+    }
+
+    fn advance(&mut self, instruction: &DeltaInstruction) {
+        let length = instruction.len() as u64;
+        match instruction {
+            DeltaInstruction::Remove(_) => self.source_offset += length,
+            DeltaInstruction::Add(_) | DeltaInstruction::TargetCopy(_) => {
+                self.target_offset += length
+            }
+            DeltaInstruction::Copy(_) => {
+                self.source_offset += length;
+                self.target_offset += length;
+            }
+        }
+        self.instruction += 1;
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SlotProgress {
+    pub token: ResumeToken,
+    pub instruction_count: u64,
+    pub target_length: u64,
+}
+
+#[derive(Debug)]
+pub enum SlotError {
+    Io(io::Error),
+    SourceLengthMismatch,
+    InvalidResumeToken,
+    VerificationFailed { target_offset: u64 },
+}
+
+impl std::fmt::Display for SlotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SlotError::Io(err) => write!(f, "Slot I/O failed: {}", err),
+            SlotError::SourceLengthMismatch => {
+                write!(f, "Source length doesn't match the patch source length")
+            }
+            SlotError::InvalidResumeToken => {
+                write!(f, "Resume token doesn't belong to this patch")
+            }
+            SlotError::VerificationFailed { target_offset } => write!(
+                f,
+                "Slot contents didn't match the written bytes at offset {}",
+                target_offset
+            ),
+        }
+    }
+}
+
+impl Error for SlotError {}
+
+impl From<io::Error> for SlotError {
+    fn from(err: io::Error) -> Self {
+        SlotError::Io(err)
+    }
+}
+
+pub fn apply_to_slot<R, W, F>(
+    source: &mut R,
+    patch: &Patch,
+    slot: &mut W,
+    resume: Option<ResumeToken>,
+    mut progress: F,
+) -> Result<(), SlotError>
+where
+    R: Read + Seek,
+    W: Read + Write + Seek,
+    F: FnMut(SlotProgress),
+{
+    if source.seek(SeekFrom::End(0))? != patch.source_length() as u64 {
+        return Err(SlotError::SourceLengthMismatch);
+    }
+    let mut token = resume.unwrap_or_default();
+    token.validate(patch)?;
+    source.seek(SeekFrom::Start(token.source_offset))?;
+    slot.seek(SeekFrom::Start(token.target_offset))?;
+
+    let instruction_count = patch.instructions().len() as u64;
+    let target_length = patch.target_length() as u64;
+    let mut source_buffer: Vec<u8> = Vec::with_capacity(u8::MAX as usize);
+    let mut target_buffer: Vec<u8> = Vec::with_capacity(u8::MAX as usize);
+    let mut verify_buffer: Vec<u8> = vec![0; u8::MAX as usize];
+    for instruction in patch.instructions().iter().skip(token.instruction as usize) {
+        source_buffer.clear();
+        target_buffer.clear();
+        match instruction {
+            DeltaInstruction::Remove(_) => {
+                source.seek(SeekFrom::Current(instruction.len() as i64))?;
+            }
+            DeltaInstruction::Add(_) => (),
+            DeltaInstruction::Copy(_) => {
+                source_buffer.resize(instruction.len() as usize, 0);
+                source.read_exact(&mut source_buffer)?;
+            }
+            DeltaInstruction::TargetCopy(target_copy) => {
+                // Only the bytes the copy reads are loaded, so the copy repeats them here
+                // instead of indexing back by the full distance.
+                let distance = target_copy.distance() as u64;
+                let history = distance.min(instruction.len() as u64) as usize;
+                target_buffer.resize(history, 0);
+                slot.seek(SeekFrom::Start(token.target_offset - distance))?;
+                slot.read_exact(&mut target_buffer)?;
+                slot.seek(SeekFrom::Start(token.target_offset))?;
+                for index in 0..instruction.len() as usize {
+                    target_buffer.push(target_buffer[index % history]);
+                }
+                target_buffer.drain(..history);
+            }
+        }
+        if !matches!(instruction, DeltaInstruction::TargetCopy(_)) {
+            instruction.apply(&mut source_buffer.iter(), &mut target_buffer);
+        }
+
+        if !target_buffer.is_empty() {
+            slot.write_all(&target_buffer)?;
+            slot.flush()?;
+            let written = &mut verify_buffer[..target_buffer.len()];
+            slot.seek(SeekFrom::Start(token.target_offset))?;
+            slot.read_exact(written)?;
+            if written != target_buffer.as_slice() {
+                return Err(SlotError::VerificationFailed {
+                    target_offset: token.target_offset,
+                });
+            }
+        }
+
+        token.advance(instruction);
+        progress(SlotProgress {
+            token,
+            instruction_count,
+            target_length,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod slot_tests {
+    use std::{fs, io::Cursor};
+
+    use super::*;
+
+    #[test]
+    fn resume_token_bytes() {
+        let token = ResumeToken {
+            instruction: 1,
+            source_offset: 2,
+            target_offset: u64::MAX,
+        };
+        assert_eq!(
+            ResumeToken::try_from_bytes(&token.to_bytes()).unwrap(),
+            token
+        );
+        assert!(matches!(
+            ResumeToken::try_from_bytes(&[0; RESUME_TOKEN_LENGTH - 1]),
+            Err(SlotError::InvalidResumeToken)
+        ));
+    }
+
+    #[test]
+    fn apply_to_slot() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+        let mut slot = Cursor::new(Vec::new());
+        let mut last_progress = None;
+        super::apply_to_slot(
+            &mut Cursor::new(&source),
+            &patch,
+            &mut slot,
+            None,
+            |progress| last_progress = Some(progress),
+        )
+        .unwrap();
+        assert_eq!(slot.into_inner(), target);
+
+        let last_progress = last_progress.unwrap();
+        assert_eq!(
+            last_progress.token.instruction,
+            last_progress.instruction_count
+        );
+        assert_eq!(
+            last_progress.token.target_offset,
+            last_progress.target_length
+        );
+    }
+
+    #[test]
+    fn apply_to_slot_resume() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+
+        let mut tokens = Vec::new();
+        let mut slot = Cursor::new(Vec::new());
+        super::apply_to_slot(
+            &mut Cursor::new(&source),
+            &patch,
+            &mut slot,
+            None,
+            |progress| tokens.push(progress.token.to_bytes()),
+        )
+        .unwrap();
+
+        let token = ResumeToken::try_from_bytes(&tokens[tokens.len() / 2]).unwrap();
+        let mut interrupted = target[..token.target_offset as usize].to_vec();
+        interrupted.resize(target.len(), u8::MAX);
+        let mut slot = Cursor::new(interrupted);
+        super::apply_to_slot(
+            &mut Cursor::new(&source),
+            &patch,
+            &mut slot,
+            Some(token),
+            |_| (),
+        )
+        .unwrap();
+        assert_eq!(slot.into_inner(), target);
+    }
+
+    #[test]
+    fn apply_to_slot_err() {
+        let patch = Patch::new(b"AAA", b"ABA");
+        let mut slot = Cursor::new(Vec::new());
+        assert!(matches!(
+            super::apply_to_slot(&mut Cursor::new(b"AA"), &patch, &mut slot, None, |_| ()),
+            Err(SlotError::SourceLengthMismatch)
+        ));
+
+        let token = ResumeToken {
+            instruction: 1,
+            source_offset: 0,
+            target_offset: 0,
+        };
+        assert!(matches!(
+            super::apply_to_slot(
+                &mut Cursor::new(b"AAA"),
+                &patch,
+                &mut slot,
+                Some(token),
+                |_| ()
+            ),
+            Err(SlotError::InvalidResumeToken)
+        ));
+    }
+
+    #[test]
+    fn apply_to_slot_target_copies() {
+        let source = b"unrelated".to_vec();
+        let mut target = b"0123456789".repeat(30);
+        target.extend(vec![b'='; 100]);
+        let patch = Patch::new_with_target_copies(&source, &target);
+        let mut slot = Cursor::new(Vec::new());
+        super::apply_to_slot(&mut Cursor::new(&source), &patch, &mut slot, None, |_| ()).unwrap();
+        assert_eq!(slot.into_inner(), target);
+
+        let patch = Patch::try_from_bytes(b"+\x06abcdef<\x02\x00\x00\x00\x05").unwrap();
+        let mut slot = Cursor::new(Vec::new());
+        super::apply_to_slot(&mut Cursor::new(b""), &patch, &mut slot, None, |_| ()).unwrap();
+        assert_eq!(slot.into_inner(), b"abcdefbc");
+    }
+}
@@ -0,0 +1,357 @@
+use crate::patch::Patch;
+
+#[derive(Debug, PartialEq, Clone)]
+enum Entry {
+    Keyframe(Vec<u8>),
+    Delta { patch: Vec<u8>, length: usize },
+}
+
+impl Entry {
+    fn stored_bytes(&self) -> usize {
+        match self {
+            Entry::Keyframe(bytes) => bytes.len(),
+            Entry::Delta { patch, .. } => patch.len(),
+        }
+    }
+
+    fn length(&self) -> usize {
+        match self {
+            Entry::Keyframe(bytes) => bytes.len(),
+            Entry::Delta { length, .. } => *length,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct KeyframePolicy {
+    pub interval: Option<usize>,
+    pub max_chain_bytes: Option<usize>,
+    pub max_delta_ratio: Option<f64>,
+}
+
+impl Default for KeyframePolicy {
+    fn default() -> Self {
+        Self {
+            interval: Some(16),
+            max_chain_bytes: None,
+            max_delta_ratio: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct SnapshotStats {
+    pub versions: usize,
+    pub keyframes: usize,
+    pub deltas: usize,
+    pub stored_bytes: usize,
+    pub raw_bytes: usize,
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Snapshotter {
+    entries: Vec<Entry>,
+    latest: Vec<u8>,
+    policy: KeyframePolicy,
+}
+
+impl Snapshotter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_policy(policy: KeyframePolicy) -> Self {
+        Self {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    pub fn policy(&self) -> &KeyframePolicy {
+        &self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: KeyframePolicy) {
+        self.policy = policy;
+    }
+
+    fn last_keyframe(&self, before: usize) -> Option<usize> {
+        self.entries[..before]
+            .iter()
+            .rposition(|entry| matches!(entry, Entry::Keyframe(_)))
+    }
+
+    fn needs_keyframe(&self, patch: &[u8], length: usize) -> bool {
+        let Some(keyframe) = self.last_keyframe(self.entries.len()) else {
+            return true;
+        };
+        let chain = self.entries.len() - keyframe;
+        if self
+            .policy
+            .interval
+            .is_some_and(|interval| chain >= interval)
+        {
+            return true;
+        }
+        let chain_bytes: usize = self.entries[keyframe + 1..]
+            .iter()
+            .map(Entry::stored_bytes)
+            .sum::<usize>()
+            + patch.len();
+        if self
+            .policy
+            .max_chain_bytes
+            .is_some_and(|max| chain_bytes > max)
+        {
+            return true;
+        }
+        self.policy
+            .max_delta_ratio
+            .is_some_and(|ratio| patch.len() as f64 > ratio * length as f64)
+    }
+
+    pub fn push(&mut self, version: &[u8]) -> usize {
+        let patch = Patch::new(&self.latest, version).to_bytes();
+        let entry = match self.needs_keyframe(&patch, version.len()) {
+            true => Entry::Keyframe(version.to_vec()),
+            false => Entry::Delta {
+                patch,
+                length: version.len(),
+            },
+        };
+        self.entries.push(entry);
+        self.latest = version.to_vec();
+        self.entries.len() - 1
+    }
+
+    pub fn push_keyframe(&mut self, version: &[u8]) -> usize {
+        self.entries.push(Entry::Keyframe(version.to_vec()));
+        self.latest = version.to_vec();
+        self.entries.len() - 1
+    }
+
+    pub fn force_keyframe(&mut self, version: usize) -> Option<()> {
+        let bytes = self.get(version)?;
+        self.entries[version] = Entry::Keyframe(bytes);
+        Some(())
+    }
+
+    pub fn is_keyframe(&self, version: usize) -> Option<bool> {
+        self.entries
+            .get(version)
+            .map(|entry| matches!(entry, Entry::Keyframe(_)))
+    }
+
+    pub fn keyframes(&self) -> Vec<usize> {
+        (0..self.entries.len())
+            .filter(|version| self.is_keyframe(*version) == Some(true))
+            .collect()
+    }
+
+    pub fn chain_length(&self, version: usize) -> Option<usize> {
+        if version >= self.entries.len() {
+            return None;
+        }
+        Some(version - self.last_keyframe(version + 1)?)
+    }
+
+    pub fn get(&self, version: usize) -> Option<Vec<u8>> {
+        if version >= self.entries.len() {
+            return None;
+        }
+        if version == self.entries.len() - 1 {
+            return Some(self.latest.clone());
+        }
+        let keyframe = self.last_keyframe(version + 1)?;
+        let mut buffer = match &self.entries[keyframe] {
+            Entry::Keyframe(bytes) => bytes.clone(),
+            Entry::Delta { .. } => unreachable!(),
+        };
+        for entry in &self.entries[keyframe + 1..=version] {
+            if let Entry::Delta { patch, .. } = entry {
+                buffer = Patch::try_from_bytes(patch).ok()?.apply(&buffer)?;
+            }
+        }
+        Some(buffer)
+    }
+
+    pub fn latest(&self) -> Option<&[u8]> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        Some(&self.latest)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.entries.len() {
+            return;
+        }
+        self.latest = match len {
+            0 => Vec::new(),
+            _ => self.get(len - 1).unwrap(),
+        };
+        self.entries.truncate(len);
+    }
+
+    pub fn stats(&self) -> SnapshotStats {
+        self.entries
+            .iter()
+            .fold(SnapshotStats::default(), |mut stats, entry| {
+                match entry {
+                    Entry::Keyframe(_) => stats.keyframes += 1,
+                    Entry::Delta { .. } => stats.deltas += 1,
+                }
+                stats.versions += 1;
+                stats.stored_bytes += entry.stored_bytes();
+                stats.raw_bytes += entry.length();
+                stats
+            })
+    }
+}
+
+#[cfg(test)]
+mod snapshot_tests {
+    use super::*;
+
+    fn versions(count: usize) -> Vec<Vec<u8>> {
+        let mut version = b"The quick brown fox jumps over the lazy dog.".to_vec();
+        (0..count)
+            .map(|index| {
+                let position = index % version.len();
+                version[position] = b'0' + (index % 10) as u8;
+                version.clone()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn push_get() {
+        let versions = versions(40);
+        let mut snapshotter = Snapshotter::new();
+        for (index, version) in versions.iter().enumerate() {
+            assert_eq!(snapshotter.push(version), index);
+        }
+        assert_eq!(snapshotter.len(), versions.len());
+        for (index, version) in versions.iter().enumerate() {
+            assert_eq!(snapshotter.get(index).as_ref(), Some(version));
+        }
+        assert_eq!(snapshotter.get(versions.len()), None);
+        assert_eq!(snapshotter.latest(), versions.last().map(|v| v.as_slice()));
+    }
+
+    #[test]
+    fn truncate() {
+        let versions = versions(20);
+        let mut snapshotter = Snapshotter::new();
+        for version in versions.iter() {
+            snapshotter.push(version);
+        }
+        snapshotter.truncate(18);
+        assert_eq!(snapshotter.len(), 18);
+        assert_eq!(snapshotter.latest(), Some(versions[17].as_slice()));
+
+        snapshotter.push(b"replacement");
+        assert_eq!(snapshotter.get(18), Some(b"replacement".to_vec()));
+        assert_eq!(snapshotter.get(17), Some(versions[17].clone()));
+
+        snapshotter.truncate(0);
+        assert!(snapshotter.is_empty());
+        assert_eq!(snapshotter.latest(), None);
+    }
+
+    #[test]
+    fn stats() {
+        let versions = versions(20);
+        let mut snapshotter = Snapshotter::new();
+        for version in versions.iter() {
+            snapshotter.push(version);
+        }
+        let stats = snapshotter.stats();
+        assert_eq!(stats.versions, 20);
+        assert_eq!(stats.keyframes, 2);
+        assert_eq!(stats.deltas, 18);
+        assert_eq!(
+            stats.raw_bytes,
+            versions.iter().map(Vec::len).sum::<usize>()
+        );
+        let deltas: usize = (1..versions.len())
+            .filter(|index| *index != 16)
+            .map(|index| {
+                Patch::new(&versions[index - 1], &versions[index])
+                    .to_bytes()
+                    .len()
+            })
+            .sum();
+        assert_eq!(
+            stats.stored_bytes,
+            versions[0].len() + versions[16].len() + deltas
+        );
+    }
+
+    #[test]
+    fn keyframe_policy() {
+        let versions = versions(20);
+        let mut snapshotter = Snapshotter::with_policy(KeyframePolicy {
+            interval: Some(5),
+            ..Default::default()
+        });
+        for version in versions.iter() {
+            snapshotter.push(version);
+        }
+        assert_eq!(snapshotter.keyframes(), vec![0, 5, 10, 15]);
+        assert_eq!(snapshotter.chain_length(13), Some(3));
+        assert_eq!(snapshotter.chain_length(20), None);
+
+        let patch_length = Patch::new(&versions[0], &versions[1]).to_bytes().len();
+        let mut snapshotter = Snapshotter::with_policy(KeyframePolicy {
+            interval: None,
+            max_chain_bytes: Some(patch_length * 3),
+            max_delta_ratio: None,
+        });
+        for version in versions.iter() {
+            snapshotter.push(version);
+        }
+        assert_eq!(snapshotter.keyframes(), vec![0, 4, 8, 12, 16]);
+
+        let mut snapshotter = Snapshotter::with_policy(KeyframePolicy {
+            interval: None,
+            max_chain_bytes: None,
+            max_delta_ratio: Some(0.5),
+        });
+        snapshotter.push(&versions[0]);
+        snapshotter.push(&versions[1]);
+        snapshotter.push(&[0xAA; 64]);
+        assert_eq!(snapshotter.keyframes(), vec![0, 1, 2]);
+        for (index, version) in versions.iter().enumerate() {
+            snapshotter.push(version);
+            assert_eq!(snapshotter.get(index + 3).as_ref(), Some(version));
+        }
+    }
+
+    #[test]
+    fn force_keyframe() {
+        let versions = versions(10);
+        let mut snapshotter = Snapshotter::new();
+        for version in versions.iter() {
+            snapshotter.push(version);
+        }
+        assert_eq!(snapshotter.is_keyframe(6), Some(false));
+        assert_eq!(snapshotter.force_keyframe(6), Some(()));
+        assert_eq!(snapshotter.is_keyframe(6), Some(true));
+        assert_eq!(snapshotter.chain_length(9), Some(3));
+        assert_eq!(snapshotter.force_keyframe(10), None);
+        assert_eq!(snapshotter.push_keyframe(&versions[9]), 10);
+        assert_eq!(snapshotter.keyframes(), vec![0, 6, 10]);
+        for (index, version) in versions.iter().enumerate() {
+            assert_eq!(snapshotter.get(index).as_ref(), Some(version));
+        }
+    }
+}
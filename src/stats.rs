@@ -0,0 +1,295 @@
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    instructions::{delta_instruction::DeltaInstruction, InstructionInfo},
+    patch::Patch,
+};
+
+pub const CSV_HEADER: &str = "name,source_length,target_length,patch_length,removes,adds,copies,target_copies,removed_bytes,added_bytes,copied_bytes,target_copied_bytes,encode_ns,apply_ns";
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct PatchStats {
+    pub name: String,
+    pub source_length: usize,
+    pub target_length: usize,
+    pub patch_length: usize,
+    pub removes: usize,
+    pub adds: usize,
+    pub copies: usize,
+    pub target_copies: usize,
+    pub removed_bytes: usize,
+    pub added_bytes: usize,
+    pub copied_bytes: usize,
+    pub target_copied_bytes: usize,
+    pub encode_duration: Option<Duration>,
+    pub apply_duration: Option<Duration>,
+}
+
+impl PatchStats {
+    pub fn new(name: impl Into<String>, patch: &Patch) -> Self {
+        let mut stats = Self {
+            name: name.into(),
+            source_length: patch.source_length(),
+            target_length: patch.target_length(),
+            patch_length: patch.byte_length(),
+            ..Default::default()
+        };
+        for instruction in patch.instructions() {
+            let length = instruction.len() as usize;
+            let (count, bytes) = match instruction {
+                DeltaInstruction::Remove(_) => (&mut stats.removes, &mut stats.removed_bytes),
+                DeltaInstruction::Add(_) => (&mut stats.adds, &mut stats.added_bytes),
+                DeltaInstruction::Copy(_) => (&mut stats.copies, &mut stats.copied_bytes),
+                DeltaInstruction::TargetCopy(_) => {
+                    (&mut stats.target_copies, &mut stats.target_copied_bytes)
+                }
+            };
+            *count += 1;
+            *bytes += length;
+        }
+        stats
+    }
+
+    pub fn measure(name: impl Into<String>, source: &[u8], target: &[u8]) -> Self {
+        let start = Instant::now();
+        let patch = Patch::new(source, target);
+        let encode_duration = start.elapsed();
+        let start = Instant::now();
+        let applied = patch.apply(source);
+        let apply_duration = start.elapsed();
+        debug_assert_eq!(applied.as_deref(), Some(target));
+        Self {
+            encode_duration: Some(encode_duration),
+            apply_duration: Some(apply_duration),
+            ..Self::new(name, &patch)
+        }
+    }
+
+    pub fn to_csv_row(&self) -> String {
+        let name = if self.name.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", self.name.replace('"', "\"\""))
+        } else {
+            self.name.clone()
+        };
+        let duration = |duration: Option<Duration>| {
+            duration.map_or_else(String::new, |duration| duration.as_nanos().to_string())
+        };
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            name,
+            self.source_length,
+            self.target_length,
+            self.patch_length,
+            self.removes,
+            self.adds,
+            self.copies,
+            self.target_copies,
+            self.removed_bytes,
+            self.added_bytes,
+            self.copied_bytes,
+            self.target_copied_bytes,
+            duration(self.encode_duration),
+            duration(self.apply_duration),
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct CsvExporter<W: Write> {
+    writer: W,
+    header_written: bool,
+}
+
+impl<W: Write> CsvExporter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            header_written: false,
+        }
+    }
+
+    pub fn write(&mut self, stats: &PatchStats) -> io::Result<()> {
+        if !self.header_written {
+            writeln!(self.writer, "{}", CSV_HEADER)?;
+            self.header_written = true;
+        }
+        writeln!(self.writer, "{}", stats.to_csv_row())
+    }
+
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(feature = "parquet")]
+pub fn write_parquet<W: Write + Send>(
+    writer: W,
+    rows: &[PatchStats],
+) -> parquet::errors::Result<W> {
+    use std::sync::Arc;
+
+    use parquet::{
+        data_type::{ByteArray, ByteArrayType, Int64Type},
+        file::{properties::WriterProperties, writer::SerializedFileWriter},
+        schema::parser::parse_message_type,
+    };
+
+    let schema = parse_message_type(
+        "message patch_stats {
+            required binary name (UTF8);
+            required int64 source_length;
+            required int64 target_length;
+            required int64 patch_length;
+            required int64 removes;
+            required int64 adds;
+            required int64 copies;
+            required int64 target_copies;
+            required int64 removed_bytes;
+            required int64 added_bytes;
+            required int64 copied_bytes;
+            required int64 target_copied_bytes;
+            optional int64 encode_ns;
+            optional int64 apply_ns;
+        }",
+    )?;
+    let mut file = SerializedFileWriter::new(
+        writer,
+        Arc::new(schema),
+        Arc::new(WriterProperties::builder().build()),
+    )?;
+    let mut row_group = file.next_row_group()?;
+
+    let names: Vec<ByteArray> = rows.iter().map(|row| row.name.as_str().into()).collect();
+    let mut column = row_group.next_column()?.unwrap();
+    column
+        .typed::<ByteArrayType>()
+        .write_batch(&names, None, None)?;
+    column.close()?;
+
+    let required: [fn(&PatchStats) -> usize; 11] = [
+        |row| row.source_length,
+        |row| row.target_length,
+        |row| row.patch_length,
+        |row| row.removes,
+        |row| row.adds,
+        |row| row.copies,
+        |row| row.target_copies,
+        |row| row.removed_bytes,
+        |row| row.added_bytes,
+        |row| row.copied_bytes,
+        |row| row.target_copied_bytes,
+    ];
+    for field in required {
+        let values: Vec<i64> = rows.iter().map(|row| field(row) as i64).collect();
+        let mut column = row_group.next_column()?.unwrap();
+        column
+            .typed::<Int64Type>()
+            .write_batch(&values, None, None)?;
+        column.close()?;
+    }
+
+    let optional: [fn(&PatchStats) -> Option<Duration>; 2] =
+        [|row| row.encode_duration, |row| row.apply_duration];
+    for field in optional {
+        let values: Vec<i64> = rows
+            .iter()
+            .filter_map(|row| field(row).map(|duration| duration.as_nanos() as i64))
+            .collect();
+        let levels: Vec<i16> = rows.iter().map(|row| field(row).is_some() as i16).collect();
+        let mut column = row_group.next_column()?.unwrap();
+        column
+            .typed::<Int64Type>()
+            .write_batch(&values, Some(&levels), None)?;
+        column.close()?;
+    }
+
+    row_group.close()?;
+    file.into_inner()
+}
+
+#[cfg(test)]
+mod stats_tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn patch_stats() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+        let stats = PatchStats::new("v1", &patch);
+        assert_eq!(stats.patch_length, patch.to_bytes().len());
+        assert_eq!(
+            stats.removes + stats.adds + stats.copies + stats.target_copies,
+            patch.instructions().len()
+        );
+        assert_eq!(stats.removed_bytes + stats.copied_bytes, source.len());
+        assert_eq!(stats.added_bytes + stats.copied_bytes, target.len());
+        assert_eq!(stats.encode_duration, None);
+
+        let measured = PatchStats::measure("v1", &source, &target);
+        assert!(measured.encode_duration.is_some());
+        assert_eq!(
+            PatchStats {
+                encode_duration: None,
+                apply_duration: None,
+                ..measured
+            },
+            stats
+        );
+    }
+
+    #[test]
+    fn csv_exporter() {
+        let stats = PatchStats {
+            name: String::from("release \"2\", final"),
+            source_length: 3,
+            apply_duration: Some(Duration::from_nanos(42)),
+            ..Default::default()
+        };
+        let mut exporter = CsvExporter::new(Vec::new());
+        exporter.write(&PatchStats::default()).unwrap();
+        exporter.write(&stats).unwrap();
+        let csv = String::from_utf8(exporter.into_inner().unwrap()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(lines[1], ",0,0,0,0,0,0,0,0,0,0,0,,");
+        assert_eq!(
+            lines[2],
+            "\"release \"\"2\"\", final\",3,0,0,0,0,0,0,0,0,0,0,,42"
+        );
+        assert_eq!(CSV_HEADER.split(',').count(), lines[1].split(',').count());
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_export() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let rows = vec![
+            PatchStats::measure("v1", &source, &target),
+            PatchStats::new("v2", &Patch::new(&target, &source)),
+        ];
+        let path =
+            std::env::temp_dir().join(format!("deltas-stats-{}.parquet", std::process::id()));
+        write_parquet(fs::File::create(&path).unwrap(), &rows).unwrap();
+        let reader = SerializedFileReader::new(fs::File::open(&path).unwrap()).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 2);
+        assert_eq!(
+            reader
+                .metadata()
+                .file_metadata()
+                .schema_descr()
+                .num_columns(),
+            CSV_HEADER.split(',').count()
+        );
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+pub const PATCHES_ENCODED: &str = "deltas_patches_encoded_total";
+pub const BYTES_SAVED: &str = "deltas_bytes_saved_total";
+pub const ENCODE_DURATION: &str = "deltas_encode_duration_seconds";
+pub const PATCHES_APPLIED: &str = "deltas_patches_applied_total";
+pub const BYTES_APPLIED: &str = "deltas_bytes_applied_total";
+pub const APPLY_DURATION: &str = "deltas_apply_duration_seconds";
+pub const CACHE_HITS: &str = "deltas_cache_hits_total";
+pub const CACHE_MISSES: &str = "deltas_cache_misses_total";
+pub const CACHE_EVICTIONS: &str = "deltas_cache_evictions_total";
+
+#[cfg(feature = "metrics")]
+pub(crate) fn record_encode(target_length: usize, patch_length: usize, elapsed: Duration) {
+    metrics::counter!(PATCHES_ENCODED).increment(1);
+    metrics::counter!(BYTES_SAVED).increment(target_length.saturating_sub(patch_length) as u64);
+    metrics::histogram!(ENCODE_DURATION).record(elapsed.as_secs_f64());
+}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn record_apply(target_length: usize, elapsed: Duration) {
+    metrics::counter!(PATCHES_APPLIED).increment(1);
+    metrics::counter!(BYTES_APPLIED).increment(target_length as u64);
+    metrics::histogram!(APPLY_DURATION).record(elapsed.as_secs_f64());
+}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn record_cache_lookup(hit: bool) {
+    metrics::counter!(if hit { CACHE_HITS } else { CACHE_MISSES }).increment(1);
+}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn record_cache_eviction() {
+    metrics::counter!(CACHE_EVICTIONS).increment(1);
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn record_encode(_: usize, _: usize, _: Duration) {}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn record_apply(_: usize, _: Duration) {}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn record_cache_lookup(_: bool) {}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn record_cache_eviction() {}
+
+#[cfg(all(test, feature = "metrics"))]
+mod telemetry_tests {
+    use std::fs;
+
+    use metrics_util::{
+        debugging::{DebugValue, DebuggingRecorder},
+        MetricKind,
+    };
+
+    use super::*;
+    use crate::patch::Patch;
+
+    #[test]
+    fn record() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || {
+            let patch = Patch::new(&source, &target);
+            patch.apply(&source).unwrap();
+            patch.apply(&source).unwrap();
+        });
+
+        let values: Vec<(MetricKind, String, DebugValue)> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| (key.kind(), key.key().name().to_string(), value))
+            .collect();
+        let counter = |name: &str| {
+            values.iter().find_map(|(kind, key, value)| match value {
+                DebugValue::Counter(count) if *kind == MetricKind::Counter && key == name => {
+                    Some(*count)
+                }
+                _ => None,
+            })
+        };
+        let histogram = |name: &str| {
+            values.iter().find_map(|(_, key, value)| match value {
+                DebugValue::Histogram(values) if key == name => Some(values.len()),
+                _ => None,
+            })
+        };
+        assert_eq!(counter(PATCHES_ENCODED), Some(1));
+        assert!(counter(BYTES_SAVED).is_some());
+        assert_eq!(counter(PATCHES_APPLIED), Some(2));
+        assert_eq!(counter(BYTES_APPLIED), Some(2 * target.len() as u64));
+        assert_eq!(histogram(ENCODE_DURATION), Some(1));
+        assert_eq!(histogram(APPLY_DURATION), Some(2));
+    }
+
+    #[test]
+    fn record_cache() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || {
+            let mut cache = crate::cache::WeightedCache::new(64);
+            cache.insert(1, vec![0u8; 16]);
+            cache.get(&1);
+            cache.get(&2);
+            cache.insert(2, vec![0u8; 16]);
+        });
+        let counters: Vec<(String, u64)> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .filter_map(|(key, _, _, value)| match value {
+                DebugValue::Counter(count) => Some((key.key().name().to_string(), count)),
+                _ => None,
+            })
+            .collect();
+        for name in [CACHE_HITS, CACHE_MISSES, CACHE_EVICTIONS] {
+            assert!(counters.contains(&(name.to_string(), 1)), "{}", name);
+        }
+    }
+}
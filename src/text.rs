@@ -0,0 +1,214 @@
+use std::error::Error;
+
+use crate::{
+    instructions::{
+        delta_instruction::DeltaInstruction, InstructionError, InstructionInfo,
+        ADD_INSTRUCTION_SIGN, COPY_INSTRUCTION_SIGN, REMOVE_INSTRUCTION_SIGN,
+        TARGET_COPY_INSTRUCTION_SIGN,
+    },
+    patch::Patch,
+};
+
+const HEADER: &str = "deltas patch v1";
+/// Content bytes per hex line, so an edit only touches the lines it falls on.
+const BYTES_PER_LINE: usize = 32;
+const INDENT: &str = "  ";
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum TextError {
+    MissingHeader,
+    /// The 1-based number of a line that couldn't be parsed.
+    InvalidLine(usize),
+    InvalidPatch(InstructionError),
+}
+
+impl std::fmt::Display for TextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextError::MissingHeader => write!(f, "Text patch doesn't start with {:?}", HEADER),
+            TextError::InvalidLine(line) => write!(f, "Invalid text patch line {}", line),
+            TextError::InvalidPatch(error) => write!(f, "Text patch is invalid: {}", error),
+        }
+    }
+}
+
+impl Error for TextError {}
+
+impl From<InstructionError> for TextError {
+    fn from(error: InstructionError) -> Self {
+        TextError::InvalidPatch(error)
+    }
+}
+
+fn push_content(text: &mut String, content: &[u8]) {
+    for line in content.chunks(BYTES_PER_LINE) {
+        text.push_str(INDENT);
+        text.extend(line.iter().map(|byte| format!("{:02x}", byte)));
+        text.push('\n');
+    }
+}
+
+fn parse_hex(line: &str) -> Option<Vec<u8>> {
+    if !line.len().is_multiple_of(2) || !line.is_ascii() {
+        return None;
+    }
+    (0..line.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&line[index..index + 2], 16).ok())
+        .collect()
+}
+
+impl Patch {
+    /// Writes one instruction per line, followed by its content as indented hex lines of
+    /// [`BYTES_PER_LINE`] bytes. The output only depends on the instructions.
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}\n", HEADER);
+        for instruction in self.instructions() {
+            match instruction {
+                DeltaInstruction::Remove(_) => {
+                    text.push_str(&format!("remove {}\n", instruction.len()))
+                }
+                DeltaInstruction::Add(add) => {
+                    text.push_str(&format!("add {}\n", instruction.len()));
+                    push_content(&mut text, add.content());
+                }
+                DeltaInstruction::Copy(copy) => {
+                    text.push_str(&format!("copy {}\n", instruction.len()));
+                    push_content(&mut text, copy.content());
+                }
+                DeltaInstruction::TargetCopy(target_copy) => text.push_str(&format!(
+                    "target_copy {} {}\n",
+                    instruction.len(),
+                    target_copy.distance()
+                )),
+            }
+        }
+        text
+    }
+
+    /// Parses [`Patch::to_text`] output. Blank lines and `#` comments are ignored.
+    pub fn try_from_text(text: &str) -> Result<Self, TextError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim_end()))
+            .filter(|(_, line)| !line.trim_start().is_empty() && !line.starts_with('#'));
+        match lines.next() {
+            Some((_, HEADER)) => (),
+            _ => return Err(TextError::MissingHeader),
+        }
+        let mut bytes: Vec<u8> = Vec::with_capacity(text.len() / 2);
+        let mut has_content = false;
+        for (number, line) in lines {
+            let invalid = || TextError::InvalidLine(number);
+            if let Some(hex) = line.strip_prefix(INDENT) {
+                if !has_content {
+                    return Err(invalid());
+                }
+                bytes.extend(parse_hex(hex).ok_or_else(invalid)?);
+                continue;
+            }
+            let mut words = line.split(' ');
+            let (sign, length) = match (words.next(), words.next()) {
+                (Some("remove"), Some(length)) => (REMOVE_INSTRUCTION_SIGN, length),
+                (Some("add"), Some(length)) => (ADD_INSTRUCTION_SIGN, length),
+                (Some("copy"), Some(length)) => (COPY_INSTRUCTION_SIGN, length),
+                (Some("target_copy"), Some(length)) => (TARGET_COPY_INSTRUCTION_SIGN, length),
+                _ => return Err(invalid()),
+            };
+            bytes.push(sign);
+            bytes.push(length.parse().map_err(|_| invalid())?);
+            if sign == TARGET_COPY_INSTRUCTION_SIGN {
+                let distance: u32 = words
+                    .next()
+                    .and_then(|distance| distance.parse().ok())
+                    .ok_or_else(invalid)?;
+                bytes.extend(distance.to_be_bytes());
+            }
+            if words.next().is_some() {
+                return Err(invalid());
+            }
+            has_content = matches!(sign, ADD_INSTRUCTION_SIGN | COPY_INSTRUCTION_SIGN);
+        }
+        Ok(Patch::try_from_bytes(&bytes)?)
+    }
+}
+
+#[cfg(test)]
+mod text_tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn to_text() {
+        let patch = Patch::try_from_bytes(&[
+            b'-', 2, b'+', 3, 0xde, 0xad, 0x01, b'|', 2, 0, 0xff, b'<', 4, 0, 0, 0, 2,
+        ])
+        .unwrap();
+        assert_eq!(
+            patch.to_text(),
+            "deltas patch v1\nremove 2\nadd 3\n  dead01\ncopy 2\n  00ff\ntarget_copy 4 2\n"
+        );
+        let long = Patch::new(b"", &[0xab; 70]).to_text();
+        assert_eq!(
+            long.lines().skip(2).map(str::len).collect::<Vec<usize>>(),
+            vec![66, 66, 14]
+        );
+    }
+
+    #[test]
+    fn text_roundtrip() {
+        let source = fs::read("files/source.txt").unwrap();
+        let mut target = fs::read("files/target.txt").unwrap();
+        target.extend(b"0123456789".repeat(40));
+        let patch = Patch::new_with_target_copies(&source, &target);
+        let text = patch.to_text();
+        assert_eq!(Patch::try_from_text(&text), Ok(patch.clone()));
+
+        let commented = format!("# generated\n\n{}\n\n", text.replace('\n', "  \n"));
+        assert_eq!(Patch::try_from_text(&commented), Ok(patch.clone()));
+
+        let mut edited = target.clone();
+        edited[100] ^= 1;
+        let changed = Patch::new_with_target_copies(&source, &edited).to_text();
+        let differing = text
+            .lines()
+            .zip(changed.lines())
+            .filter(|(old, new)| old != new)
+            .count();
+        assert!(differing <= 2, "{} lines changed", differing);
+    }
+
+    #[test]
+    fn try_from_text_err() {
+        assert_eq!(
+            Patch::try_from_text("add 1\n"),
+            Err(TextError::MissingHeader)
+        );
+        assert_eq!(
+            Patch::try_from_text("deltas patch v1\nadd 1\n  zz\n"),
+            Err(TextError::InvalidLine(3))
+        );
+        assert_eq!(
+            Patch::try_from_text("deltas patch v1\nremove 1\n  00\n"),
+            Err(TextError::InvalidLine(3))
+        );
+        assert_eq!(
+            Patch::try_from_text("deltas patch v1\nmove 1\n"),
+            Err(TextError::InvalidLine(2))
+        );
+        assert_eq!(
+            Patch::try_from_text("deltas patch v1\nremove 256\n"),
+            Err(TextError::InvalidLine(2))
+        );
+        assert_eq!(
+            Patch::try_from_text("deltas patch v1\ntarget_copy 1\n"),
+            Err(TextError::InvalidLine(2))
+        );
+        assert_eq!(
+            Patch::try_from_text("deltas patch v1\nadd 2\n  00\n"),
+            Err(TextError::InvalidPatch(InstructionError::MissingContent))
+        );
+    }
+}
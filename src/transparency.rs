@@ -0,0 +1,197 @@
+use crate::{
+    digest::Digest,
+    merkle::{leaf, levels, proof, root_from_proof},
+    patch::Patch,
+};
+
+/// Proof that an entry is leaf `index` of the log when it had `tree_size` entries.
+#[derive(Debug, PartialEq, Clone)]
+pub struct InclusionProof<O> {
+    pub index: usize,
+    pub tree_size: usize,
+    pub hashes: Vec<O>,
+}
+
+/// Append-only log of patch hashes hashed into an RFC 6962 Merkle tree.
+///
+/// The log publishes signed tree heads ([`TransparencyLog::root`] at a size) elsewhere; a
+/// device that received a patch checks it against a tree head with
+/// [`verify_patch_inclusion`] to know everyone was offered the same patch.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TransparencyLog<D: Digest> {
+    leaves: Vec<D::Output>,
+}
+
+impl<D: Digest> Default for TransparencyLog<D> {
+    fn default() -> Self {
+        Self { leaves: Vec::new() }
+    }
+}
+
+/// The log entry for `patch`: the digest of its encoded bytes.
+pub fn patch_entry<D: Digest>(patch: &Patch) -> D::Output {
+    D::digest(&patch.to_bytes())
+}
+
+impl<D: Digest> TransparencyLog<D>
+where
+    D::Output: Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Appends a raw entry and returns its index.
+    pub fn append(&mut self, entry: &[u8]) -> usize {
+        self.leaves.push(leaf::<D>(entry));
+        self.leaves.len() - 1
+    }
+
+    /// Appends the [`patch_entry`] of `patch` and returns its index.
+    pub fn append_patch(&mut self, patch: &Patch) -> usize {
+        self.append(patch_entry::<D>(patch).as_ref())
+    }
+
+    /// The tree head over the first `tree_size` entries, or `None` past the end of the log.
+    pub fn root_at(&self, tree_size: usize) -> Option<D::Output> {
+        match tree_size {
+            0 => Some(D::digest(b"")),
+            _ if tree_size > self.leaves.len() => None,
+            _ => levels::<D>(self.leaves[..tree_size].to_vec())
+                .pop()
+                .map(|mut root| root.remove(0)),
+        }
+    }
+
+    pub fn root(&self) -> D::Output {
+        self.root_at(self.leaves.len()).unwrap()
+    }
+
+    /// Proves entry `index` against the tree head at `tree_size`.
+    pub fn inclusion_proof(
+        &self,
+        index: usize,
+        tree_size: usize,
+    ) -> Option<InclusionProof<D::Output>> {
+        if index >= tree_size || tree_size > self.leaves.len() {
+            return None;
+        }
+        let levels = levels::<D>(self.leaves[..tree_size].to_vec());
+        Some(InclusionProof {
+            index,
+            tree_size,
+            hashes: proof(&levels, index),
+        })
+    }
+}
+
+/// Checks that `entry` is in the log whose tree head at `proof.tree_size` is `root`.
+pub fn verify_inclusion<D: Digest>(
+    entry: &[u8],
+    proof: &InclusionProof<D::Output>,
+    root: &[u8],
+) -> bool {
+    root_from_proof::<D>(
+        leaf::<D>(entry),
+        proof.tree_size,
+        proof.index,
+        &proof.hashes,
+    )
+    .is_some_and(|hash| hash.as_ref() == root)
+}
+
+pub fn verify_patch_inclusion<D: Digest>(
+    patch: &Patch,
+    proof: &InclusionProof<D::Output>,
+    root: &[u8],
+) -> bool {
+    verify_inclusion::<D>(patch_entry::<D>(patch).as_ref(), proof, root)
+}
+
+#[cfg(test)]
+mod transparency_tests {
+    use std::fs;
+
+    use super::*;
+    use crate::digest::digest_tests::Fnv1a;
+
+    #[test]
+    fn inclusion_proof() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patches: Vec<Patch> = (0..13)
+            .map(|length| Patch::new(&source, &target[..target.len() - length]))
+            .collect();
+        let mut log = TransparencyLog::<Fnv1a>::new();
+        assert_eq!(log.root(), Fnv1a::digest(b""));
+        for (index, patch) in patches.iter().enumerate() {
+            assert_eq!(log.append_patch(patch), index);
+        }
+        assert_eq!(log.len(), 13);
+
+        for tree_size in 1..=log.len() {
+            let root = log.root_at(tree_size).unwrap();
+            for (index, patch) in patches[..tree_size].iter().enumerate() {
+                let proof = log.inclusion_proof(index, tree_size).unwrap();
+                assert!(verify_patch_inclusion::<Fnv1a>(patch, &proof, &root));
+                let other = &patches[(index + 1) % tree_size];
+                if tree_size > 1 {
+                    assert!(!verify_patch_inclusion::<Fnv1a>(other, &proof, &root));
+                }
+            }
+        }
+        assert_eq!(log.inclusion_proof(3, 3), None);
+        assert_eq!(log.inclusion_proof(0, 14), None);
+        assert_eq!(log.root_at(14), None);
+    }
+
+    #[cfg(feature = "manifest")]
+    #[test]
+    fn rfc6962_vectors() {
+        let entries: [&[u8]; 8] = [
+            b"",
+            b"\x00",
+            b"\x10",
+            b"\x20\x21",
+            b"\x30\x31",
+            b"\x40\x41\x42\x43",
+            b"\x50\x51\x52\x53\x54\x55\x56\x57",
+            b"\x60\x61\x62\x63\x64\x65\x66\x67\x68\x69\x6a\x6b\x6c\x6d\x6e\x6f",
+        ];
+        let roots = [
+            "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+            "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125",
+            "aeb6bcfe274b70a14fb067a5e5578264db0fa9b51af5e0ba159158f329e06e77",
+            "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+            "4e3bbb1f7b478dcfe71fb631631519a3bca12c9aefca1612bfce4c13a86264d4",
+            "76e67dadbcdf1e10e1b74ddc608abd2f98dfb16fbce75277b5232a127f2087ef",
+            "ddb89be403809e325750d3d263cd78929c2942b7942a34b77e122c9594a74c8c",
+            "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328",
+        ];
+        let mut log = TransparencyLog::<sha2::Sha256>::new();
+        for (entry, root) in entries.iter().zip(roots) {
+            log.append(entry);
+            let hex: String = log
+                .root()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            assert_eq!(hex, root);
+        }
+        let proof = log.inclusion_proof(5, 8).unwrap();
+        assert_eq!(proof.hashes.len(), 3);
+        assert!(verify_inclusion::<sha2::Sha256>(
+            entries[5],
+            &proof,
+            &log.root()
+        ));
+    }
+}
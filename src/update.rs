@@ -0,0 +1,284 @@
+use std::{
+    error::Error,
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{digest::Digest, instructions::InstructionError, patch::Patch};
+
+/// Where an [`Updater`] downloads patches from.
+pub trait PatchTransport {
+    type Reader: Read;
+
+    /// Opens the patch for reading from `offset`, so a dropped download can continue where it
+    /// stopped.
+    fn open_at(&mut self, offset: u64) -> io::Result<Self::Reader>;
+}
+
+#[derive(Debug)]
+pub enum UpdateError {
+    Io(io::Error),
+    Download { attempts: usize, error: io::Error },
+    PatchDigestMismatch,
+    InvalidPatch(InstructionError),
+    SourceMismatch,
+    TargetDigestMismatch,
+}
+
+impl std::fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpdateError::Io(err) => write!(f, "Update I/O failed: {}", err),
+            UpdateError::Download { attempts, error } => {
+                write!(f, "Download failed after {} attempts: {}", attempts, error)
+            }
+            UpdateError::PatchDigestMismatch => write!(f, "Patch digest didn't match"),
+            UpdateError::InvalidPatch(error) => write!(f, "Downloaded patch is invalid: {}", error),
+            UpdateError::SourceMismatch => {
+                write!(f, "Installed file doesn't match the patch source length")
+            }
+            UpdateError::TargetDigestMismatch => write!(f, "Updated file digest didn't match"),
+        }
+    }
+}
+
+impl Error for UpdateError {}
+
+impl From<io::Error> for UpdateError {
+    fn from(err: io::Error) -> Self {
+        UpdateError::Io(err)
+    }
+}
+
+impl From<InstructionError> for UpdateError {
+    fn from(error: InstructionError) -> Self {
+        UpdateError::InvalidPatch(error)
+    }
+}
+
+/// Downloads a patch, verifies it and replaces a file with the patched contents.
+///
+/// The new contents are written next to the file, read back and checked against the expected
+/// digest, then renamed over it in one step, so the file is either left untouched or fully
+/// updated. The directory is synced afterwards so the rename survives a crash.
+pub struct Updater<T: PatchTransport> {
+    transport: T,
+    max_attempts: usize,
+}
+
+impl<T: PatchTransport> Updater<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            max_attempts: 5,
+        }
+    }
+
+    /// Consecutive failed reads tolerated before giving up. Progress resets the count.
+    pub fn max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn download(&mut self) -> Result<Vec<u8>, UpdateError> {
+        let mut bytes: Vec<u8> = Vec::new();
+        let mut attempts = 0;
+        loop {
+            let offset = bytes.len();
+            let result = self
+                .transport
+                .open_at(offset as u64)
+                .and_then(|mut reader| reader.read_to_end(&mut bytes));
+            match result {
+                Ok(_) => return Ok(bytes),
+                Err(error) => {
+                    attempts = match bytes.len() > offset {
+                        true => 1,
+                        false => attempts + 1,
+                    };
+                    if attempts >= self.max_attempts {
+                        return Err(UpdateError::Download { attempts, error });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Downloads the patch, checks it against `patch_digest`, applies it to the file at `path`
+    /// and checks the result against `target_digest` before replacing the file.
+    pub fn update<D: Digest>(
+        &mut self,
+        path: &Path,
+        patch_digest: &[u8],
+        target_digest: &[u8],
+    ) -> Result<(), UpdateError> {
+        let bytes = self.download()?;
+        if D::digest(&bytes).as_ref() != patch_digest {
+            return Err(UpdateError::PatchDigestMismatch);
+        }
+        let patch = Patch::try_from_bytes(&bytes)?;
+        let target = patch
+            .apply(&fs::read(path)?)
+            .ok_or(UpdateError::SourceMismatch)?;
+        if D::digest(&target).as_ref() != target_digest {
+            return Err(UpdateError::TargetDigestMismatch);
+        }
+        replace::<D>(path, &target, target_digest)
+    }
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+fn replace<D: Digest>(path: &Path, contents: &[u8], digest: &[u8]) -> Result<(), UpdateError> {
+    let staged = sibling(path, ".deltas-new");
+    let verified = fs::File::create(&staged)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::read(&staged))
+        .map_err(UpdateError::from)
+        .and_then(|read| match D::digest(&read).as_ref() == digest {
+            true => Ok(()),
+            false => Err(UpdateError::TargetDigestMismatch),
+        });
+    if let Err(err) = verified.and_then(|_| Ok(fs::rename(&staged, path)?)) {
+        let _ = fs::remove_file(&staged);
+        return Err(err);
+    }
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    fs::File::open(parent)?.sync_all()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod update_tests {
+    use std::{env, process};
+
+    use super::*;
+    use crate::digest::digest_tests::Fnv1a;
+
+    /// Serves `patch`, dropping the connection after every `chunk` bytes.
+    struct FlakyTransport {
+        patch: Vec<u8>,
+        chunk: usize,
+        opened: Vec<u64>,
+    }
+
+    struct FlakyReader {
+        bytes: Vec<u8>,
+        offset: usize,
+        drops: bool,
+    }
+
+    impl Read for FlakyReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.offset == self.bytes.len() && self.drops {
+                return Err(io::Error::new(io::ErrorKind::ConnectionReset, "dropped"));
+            }
+            let length = buf.len().min(self.bytes.len() - self.offset);
+            buf[..length].copy_from_slice(&self.bytes[self.offset..self.offset + length]);
+            self.offset += length;
+            Ok(length)
+        }
+    }
+
+    impl PatchTransport for FlakyTransport {
+        type Reader = FlakyReader;
+
+        fn open_at(&mut self, offset: u64) -> io::Result<Self::Reader> {
+            self.opened.push(offset);
+            let start = offset as usize;
+            let end = (start + self.chunk).min(self.patch.len());
+            Ok(FlakyReader {
+                bytes: self.patch[start..end].to_vec(),
+                offset: 0,
+                drops: end < self.patch.len(),
+            })
+        }
+    }
+
+    fn installed(name: &str, contents: &[u8]) -> PathBuf {
+        let path = env::temp_dir().join(format!("deltas-update-{}-{}", name, process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn update() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target).to_bytes();
+        let path = installed("ok", &source);
+        let mut updater = Updater::new(FlakyTransport {
+            patch: patch.clone(),
+            chunk: 100,
+            opened: Vec::new(),
+        });
+        updater
+            .update::<Fnv1a>(&path, &Fnv1a::digest(&patch), &Fnv1a::digest(&target))
+            .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), target);
+        assert_eq!(
+            updater.transport.opened,
+            (0..patch.len() as u64).step_by(100).collect::<Vec<u64>>()
+        );
+        assert!(!sibling(&path, ".deltas-new").exists());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn update_err() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target).to_bytes();
+        let path = installed("err", &source);
+        let transport = |chunk: usize| FlakyTransport {
+            patch: patch.clone(),
+            chunk,
+            opened: Vec::new(),
+        };
+        let (patch_digest, target_digest) = (Fnv1a::digest(&patch), Fnv1a::digest(&target));
+
+        let mut updater = Updater::new(transport(0)).max_attempts(3);
+        assert!(matches!(
+            updater.update::<Fnv1a>(&path, &patch_digest, &target_digest),
+            Err(UpdateError::Download { attempts: 3, .. })
+        ));
+        assert_eq!(updater.transport.opened, vec![0, 0, 0]);
+        assert!(matches!(
+            Updater::new(transport(1000)).update::<Fnv1a>(&path, &target_digest, &target_digest),
+            Err(UpdateError::PatchDigestMismatch)
+        ));
+        assert!(matches!(
+            Updater::new(transport(1000)).update::<Fnv1a>(&path, &patch_digest, &patch_digest),
+            Err(UpdateError::TargetDigestMismatch)
+        ));
+        assert_eq!(fs::read(&path).unwrap(), source);
+
+        fs::write(&path, &target).unwrap();
+        assert!(matches!(
+            Updater::new(transport(1000)).update::<Fnv1a>(&path, &patch_digest, &target_digest),
+            Err(UpdateError::SourceMismatch)
+        ));
+        assert_eq!(fs::read(&path).unwrap(), target);
+        fs::remove_file(&path).unwrap();
+
+        let path = installed("replace", b"old");
+        assert!(matches!(
+            replace::<Fnv1a>(&path, b"new", &Fnv1a::digest(b"other")),
+            Err(UpdateError::TargetDigestMismatch)
+        ));
+        assert_eq!(fs::read(&path).unwrap(), b"old");
+        assert!(!sibling(&path, ".deltas-new").exists());
+        fs::remove_file(&path).unwrap();
+    }
+}
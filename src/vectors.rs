@@ -0,0 +1,255 @@
+use std::error::Error;
+
+use crate::patch::Patch;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TestVector {
+    pub name: &'static str,
+    pub source: &'static str,
+    pub target: &'static str,
+    pub patch: &'static str,
+    pub target_copies: bool,
+}
+
+pub const TEST_VECTORS: &[TestVector] = &[
+    TestVector {
+        name: "empty",
+        source: "",
+        target: "",
+        patch: "",
+        target_copies: false,
+    },
+    TestVector {
+        name: "add",
+        source: "",
+        target: "64656c7461",
+        patch: "2b0564656c7461",
+        target_copies: false,
+    },
+    TestVector {
+        name: "remove",
+        source: "64656c7461",
+        target: "",
+        patch: "2d05",
+        target_copies: false,
+    },
+    TestVector {
+        name: "copy",
+        source: "54686520717569636b2062726f776e20666f78206a756d7073206f76657220746865206c617a7920646f672e",
+        target: "54686520717569636b2072656420666f78206c65617073206f76657220746865206c617a7920646f6721",
+        patch: "7c2a0000000000000000000010f3f5a9f84f12b1f445f7fb06b0fc56f6fcbb0248f1b807410e18a6eb4f03b22d02",
+        target_copies: false,
+    },
+    TestVector {
+        name: "target_copy",
+        source: "616263",
+        target: "616263616263616263616263616263616263616263616263",
+        patch: "7c030000003c1500000003",
+        target_copies: true,
+    },
+];
+
+pub const FORMAT_VERSION: u32 = 2;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct GoldenVector {
+    pub version: u32,
+    pub name: &'static str,
+    pub source: &'static str,
+    pub patch: &'static str,
+    pub target: &'static str,
+}
+
+/// Patches pinned per format version. Every applier must keep reproducing these targets
+/// byte for byte, independent of what the current encoder emits.
+pub const GOLDEN_VECTORS: &[GoldenVector] = &[
+    GoldenVector {
+        version: 1,
+        name: "interleaved",
+        source: "616263646566",
+        patch: "2d022b0358595a7c0400000001",
+        target: "58595a63646567",
+    },
+    GoldenVector {
+        version: 1,
+        name: "wrapping_copy",
+        source: "ff00",
+        patch: "7c0201ff",
+        target: "00ff",
+    },
+    GoldenVector {
+        version: 1,
+        name: "split_adds",
+        source: "",
+        patch: "2b01612b0162",
+        target: "6162",
+    },
+    GoldenVector {
+        version: 2,
+        name: "overlapping_target_copy",
+        source: "6162",
+        patch: "7c0200003c0500000002",
+        target: "61626162616261",
+    },
+];
+
+impl GoldenVector {
+    pub fn verify(&self) -> bool {
+        let (Some(source), Some(bytes), Some(target)) = (
+            from_hex(self.source),
+            from_hex(self.patch),
+            from_hex(self.target),
+        ) else {
+            return false;
+        };
+        Patch::try_from_bytes(&bytes)
+            .ok()
+            .and_then(|patch| patch.apply(&source))
+            == Some(target)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FormatCompatibility {
+    Compatible,
+    Unsupported,
+    Regressed(&'static str),
+}
+
+impl FormatCompatibility {
+    pub fn check(patch_version: u32) -> Self {
+        if patch_version == 0 || patch_version > FORMAT_VERSION {
+            return FormatCompatibility::Unsupported;
+        }
+        match GOLDEN_VECTORS
+            .iter()
+            .filter(|vector| vector.version <= patch_version)
+            .find(|vector| !vector.verify())
+        {
+            Some(vector) => FormatCompatibility::Regressed(vector.name),
+            None => FormatCompatibility::Compatible,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Stage {
+    Hex,
+    Encode,
+    Decode,
+    Apply,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SelfTestError {
+    pub vector: &'static str,
+    pub stage: Stage,
+}
+
+impl std::fmt::Display for SelfTestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Test vector {:?} failed at the {:?} stage",
+            self.vector, self.stage
+        )
+    }
+}
+
+impl Error for SelfTestError {}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+impl TestVector {
+    pub fn verify(&self) -> Result<(), SelfTestError> {
+        let error = |stage| SelfTestError {
+            vector: self.name,
+            stage,
+        };
+        let (Some(source), Some(target), Some(bytes)) = (
+            from_hex(self.source),
+            from_hex(self.target),
+            from_hex(self.patch),
+        ) else {
+            return Err(error(Stage::Hex));
+        };
+        let encoded = match self.target_copies {
+            true => Patch::new_with_target_copies(&source, &target),
+            false => Patch::new(&source, &target),
+        };
+        if encoded.to_bytes() != bytes {
+            return Err(error(Stage::Encode));
+        }
+        let decoded = Patch::try_from_bytes(&bytes).map_err(|_| error(Stage::Decode))?;
+        if decoded != encoded {
+            return Err(error(Stage::Decode));
+        }
+        match decoded.apply(&source) {
+            Some(applied) if applied == target => Ok(()),
+            _ => Err(error(Stage::Apply)),
+        }
+    }
+}
+
+pub fn verify_implementation() -> Result<(), SelfTestError> {
+    TEST_VECTORS.iter().try_for_each(TestVector::verify)
+}
+
+#[cfg(test)]
+mod vectors_tests {
+    use super::*;
+
+    #[test]
+    fn verify_implementation() {
+        assert_eq!(super::verify_implementation(), Ok(()));
+    }
+
+    #[test]
+    fn golden_vectors() {
+        for vector in GOLDEN_VECTORS {
+            assert!(vector.verify(), "{}", vector.name);
+        }
+        for version in 1..=FORMAT_VERSION {
+            assert_eq!(
+                FormatCompatibility::check(version),
+                FormatCompatibility::Compatible
+            );
+        }
+        assert_eq!(
+            FormatCompatibility::check(0),
+            FormatCompatibility::Unsupported
+        );
+        assert_eq!(
+            FormatCompatibility::check(FORMAT_VERSION + 1),
+            FormatCompatibility::Unsupported
+        );
+        let mut vector = GOLDEN_VECTORS[0];
+        vector.target = "58595a63646566";
+        assert!(!vector.verify());
+    }
+
+    #[test]
+    fn verify_failures() {
+        let mut vector = TEST_VECTORS[1];
+        vector.patch = "2b0564656c74";
+        assert_eq!(
+            vector.verify(),
+            Err(SelfTestError {
+                vector: "add",
+                stage: Stage::Encode
+            })
+        );
+        vector.target = "6";
+        assert_eq!(vector.verify().unwrap_err().stage, Stage::Hex);
+        assert_eq!(from_hex("0aFf"), Some(vec![0x0a, 0xff]));
+        assert_eq!(from_hex("zz"), None);
+    }
+}
@@ -0,0 +1,229 @@
+use std::{error::Error, io, io::Write};
+
+use crate::{apply::ApplyOptions, instructions::InstructionError, patch::Patch};
+
+pub const VERSION_HEADER_SIGN: u8 = b'V';
+const HEADER_LENGTH: usize = 17;
+
+/// Versions a patch is bound to, carried in a header in front of the patch bytes so a
+/// signature over the bytes covers them.
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct VersionBinding {
+    /// Oldest installed version the patch may be applied to.
+    pub min_source_version: u64,
+    /// Version the patch installs. It must be newer than the installed version.
+    pub target_version: u64,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum VersionError {
+    MissingHeader,
+    InvalidPatch(InstructionError),
+    SourceTooOld {
+        installed: u64,
+        required: u64,
+    },
+    Downgrade {
+        installed: u64,
+        target: u64,
+    },
+    /// Versions were enforced for a patch without a binding.
+    Unbound,
+}
+
+impl std::fmt::Display for VersionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VersionError::MissingHeader => write!(f, "Patch has no version header"),
+            VersionError::InvalidPatch(error) => {
+                write!(f, "Version bound patch is invalid: {}", error)
+            }
+            VersionError::SourceTooOld {
+                installed,
+                required,
+            } => write!(
+                f,
+                "Installed version {} is older than the required version {}",
+                installed, required
+            ),
+            VersionError::Downgrade { installed, target } => write!(
+                f,
+                "Patch to version {} would not upgrade installed version {}",
+                target, installed
+            ),
+            VersionError::Unbound => write!(f, "Patch isn't bound to versions"),
+        }
+    }
+}
+
+impl Error for VersionError {}
+
+impl From<InstructionError> for VersionError {
+    fn from(error: InstructionError) -> Self {
+        VersionError::InvalidPatch(error)
+    }
+}
+
+impl VersionBinding {
+    /// Checks that the patch may be applied on top of the `installed` version.
+    pub fn check(&self, installed: u64) -> Result<(), VersionError> {
+        if installed < self.min_source_version {
+            return Err(VersionError::SourceTooOld {
+                installed,
+                required: self.min_source_version,
+            });
+        }
+        if self.target_version <= installed {
+            return Err(VersionError::Downgrade {
+                installed,
+                target: self.target_version,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct VersionedPatch {
+    pub patch: Patch,
+    pub binding: VersionBinding,
+}
+
+impl VersionedPatch {
+    pub fn new(patch: Patch, binding: VersionBinding) -> Self {
+        Self { patch, binding }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(HEADER_LENGTH + self.patch.byte_length());
+        bytes.push(VERSION_HEADER_SIGN);
+        bytes.extend(self.binding.min_source_version.to_be_bytes());
+        bytes.extend(self.binding.target_version.to_be_bytes());
+        bytes.extend(self.patch.to_bytes());
+        bytes
+    }
+
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, VersionError> {
+        if bytes.len() < HEADER_LENGTH || bytes[0] != VERSION_HEADER_SIGN {
+            return Err(VersionError::MissingHeader);
+        }
+        let binding = VersionBinding {
+            min_source_version: u64::from_be_bytes(bytes[1..9].try_into().unwrap()),
+            target_version: u64::from_be_bytes(bytes[9..HEADER_LENGTH].try_into().unwrap()),
+        };
+        Ok(Self {
+            patch: Patch::try_from_bytes(&bytes[HEADER_LENGTH..])?,
+            binding,
+        })
+    }
+
+    /// [`Patch::apply_with`] that refuses the patch when [`ApplyOptions::enforce_versions`]
+    /// is set and the binding doesn't allow upgrading from that version.
+    pub fn apply_with<W: Write>(
+        &self,
+        source: &[u8],
+        target: &mut W,
+        options: &mut ApplyOptions,
+    ) -> io::Result<()> {
+        let Some(installed) = options.enforce_versions.take() else {
+            return self.patch.apply_with(source, target, options);
+        };
+        if let Err(error) = self.binding.check(installed) {
+            options.enforce_versions = Some(installed);
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, error));
+        }
+        let result = self.patch.apply_with(source, target, options);
+        options.enforce_versions = Some(installed);
+        result
+    }
+}
+
+#[cfg(test)]
+mod version_tests {
+    use std::fs;
+
+    use super::*;
+
+    fn versioned() -> (Vec<u8>, Vec<u8>, VersionedPatch) {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = VersionedPatch::new(
+            Patch::new(&source, &target),
+            VersionBinding {
+                min_source_version: 3,
+                target_version: 5,
+            },
+        );
+        (source, target, patch)
+    }
+
+    #[test]
+    fn versioned_bytes() {
+        let (_, _, patch) = versioned();
+        let bytes = patch.to_bytes();
+        assert_eq!(bytes[0], VERSION_HEADER_SIGN);
+        assert_eq!(VersionedPatch::try_from_bytes(&bytes), Ok(patch.clone()));
+        assert_eq!(
+            VersionedPatch::try_from_bytes(&patch.patch.to_bytes()),
+            Err(VersionError::MissingHeader)
+        );
+        assert_eq!(
+            VersionedPatch::try_from_bytes(&bytes[..10]),
+            Err(VersionError::MissingHeader)
+        );
+        assert_eq!(
+            VersionedPatch::try_from_bytes(&bytes[..bytes.len() - 1]),
+            Err(VersionError::InvalidPatch(InstructionError::MissingContent))
+        );
+    }
+
+    #[test]
+    fn enforce_versions() {
+        let (source, target, patch) = versioned();
+        let binding = patch.binding;
+        assert_eq!(binding.check(3), Ok(()));
+        assert_eq!(binding.check(4), Ok(()));
+        assert_eq!(
+            binding.check(2),
+            Err(VersionError::SourceTooOld {
+                installed: 2,
+                required: 3
+            })
+        );
+        assert_eq!(
+            binding.check(5),
+            Err(VersionError::Downgrade {
+                installed: 5,
+                target: 5
+            })
+        );
+
+        let mut options = ApplyOptions {
+            enforce_versions: Some(4),
+            ..Default::default()
+        };
+        let mut output: Vec<u8> = Vec::new();
+        patch
+            .apply_with(&source, &mut output, &mut options)
+            .unwrap();
+        assert_eq!(output, target);
+        assert_eq!(options.enforce_versions, Some(4));
+
+        options.enforce_versions = Some(7);
+        let error = patch
+            .apply_with(&source, &mut Vec::new(), &mut options)
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        let error = patch
+            .patch
+            .apply_with(&source, &mut Vec::new(), &mut options)
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+
+        let mut output: Vec<u8> = Vec::new();
+        patch
+            .apply_with(&source, &mut output, &mut ApplyOptions::default())
+            .unwrap();
+        assert_eq!(output, target);
+    }
+}
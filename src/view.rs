@@ -0,0 +1,206 @@
+use crate::{
+    instructions::{delta_instruction::DeltaInstruction, InstructionContent, InstructionInfo},
+    patch::Patch,
+};
+
+#[derive(Debug, PartialEq, Clone)]
+struct Segment {
+    target_offset: usize,
+    source_offset: usize,
+    instruction: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct PatchedView<'a> {
+    source: &'a [u8],
+    patch: &'a Patch,
+    segments: Vec<Segment>,
+    length: usize,
+}
+
+impl<'a> PatchedView<'a> {
+    pub fn new(source: &'a [u8], patch: &'a Patch) -> Option<Self> {
+        if source.len() != patch.source_length() {
+            return None;
+        }
+        let mut segments: Vec<Segment> = Vec::new();
+        let mut source_offset = 0usize;
+        let mut target_offset = 0usize;
+        for (index, instruction) in patch.instructions().iter().enumerate() {
+            let length = instruction.len() as usize;
+            if let DeltaInstruction::Remove(_) = instruction {
+                source_offset += length;
+                continue;
+            }
+            if length > 0 {
+                segments.push(Segment {
+                    target_offset,
+                    source_offset,
+                    instruction: index,
+                });
+            }
+            target_offset += length;
+            if let DeltaInstruction::Copy(_) = instruction {
+                source_offset += length;
+            }
+        }
+        Some(Self {
+            source,
+            patch,
+            segments,
+            length: target_offset,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    fn segment_at(&self, position: usize) -> &Segment {
+        let index = self
+            .segments
+            .partition_point(|segment| segment.target_offset <= position);
+        &self.segments[index - 1]
+    }
+
+    /// Resolves a single target byte, following target copies back until a byte that was
+    /// added or copied from the source.
+    fn byte_at(&self, mut position: usize) -> u8 {
+        loop {
+            let segment = self.segment_at(position);
+            let start = position - segment.target_offset;
+            match &self.patch.instructions()[segment.instruction] {
+                DeltaInstruction::TargetCopy(target_copy) => {
+                    let distance = target_copy.distance() as usize;
+                    position = segment.target_offset + start % distance - distance;
+                }
+                DeltaInstruction::Add(add) => return add.content()[start],
+                DeltaInstruction::Copy(copy) => {
+                    return self.source[segment.source_offset + start]
+                        .wrapping_add(copy.content()[start])
+                }
+                DeltaInstruction::Remove(_) => unreachable!(),
+            }
+        }
+    }
+
+    pub fn read_at(&self, offset: usize, buffer: &mut [u8]) -> usize {
+        if offset >= self.length {
+            return 0;
+        }
+        let mut segment_index = self
+            .segments
+            .partition_point(|segment| segment.target_offset <= offset)
+            - 1;
+        let mut position = offset;
+        let mut written = 0usize;
+        let mut output: Vec<u8> = Vec::with_capacity(u8::MAX as usize);
+        while written < buffer.len() && segment_index < self.segments.len() {
+            let segment = &self.segments[segment_index];
+            let instruction = &self.patch.instructions()[segment.instruction];
+            let length = instruction.len() as usize;
+            let start = position - segment.target_offset;
+            let count = match instruction {
+                DeltaInstruction::TargetCopy(target_copy) => {
+                    let distance = target_copy.distance() as usize;
+                    let count = (length - start).min(buffer.len() - written);
+                    for index in written..written + count {
+                        let from = offset + index - distance;
+                        buffer[index] = match from >= offset {
+                            true => buffer[from - offset],
+                            false => self.byte_at(from),
+                        };
+                    }
+                    count
+                }
+                _ => {
+                    output.clear();
+                    instruction.apply(
+                        &mut self.source[segment.source_offset..].iter(),
+                        &mut output,
+                    );
+                    let count = (length - start).min(buffer.len() - written);
+                    buffer[written..written + count].copy_from_slice(&output[start..start + count]);
+                    count
+                }
+            };
+            written += count;
+            position += count;
+            if position == segment.target_offset + length {
+                segment_index += 1;
+            }
+        }
+        written
+    }
+}
+
+#[cfg(test)]
+mod view_tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn new() {
+        let patch = Patch::new(b"AAA", b"ABAB");
+        assert!(PatchedView::new(b"AA", &patch).is_none());
+        let view = PatchedView::new(b"AAA", &patch).unwrap();
+        assert_eq!(view.len(), 4);
+        assert!(PatchedView::new(b"", &Patch::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn read_at() {
+        let source = fs::read("files/source.txt").unwrap();
+        let target = fs::read("files/target.txt").unwrap();
+        let patch = Patch::new(&source, &target);
+        let view = PatchedView::new(&source, &patch).unwrap();
+        assert_eq!(view.len(), target.len());
+
+        for (offset, length) in [(0, 1), (0, 700), (5, 5), (250, 600), (target.len() - 3, 10)] {
+            let mut buffer = vec![0; length];
+            let read = view.read_at(offset, &mut buffer);
+            let expected = &target[offset..target.len().min(offset + length)];
+            assert_eq!(&buffer[..read], expected);
+        }
+
+        let mut buffer = vec![0; target.len()];
+        assert_eq!(view.read_at(0, &mut buffer), target.len());
+        assert_eq!(buffer, target);
+        assert_eq!(view.read_at(target.len(), &mut buffer), 0);
+    }
+
+    #[test]
+    fn read_at_target_copies() {
+        let source = b"unrelated".to_vec();
+        let mut target = b"0123456789".repeat(30);
+        target.extend(vec![b'='; 100]);
+        let patch = Patch::new_with_target_copies(&source, &target);
+        assert!(patch.has_target_copies());
+        let view = PatchedView::new(&source, &patch).unwrap();
+        for (offset, length) in [(0, 400), (13, 7), (250, 100), (320, 80)] {
+            let mut buffer = vec![0; length];
+            let read = view.read_at(offset, &mut buffer);
+            assert_eq!(&buffer[..read], &target[offset..offset + length]);
+        }
+    }
+
+    #[test]
+    fn read_at_long_target_copy_run() {
+        let mut target = b"x".to_vec();
+        target.extend(vec![0; 1_000_000]);
+        let patch = Patch::new_with_target_copies(b"", &target);
+        assert!(patch.has_target_copies());
+        let view = PatchedView::new(b"", &patch).unwrap();
+        let mut buffer = [1u8; 1];
+        assert_eq!(view.read_at(target.len() - 1, &mut buffer), 1);
+        assert_eq!(buffer, [0]);
+        let mut buffer = vec![1u8; 4096];
+        assert_eq!(view.read_at(500_000, &mut buffer), 4096);
+        assert!(buffer.iter().all(|byte| *byte == 0));
+    }
+}
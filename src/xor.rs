@@ -0,0 +1,237 @@
+use crate::{
+    instructions::{InstructionError, Result},
+    patch::{Patch, PatchBuilder},
+};
+
+const XOR_PATCH_SIGN: u8 = b'X';
+const RUN_HEADER_LENGTH: usize = 12;
+const MAX_RUN_LENGTH: usize = u32::MAX as usize;
+
+#[derive(Debug, PartialEq, Clone)]
+struct XorRun {
+    skip: usize,
+    xor: Vec<u8>,
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct XorPatch {
+    length: usize,
+    runs: Vec<XorRun>,
+}
+
+impl XorPatch {
+    pub fn new(source: &[u8], target: &[u8]) -> Option<Self> {
+        if source.len() != target.len() {
+            return None;
+        }
+        let xor: Vec<u8> = source.iter().zip(target).map(|(s, t)| s ^ t).collect();
+        let mut runs: Vec<XorRun> = Vec::new();
+        let mut offset = 0usize;
+        while let Some(start) = xor[offset..].iter().position(|item| *item != 0) {
+            let start = offset + start;
+            let mut end = start;
+            while end < xor.len() {
+                let zeros = xor[end..].iter().take_while(|item| **item == 0).count();
+                if zeros >= RUN_HEADER_LENGTH || end + zeros == xor.len() {
+                    break;
+                }
+                end += zeros;
+                end += xor[end..].iter().take_while(|item| **item != 0).count();
+            }
+            let end = end.min(start + MAX_RUN_LENGTH);
+            runs.push(XorRun {
+                skip: start - offset,
+                xor: xor[start..end].to_vec(),
+            });
+            offset = end;
+        }
+        Some(Self {
+            length: source.len(),
+            runs,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn apply_in_place(&self, buffer: &mut [u8]) -> Option<()> {
+        if buffer.len() != self.length {
+            return None;
+        }
+        let mut offset = 0usize;
+        for run in self.runs.iter() {
+            offset += run.skip;
+            for (item, xor) in buffer[offset..].iter_mut().zip(run.xor.iter()) {
+                *item ^= xor;
+            }
+            offset += run.xor.len();
+        }
+        Some(())
+    }
+
+    pub fn apply(&self, source: &[u8]) -> Option<Vec<u8>> {
+        let mut target = source.to_vec();
+        self.apply_in_place(&mut target)?;
+        Some(target)
+    }
+
+    pub fn to_patch(&self, source: &[u8]) -> Option<Patch> {
+        if source.len() != self.length {
+            return None;
+        }
+        let mut builder = PatchBuilder::default();
+        let mut offset = 0usize;
+        for run in self.runs.iter() {
+            builder.copy_unchanged(run.skip);
+            offset += run.skip;
+            let source = &source[offset..offset + run.xor.len()];
+            let target: Vec<u8> = source.iter().zip(&run.xor).map(|(s, x)| s ^ x).collect();
+            builder.copy(source, &target);
+            offset += run.xor.len();
+        }
+        builder.copy_unchanged(self.length - offset);
+        Some(builder.build())
+    }
+
+    pub fn from_patch(patch: &Patch, source: &[u8]) -> Option<Self> {
+        Self::new(source, &patch.apply(source)?)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(
+            9 + self
+                .runs
+                .iter()
+                .map(|run| RUN_HEADER_LENGTH + run.xor.len())
+                .sum::<usize>(),
+        );
+        bytes.push(XOR_PATCH_SIGN);
+        bytes.extend((self.length as u64).to_be_bytes());
+        for run in self.runs.iter() {
+            bytes.extend((run.skip as u64).to_be_bytes());
+            bytes.extend((run.xor.len() as u32).to_be_bytes());
+            bytes.extend(run.xor.iter());
+        }
+        bytes
+    }
+
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<Self> {
+        match bytes.first() {
+            Some(&XOR_PATCH_SIGN) => (),
+            Some(_) => return Err(InstructionError::InvalidSign),
+            None => return Err(InstructionError::MissignSign),
+        }
+        let length = bytes.get(1..9).ok_or(InstructionError::MissingLength)?;
+        let length = usize::try_from(u64::from_be_bytes(length.try_into().unwrap()))
+            .map_err(|_| InstructionError::InvalidLength)?;
+        let mut runs: Vec<XorRun> = Vec::new();
+        let mut offset = 9usize;
+        let mut covered = 0usize;
+        while offset < bytes.len() {
+            let header = bytes
+                .get(offset..offset + RUN_HEADER_LENGTH)
+                .ok_or(InstructionError::MissingLength)?;
+            let skip = usize::try_from(u64::from_be_bytes(header[..8].try_into().unwrap()))
+                .map_err(|_| InstructionError::InvalidLength)?;
+            let xor_length = u32::from_be_bytes(header[8..].try_into().unwrap()) as usize;
+            offset += RUN_HEADER_LENGTH;
+            let xor = bytes
+                .get(offset..offset.saturating_add(xor_length))
+                .ok_or(InstructionError::MissingContent)?;
+            offset += xor_length;
+            covered = covered
+                .checked_add(skip)
+                .and_then(|covered| covered.checked_add(xor_length))
+                .filter(|covered| *covered <= length)
+                .ok_or(InstructionError::InvalidLength)?;
+            runs.push(XorRun {
+                skip,
+                xor: xor.to_vec(),
+            });
+        }
+        Ok(Self { length, runs })
+    }
+}
+
+#[cfg(test)]
+mod xor_tests {
+    use super::*;
+
+    fn firmware() -> (Vec<u8>, Vec<u8>) {
+        let source: Vec<u8> = (0..8192u32).map(|item| (item * 7 % 253) as u8).collect();
+        let mut target = source.clone();
+        target[100..110].copy_from_slice(b"0123456789");
+        target[112] ^= 0xff;
+        target[5000] ^= 0x10;
+        target[8191] ^= 0x01;
+        (source, target)
+    }
+
+    #[test]
+    fn new_apply() {
+        let (source, target) = firmware();
+        let patch = XorPatch::new(&source, &target).unwrap();
+        assert_eq!(patch.runs.len(), 3);
+        assert_eq!(patch.apply(&source), Some(target.clone()));
+        let mut buffer = source.clone();
+        patch.apply_in_place(&mut buffer).unwrap();
+        assert_eq!(buffer, target);
+        assert_eq!(patch.apply(&source[1..]), None);
+        assert_eq!(XorPatch::new(&source, &target[1..]), None);
+        assert!(patch.to_bytes().len() < Patch::new(&source, &target).to_bytes().len());
+    }
+
+    #[test]
+    fn patch_conversion() {
+        let (source, target) = firmware();
+        let xor_patch = XorPatch::new(&source, &target).unwrap();
+        let patch = xor_patch.to_patch(&source).unwrap();
+        assert_eq!(patch.apply(&source), Some(target.clone()));
+        assert_eq!(XorPatch::from_patch(&patch, &source), Some(xor_patch));
+        assert_eq!(
+            XorPatch::from_patch(&Patch::new(b"ab", b"abc"), b"ab"),
+            None
+        );
+    }
+
+    #[test]
+    fn bytes() {
+        let (source, target) = firmware();
+        let patch = XorPatch::new(&source, &target).unwrap();
+        let bytes = patch.to_bytes();
+        assert_eq!(XorPatch::try_from_bytes(&bytes), Ok(patch));
+        assert_eq!(
+            XorPatch::try_from_bytes(&bytes[..bytes.len() - 1]),
+            Err(InstructionError::MissingContent)
+        );
+        assert_eq!(
+            XorPatch::try_from_bytes(&bytes[..5]),
+            Err(InstructionError::MissingLength)
+        );
+        assert_eq!(
+            XorPatch::try_from_bytes(b"+"),
+            Err(InstructionError::InvalidSign)
+        );
+        let mut short = XorPatch::new(b"abc", b"abd").unwrap().to_bytes();
+        short[16] = 3;
+        assert_eq!(
+            XorPatch::try_from_bytes(&short),
+            Err(InstructionError::InvalidLength)
+        );
+
+        let mut overflow = vec![XOR_PATCH_SIGN];
+        overflow.extend(u64::MAX.to_be_bytes());
+        overflow.extend((u64::MAX - 1).to_be_bytes());
+        overflow.extend(5u32.to_be_bytes());
+        overflow.extend([0; 5]);
+        assert_eq!(
+            XorPatch::try_from_bytes(&overflow),
+            Err(InstructionError::InvalidLength)
+        );
+    }
+}